@@ -0,0 +1,287 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+// time of the frame being rendered, in seconds
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameTime {
+	pub time: f32,
+	pub delta: f32,
+	pub frame: u64,
+}
+
+pub struct FrameClock {
+	start: Instant,
+	last: Option<Instant>,
+	current: FrameTime,
+	// advance by a fixed delta instead of the wall clock
+	step: Option<f32>,
+}
+
+impl Default for FrameClock {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl FrameClock {
+	pub fn new() -> Self {
+		Self {
+			start: Instant::now(),
+			last: None,
+			current: FrameTime::default(),
+			step: None,
+		}
+	}
+
+	// every frame is `step` seconds after the previous one, for offscreen
+	// rendering where frames take arbitrarily long
+	pub fn fixed(step: f32) -> Self {
+		Self {
+			step: Some(step),
+			..Self::new()
+		}
+	}
+
+	// start a new frame, the first delta is zero
+	pub fn tick(&mut self) -> FrameTime {
+		if let Some(step) = self.step {
+			let frame = match self.last {
+				Some(_) => self.current.frame + 1,
+				None => 0,
+			};
+			self.last = Some(Instant::now());
+			self.current = FrameTime {
+				time: frame as f32 * step,
+				delta: if frame == 0 { 0.0 } else { step },
+				frame,
+			};
+			return self.current;
+		}
+		let now = Instant::now();
+		let (delta, frame) = match self.last {
+			Some(last) => (now - last, self.current.frame + 1),
+			None => (Duration::ZERO, 0),
+		};
+		self.last = Some(now);
+		self.current = FrameTime {
+			time: (now - self.start).as_secs_f32(),
+			delta: delta.as_secs_f32(),
+			frame,
+		};
+		self.current
+	}
+
+	pub fn now(&self) -> FrameTime {
+		self.current
+	}
+}
+
+#[derive(Clone, Copy)]
+pub enum Easing {
+	Linear,
+	QuadIn,
+	QuadOut,
+	QuadInOut,
+	CubicIn,
+	CubicOut,
+	CubicInOut,
+	SineInOut,
+	// overshoots slightly before settling
+	BackOut,
+	Custom(fn(f32) -> f32),
+}
+
+impl Easing {
+	// maps progress in [0, 1] to eased progress, 0 and 1 are fixed points
+	pub fn apply(self, t: f32) -> f32 {
+		let t = t.clamp(0.0, 1.0);
+		match self {
+			Self::Linear => t,
+			Self::QuadIn => t * t,
+			Self::QuadOut => t * (2.0 - t),
+			Self::QuadInOut => if t < 0.5 {
+				2.0 * t * t
+			} else {
+				1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+			},
+			Self::CubicIn => t * t * t,
+			Self::CubicOut => 1.0 - (1.0 - t).powi(3),
+			Self::CubicInOut => if t < 0.5 {
+				4.0 * t * t * t
+			} else {
+				1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+			},
+			Self::SineInOut => -((std::f32::consts::PI * t).cos() - 1.0) / 2.0,
+			Self::BackOut => {
+				let c1 = 1.70158;
+				let c3 = c1 + 1.0;
+				1.0 + c3 * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2)
+			}
+			Self::Custom(f) => f(t),
+		}
+	}
+}
+
+pub trait Lerp: Copy {
+	fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+	fn lerp(self, other: Self, t: f32) -> Self {
+		self + (other - self) * t
+	}
+}
+
+impl<const N: usize> Lerp for [f32; N] {
+	fn lerp(mut self, other: Self, t: f32) -> Self {
+		for (a, b) in self.iter_mut().zip(other.iter()) {
+			*a = a.lerp(*b, t);
+		}
+		self
+	}
+}
+
+#[derive(Clone, Copy)]
+pub struct Tween<T: Lerp> {
+	pub from: T,
+	pub to: T,
+	// in seconds
+	pub duration: f32,
+	pub easing: Easing,
+}
+
+impl<T: Lerp> Tween<T> {
+	pub fn new(from: T, to: T, duration: f32) -> Self {
+		Self {
+			from,
+			to,
+			duration,
+			easing: Easing::Linear,
+		}
+	}
+
+	pub fn easing(mut self, easing: Easing) -> Self {
+		self.easing = easing;
+		self
+	}
+
+	pub fn value(&self, time: f32) -> T {
+		let t = if self.duration > 0.0 {
+			time / self.duration
+		} else {
+			1.0
+		};
+		self.from.lerp(self.to, self.easing.apply(t))
+	}
+
+	// drive a property of `layer`, `set` is called with the value every frame
+	pub fn apply<L, F>(self, layer: Arc<RwLock<L>>, set: F) -> Property<L, T>
+	where
+		L: ?Sized + Send + Sync,
+		F: FnMut(&mut L, T) + Send + 'static,
+		T: Send + 'static,
+	{
+		Property {
+			layer,
+			tween: self,
+			set: Box::new(set),
+		}
+	}
+}
+
+// anything with a fixed duration whose state is a function of time
+pub trait Animation: Send {
+	fn duration(&self) -> f32;
+	// apply the state `time` seconds after the start, clamped to the duration
+	fn seek(&mut self, time: f32);
+}
+
+type Setter<L, T> = Box<dyn FnMut(&mut L, T) + Send>;
+
+pub struct Property<L: ?Sized, T: Lerp> {
+	layer: Arc<RwLock<L>>,
+	tween: Tween<T>,
+	set: Setter<L, T>,
+}
+
+impl<L: ?Sized + Send + Sync, T: Lerp + Send> Animation for Property<L, T> {
+	fn duration(&self) -> f32 {
+		self.tween.duration
+	}
+
+	fn seek(&mut self, time: f32) {
+		let value = self.tween.value(time.clamp(0.0, self.tween.duration));
+		let mut layer = self.layer.write().unwrap();
+		(self.set)(&mut *layer, value);
+	}
+}
+
+// animations placed at start times, an animation is only applied once
+// started so sequenced animations of the same property do not fight
+#[derive(Default)]
+pub struct Timeline {
+	// sorted by start time
+	entries: Vec<(f32, Box<dyn Animation>)>,
+	last_start: f32,
+}
+
+impl Timeline {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	// start at `time` seconds
+	pub fn at(mut self, time: f32, animation: impl Animation + 'static) -> Self {
+		let idx = self.entries.partition_point(|entry| entry.0 <= time);
+		self.entries.insert(idx, (time, Box::new(animation)));
+		self.last_start = time;
+		self
+	}
+
+	// start after everything added so far has finished
+	pub fn then(self, animation: impl Animation + 'static) -> Self {
+		let end = self.duration();
+		self.at(end, animation)
+	}
+
+	// start together with the last added animation
+	pub fn with(self, animation: impl Animation + 'static) -> Self {
+		let start = self.last_start;
+		self.at(start, animation)
+	}
+}
+
+impl Animation for Timeline {
+	fn duration(&self) -> f32 {
+		self.entries
+			.iter()
+			.map(|(start, animation)| start + animation.duration())
+			.fold(0.0, f32::max)
+	}
+
+	fn seek(&mut self, time: f32) {
+		for (start, animation) in self.entries.iter_mut() {
+			if time >= *start {
+				animation.seek(time - *start);
+			}
+		}
+	}
+}
+
+// an animation being played, see `LayerCompositor::animate`
+pub struct Playing {
+	// damaged every frame until finished
+	pub layers: Vec<usize>,
+	// frame time of the first frame, frames can be far apart when idle
+	pub start: Option<f32>,
+	pub animation: Box<dyn Animation>,
+}
+
+impl Playing {
+	// false once the final state has been applied
+	pub fn advance(&mut self, time: &FrameTime) -> bool {
+		let elapsed = time.time - *self.start.get_or_insert(time.time);
+		self.animation.seek(elapsed);
+		elapsed < self.animation.duration()
+	}
+}
@@ -0,0 +1,193 @@
+use std::time::{Duration, Instant};
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopProxy};
+use winit::platform::run_return::EventLoopExtRunReturn;
+use winit::window::{Window, WindowBuilder};
+use winit::dpi::PhysicalSize;
+
+use crate::base::{Base, BaseRef};
+use crate::compositor::LayerCompositor;
+use crate::layer::perf_hud::PerfHud;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Redraw {
+	// only after input, resize, animation or `App::request_redraw`
+	OnDemand,
+	Continuous,
+}
+
+#[derive(Clone, Debug)]
+pub struct AppConfig {
+	pub title: String,
+	pub size: [u32; 2],
+	pub redraw: Redraw,
+	// none: as fast as presentation allows
+	pub max_fps: Option<f32>,
+	// toggles the performance overlay, none to disable it. the overlay needs
+	// `hud_font`, which is not shipped with the crate
+	pub hud_key: Option<VirtualKeyCode>,
+	// monospace font atlas of the overlay, loaded on first toggle
+	pub hud_font: String,
+}
+
+impl Default for AppConfig {
+	fn default() -> Self {
+		Self {
+			title: "vkwh".to_string(),
+			size: [800, 600],
+			redraw: Redraw::OnDemand,
+			max_fps: None,
+			hud_key: None,
+			hud_font: "assets/images/font.png".to_string(),
+		}
+	}
+}
+
+pub enum AppEvent<'a, E> {
+	// after the compositor has routed it to the layers
+	Window(&'a WindowEvent<'a>),
+	// sent through `App::proxy`, possibly from another thread
+	User(E),
+	// right before a frame is rendered, see `LayerCompositor::frame_time`
+	Frame,
+}
+
+pub struct App<E: 'static> {
+	// dropped in this order, layers owned by the compositor go before the
+	// base they were created with, the window goes last
+	pub compositor: LayerCompositor,
+	pub base: BaseRef,
+	pub window: Window,
+	proxy: EventLoopProxy<E>,
+	redraw: bool,
+	exit: bool,
+}
+
+impl<E: 'static> App<E> {
+	pub fn request_redraw(&mut self) {
+		self.redraw = true;
+	}
+
+	pub fn exit(&mut self) {
+		self.exit = true;
+	}
+
+	// wakes up the event loop with `AppEvent::User`
+	pub fn proxy(&self) -> EventLoopProxy<E> {
+		self.proxy.clone()
+	}
+}
+
+// create the window, base and compositor, then run the event loop until the
+// window is closed or `App::exit` is called. `setup` builds the layers and
+// returns the application state, which is dropped before the compositor
+pub fn run<E, S, F, U>(config: AppConfig, setup: F, mut update: U)
+where
+	E: 'static,
+	F: FnOnce(&mut App<E>) -> S,
+	U: FnMut(&mut App<E>, &mut S, AppEvent<E>),
+{
+	let mut el = EventLoop::<E>::with_user_event();
+	let window = WindowBuilder::new()
+		.with_title(&config.title)
+		.with_inner_size(PhysicalSize::new(config.size[0], config.size[1]))
+		.build(&el)
+		.unwrap();
+	let base = Base::new_ref(&window);
+	let mut app = App {
+		compositor: LayerCompositor::new(base.clone()),
+		base,
+		window,
+		proxy: el.create_proxy(),
+		redraw: true,
+		exit: false,
+	};
+	let mut state = setup(&mut app);
+	let frame_interval = config.max_fps.map(|fps| Duration::from_secs_f32(1.0 / fps));
+	let mut last_frame: Option<Instant> = None;
+
+	el.run_return(|event, _, control_flow| {
+		match event {
+			Event::WindowEvent { event, .. } => {
+				match &event {
+					WindowEvent::CloseRequested => app.exit = true,
+					WindowEvent::Resized(size) => {
+						app.compositor.resize(size.width, size.height);
+						app.redraw = true;
+					}
+					WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+						app.compositor.resize(new_inner_size.width, new_inner_size.height);
+						app.redraw = true;
+					}
+					WindowEvent::KeyboardInput {
+						input: KeyboardInput {
+							state: ElementState::Pressed,
+							virtual_keycode: Some(key),
+							..
+						},
+						..
+					} if config.hud_key == Some(*key) => {
+						toggle_hud(&mut app, &config.hud_font);
+						app.redraw = true;
+					}
+					event => if app.compositor.handle_event(event) {
+						app.redraw = true;
+					}
+				}
+				update(&mut app, &mut state, AppEvent::Window(&event));
+			}
+			Event::UserEvent(event) => update(&mut app, &mut state, AppEvent::User(event)),
+			Event::MainEventsCleared => {
+				let wanted = app.redraw
+					|| config.redraw == Redraw::Continuous
+					|| app.compositor.is_animating()
+					|| app.compositor.is_stale();
+				let next_frame = match (frame_interval, last_frame) {
+					(Some(interval), Some(last)) => Some(last + interval),
+					_ => None,
+				};
+				*control_flow = match next_frame {
+					Some(next_frame) if wanted && Instant::now() < next_frame => {
+						ControlFlow::WaitUntil(next_frame)
+					}
+					_ => {
+						if wanted {
+							app.window.request_redraw();
+						}
+						ControlFlow::Wait
+					}
+				};
+			}
+			Event::RedrawRequested(_) => {
+				update(&mut app, &mut state, AppEvent::Frame);
+				app.redraw = false;
+				last_frame = Some(Instant::now());
+				app.compositor.render();
+			}
+			_ => {},
+		}
+		if app.exit {
+			*control_flow = ControlFlow::Exit;
+		}
+	});
+
+	drop(state);
+	drop(app);
+}
+
+fn toggle_hud<E>(app: &mut App<E>, font: &str) {
+	if app.compositor.has_hud() {
+		app.compositor.toggle_hud();
+		return;
+	}
+	// the overlay stays off without its font
+	let font = match image::open(font) {
+		Ok(font) => font.into_luma8(),
+		Err(e) => {
+			eprintln!("hud font {}: {}", font, e);
+			return;
+		}
+	};
+	let hud = PerfHud::new(app.base.clone(), font);
+	app.compositor.set_hud(hud);
+}
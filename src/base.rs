@@ -2,6 +2,7 @@ use ash::extensions::{
 	ext::DebugUtils,
 	khr::{Surface, Swapchain},
 };
+use ash::prelude::VkResult;
 use ash::{Device, Instance};
 use ash::{vk, Entry};
 use std::borrow::Cow;
@@ -12,6 +13,8 @@ use std::os::raw::c_char;
 use std::sync::{Arc, RwLock};
 use winit::window::Window;
 
+use crate::tracker::{ImageTracker, ImageTrackerRef, ImageUsage};
+
 #[macro_export]
 macro_rules! offset_of {
 	($base:path, $field:ident) => {{
@@ -122,6 +125,265 @@ pub fn find_memorytype_index(
 		.map(|(index, _memory_type)| index as _)
 }
 
+// `size` is used when the surface leaves the extent to the swapchain
+unsafe fn create_swapchain(
+	surface_loader: &Surface,
+	swapchain_loader: &Swapchain,
+	pdevice: vk::PhysicalDevice,
+	surface: vk::SurfaceKHR,
+	surface_format: vk::SurfaceFormatKHR,
+	size: vk::Extent2D,
+	old_swapchain: vk::SwapchainKHR,
+) -> (vk::SwapchainKHR, vk::Extent2D) {
+	let surface_capabilities = surface_loader
+		.get_physical_device_surface_capabilities(pdevice, surface)
+		.unwrap();
+	let surface_resolution = match surface_capabilities.current_extent.width {
+		std::u32::MAX => size,
+		_ => surface_capabilities.current_extent,
+	};
+	let pre_transform = if surface_capabilities
+		.supported_transforms
+		.contains(vk::SurfaceTransformFlagsKHR::IDENTITY)
+	{
+		vk::SurfaceTransformFlagsKHR::IDENTITY
+	} else {
+		surface_capabilities.current_transform
+	};
+	let present_modes = surface_loader
+		.get_physical_device_surface_present_modes(pdevice, surface)
+		.unwrap();
+	let present_mode = present_modes
+		.iter()
+		.cloned()
+		.find(|&mode| mode == vk::PresentModeKHR::MAILBOX)
+		.unwrap_or(vk::PresentModeKHR::FIFO);
+
+	let swapchain_create_info = vk::SwapchainCreateInfoKHR::default()
+		.surface(surface)
+		.min_image_count(surface_capabilities.min_image_count)
+		.image_color_space(surface_format.color_space)
+		.image_format(surface_format.format)
+		.image_extent(surface_resolution)
+		.image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT |
+			vk::ImageUsageFlags::TRANSFER_SRC |
+			vk::ImageUsageFlags::TRANSFER_DST
+		)
+		.image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+		.pre_transform(pre_transform)
+		.composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+		.present_mode(present_mode)
+		.clipped(true)
+		.image_array_layers(1)
+		.old_swapchain(old_swapchain);
+	let swapchain = swapchain_loader
+		.create_swapchain(&swapchain_create_info, None)
+		.unwrap();
+	(swapchain, surface_resolution)
+}
+
+unsafe fn create_present_image_views(
+	device: &Device,
+	present_images: &[vk::Image],
+	surface_format: vk::SurfaceFormatKHR,
+) -> Vec<vk::ImageView> {
+	present_images
+		.iter()
+		.map(|&image| {
+			let create_view_info = vk::ImageViewCreateInfo::default()
+				.view_type(vk::ImageViewType::TYPE_2D)
+				.format(surface_format.format)
+				.components(vk::ComponentMapping {
+					r: vk::ComponentSwizzle::R,
+					g: vk::ComponentSwizzle::G,
+					b: vk::ComponentSwizzle::B,
+					a: vk::ComponentSwizzle::A,
+				})
+				.subresource_range(vk::ImageSubresourceRange {
+					aspect_mask: vk::ImageAspectFlags::COLOR,
+					base_mip_level: 0,
+					level_count: 1,
+					base_array_layer: 0,
+					layer_count: 1,
+				})
+				.image(image);
+			device.create_image_view(&create_view_info, None).unwrap()
+		})
+		.collect()
+}
+
+// images standing in for the swapchain when rendering without a window
+unsafe fn create_offscreen_images(
+	device: &Device,
+	device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+	surface_format: vk::SurfaceFormatKHR,
+	size: vk::Extent2D,
+) -> (Vec<vk::Image>, Vec<vk::DeviceMemory>) {
+	let image_create_info = vk::ImageCreateInfo::default()
+		.image_type(vk::ImageType::TYPE_2D)
+		.format(surface_format.format)
+		.extent(size.into())
+		.mip_levels(1)
+		.array_layers(1)
+		.samples(vk::SampleCountFlags::TYPE_1)
+		.tiling(vk::ImageTiling::OPTIMAL)
+		.usage(vk::ImageUsageFlags::COLOR_ATTACHMENT |
+			vk::ImageUsageFlags::TRANSFER_SRC |
+			vk::ImageUsageFlags::TRANSFER_DST
+		);
+	let image = device.create_image(&image_create_info, None).unwrap();
+	let memory_req = device.get_image_memory_requirements(image);
+	let memory_index = find_memorytype_index(
+		&memory_req,
+		device_memory_properties,
+		vk::MemoryPropertyFlags::DEVICE_LOCAL,
+	).unwrap();
+	let allocate_info = vk::MemoryAllocateInfo {
+		allocation_size: memory_req.size,
+		memory_type_index: memory_index,
+		..Default::default()
+	};
+	let memory = device.allocate_memory(&allocate_info, None).unwrap();
+	device.bind_image_memory(image, memory, 0).unwrap();
+	(vec![image], vec![memory])
+}
+
+// validation is only enabled when the layer is installed, so that headless
+// rendering works on bare drivers like lavapipe
+unsafe fn create_instance(
+	entry: &Entry,
+	mut extension_names: Vec<*const c_char>,
+) -> VkResult<Instance> {
+	let app_name = CStr::from_bytes_with_nul_unchecked(b"ash\0");
+	let validation = CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0");
+	let has_validation = entry
+		.enumerate_instance_layer_properties()
+		.unwrap()
+		.iter()
+		.any(|layer| CStr::from_ptr(layer.layer_name.as_ptr()) == validation);
+	let layers_names_raw: Vec<*const c_char> = if has_validation {
+		vec![validation.as_ptr()]
+	} else {
+		Vec::new()
+	};
+	extension_names.push(DebugUtils::name().as_ptr());
+	let appinfo = vk::ApplicationInfo::default()
+		.application_name(app_name)
+		.application_version(0)
+		.engine_name(app_name)
+		.engine_version(0)
+		.api_version(vk::make_api_version(0, 1, 0, 0));
+	let create_flags = vk::InstanceCreateFlags::default();
+	let create_info = vk::InstanceCreateInfo::default()
+		.application_info(&appinfo)
+		.enabled_layer_names(&layers_names_raw)
+		.enabled_extension_names(&extension_names)
+		.flags(create_flags);
+	entry.create_instance(&create_info, None)
+}
+
+unsafe fn create_debug_messenger(
+	entry: &Entry,
+	instance: &Instance,
+) -> (DebugUtils, vk::DebugUtilsMessengerEXT) {
+	let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
+		.message_severity(
+			vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+				| vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+		)
+		.message_type(
+			vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+				| vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+				| vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+		)
+		.pfn_user_callback(Some(vulkan_debug_callback));
+	let debug_utils_loader = DebugUtils::new(entry, instance);
+	let debug_call_back = debug_utils_loader
+		.create_debug_utils_messenger(&debug_info, None)
+		.unwrap();
+	(debug_utils_loader, debug_call_back)
+}
+
+unsafe fn create_device(
+	instance: &Instance,
+	pdevice: vk::PhysicalDevice,
+	queue_family_index: u32,
+	extension_names: &[*const c_char],
+) -> Device {
+	let supported = instance.get_physical_device_features(pdevice);
+	let features = vk::PhysicalDeviceFeatures {
+		shader_clip_distance: 1,
+		// point sizes above one
+		large_points: supported.large_points,
+		..Default::default()
+	};
+	let priorities = [1.0];
+
+	let queue_info = vk::DeviceQueueCreateInfo::default()
+		.queue_family_index(queue_family_index)
+		.queue_priorities(&priorities);
+	let device_create_info = vk::DeviceCreateInfo::default()
+		.queue_create_infos(std::slice::from_ref(&queue_info))
+		.enabled_extension_names(extension_names)
+		.enabled_features(&features);
+	instance
+		.create_device(pdevice, &device_create_info, None)
+		.unwrap()
+}
+
+struct Commands {
+	pool: vk::CommandPool,
+	setup_command_buffer: vk::CommandBuffer,
+	draw_command_buffer: vk::CommandBuffer,
+	draw_commands_reuse_fence: vk::Fence,
+	setup_commands_reuse_fence: vk::Fence,
+	present_complete_semaphore: vk::Semaphore,
+	rendering_complete_semaphore: vk::Semaphore,
+}
+
+unsafe fn create_commands(device: &Device, queue_family_index: u32) -> Commands {
+	let pool_create_info = vk::CommandPoolCreateInfo::default()
+		.flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+		.queue_family_index(queue_family_index);
+	let pool = device.create_command_pool(&pool_create_info, None).unwrap();
+	let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default()
+		.command_buffer_count(2)
+		.command_pool(pool)
+		.level(vk::CommandBufferLevel::PRIMARY);
+
+	let command_buffers = device
+		.allocate_command_buffers(&command_buffer_allocate_info)
+		.unwrap();
+
+	let fence_create_info =
+		vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+
+	let draw_commands_reuse_fence = device
+		.create_fence(&fence_create_info, None)
+		.expect("Create fence failed.");
+	let setup_commands_reuse_fence = device
+		.create_fence(&fence_create_info, None)
+		.expect("Create fence failed.");
+
+	let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+
+	let present_complete_semaphore = device
+		.create_semaphore(&semaphore_create_info, None)
+		.unwrap();
+	let rendering_complete_semaphore = device
+		.create_semaphore(&semaphore_create_info, None)
+		.unwrap();
+	Commands {
+		pool,
+		setup_command_buffer: command_buffers[0],
+		draw_command_buffer: command_buffers[1],
+		draw_commands_reuse_fence,
+		setup_commands_reuse_fence,
+		present_complete_semaphore,
+		rendering_complete_semaphore,
+	}
+}
+
 pub type BaseRef = Arc<RwLock<Base>>;
 pub struct Base {
 	pub entry: Entry,
@@ -155,6 +417,11 @@ pub struct Base {
 
 	pub draw_commands_reuse_fence: vk::Fence,
 	pub setup_commands_reuse_fence: vk::Fence,
+
+	pub image_tracker: ImageTrackerRef,
+
+	// backing the present images when headless, empty otherwise
+	pub offscreen_memory: Vec<vk::DeviceMemory>,
 }
 
 impl Base {
@@ -164,49 +431,11 @@ impl Base {
 
 	pub fn new(window: &Window) -> Self { unsafe {
 		let entry = Entry::linked();
-		let app_name = CStr::from_bytes_with_nul_unchecked(b"ash");
-		let layer_names = [CStr::from_bytes_with_nul_unchecked(
-			b"VK_LAYER_KHRONOS_validation\0",
-		)];
-		let layers_names_raw: Vec<*const c_char> = layer_names
-			.iter()
-			.map(|raw_name| raw_name.as_ptr())
-			.collect();
-		let mut extension_names = ash_window::enumerate_required_extensions(window)
+		let extension_names = ash_window::enumerate_required_extensions(window)
 			.unwrap()
 			.to_vec();
-		extension_names.push(DebugUtils::name().as_ptr());
-		let appinfo = vk::ApplicationInfo::default()
-			.application_name(app_name)
-			.application_version(0)
-			.engine_name(app_name)
-			.engine_version(0)
-			.api_version(vk::make_api_version(0, 1, 0, 0));
-		let create_flags = vk::InstanceCreateFlags::default();
-		let create_info = vk::InstanceCreateInfo::default()
-			.application_info(&appinfo)
-			.enabled_layer_names(&layers_names_raw)
-			.enabled_extension_names(&extension_names)
-			.flags(create_flags);
-		let instance: Instance = entry
-			.create_instance(&create_info, None)
-			.expect("Instance creation error");
-
-		let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
-			.message_severity(
-				vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
-					| vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
-			)
-			.message_type(
-				vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-					| vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-					| vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
-			)
-			.pfn_user_callback(Some(vulkan_debug_callback));
-		let debug_utils_loader = DebugUtils::new(&entry, &instance);
-		let debug_call_back = debug_utils_loader
-			.create_debug_utils_messenger(&debug_info, None)
-			.unwrap();
+		let instance = create_instance(&entry, extension_names).expect("Instance creation error");
+		let (debug_utils_loader, debug_call_back) = create_debug_messenger(&entry, &instance);
 
 		let surface = ash_window::create_surface(&entry, &instance, &window, None).unwrap();
 		let pdevices = instance
@@ -242,139 +471,35 @@ impl Base {
 			#[cfg(any(target_os = "macos", target_os = "ios"))]
 			KhrPortabilitySubsetFn::name().as_ptr(),
 		];
-		let features = vk::PhysicalDeviceFeatures {
-			shader_clip_distance: 1,
-			..Default::default()
-		};
-		let priorities = [1.0];
-
-		let queue_info = vk::DeviceQueueCreateInfo::default()
-			.queue_family_index(queue_family_index)
-			.queue_priorities(&priorities);
-		let device_create_info = vk::DeviceCreateInfo::default()
-			.queue_create_infos(std::slice::from_ref(&queue_info))
-			.enabled_extension_names(&device_extension_names_raw)
-			.enabled_features(&features);
-		let device: Device = instance
-			.create_device(pdevice, &device_create_info, None)
-			.unwrap();
+		let device = create_device(&instance, pdevice, queue_family_index, &device_extension_names_raw);
 		let present_queue = device.get_device_queue(queue_family_index as u32, 0);
 		let surface_format = surface_loader
 			.get_physical_device_surface_formats(pdevice, surface)
 			.unwrap()[0];
 		eprintln!("Format: {:?}", surface_format);
-		let surface_capabilities = surface_loader
-			.get_physical_device_surface_capabilities(pdevice, surface)
-			.unwrap();
-		let surface_resolution = match surface_capabilities.current_extent.width {
-			std::u32::MAX => vk::Extent2D {
-				width: 800,
-				height: 600,
-			},
-			_ => surface_capabilities.current_extent,
-		};
-		let render_resolution = vk::Extent2D {
-			width: 800,
-			height: 600,
-		};
-		let pre_transform = if surface_capabilities
-			.supported_transforms
-			.contains(vk::SurfaceTransformFlagsKHR::IDENTITY)
-		{
-			vk::SurfaceTransformFlagsKHR::IDENTITY
-		} else {
-			surface_capabilities.current_transform
-		};
-		let present_modes = surface_loader
-			.get_physical_device_surface_present_modes(pdevice, surface)
-			.unwrap();
-		let present_mode = present_modes
-			.iter()
-			.cloned()
-			.find(|&mode| mode == vk::PresentModeKHR::MAILBOX)
-			.unwrap_or(vk::PresentModeKHR::FIFO);
-
 		let swapchain_loader = Swapchain::new(&instance, &device);
-		let swapchain_create_info = vk::SwapchainCreateInfoKHR::default()
-			.surface(surface)
-			.min_image_count(surface_capabilities.min_image_count)
-			.image_color_space(surface_format.color_space)
-			.image_format(surface_format.format)
-			.image_extent(surface_resolution)
-			.image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT |
-				vk::ImageUsageFlags::TRANSFER_DST
-			)
-			.image_sharing_mode(vk::SharingMode::EXCLUSIVE)
-			.pre_transform(pre_transform)
-			.composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
-			.present_mode(present_mode)
-			.clipped(true)
-			.image_array_layers(1);
-		let swapchain = swapchain_loader
-			.create_swapchain(&swapchain_create_info, None)
-			.unwrap();
+		let (swapchain, surface_resolution) = create_swapchain(
+			&surface_loader,
+			&swapchain_loader,
+			pdevice,
+			surface,
+			surface_format,
+			vk::Extent2D {
+				width: window.inner_size().width,
+				height: window.inner_size().height,
+			},
+			vk::SwapchainKHR::null(),
+		);
+		let render_resolution = surface_resolution;
 
-		let pool_create_info = vk::CommandPoolCreateInfo::default()
-			.flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
-			.queue_family_index(queue_family_index);
-		let pool = device.create_command_pool(&pool_create_info, None).unwrap();
-		let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default()
-			.command_buffer_count(2)
-			.command_pool(pool)
-			.level(vk::CommandBufferLevel::PRIMARY);
-
-		let command_buffers = device
-			.allocate_command_buffers(&command_buffer_allocate_info)
-			.unwrap();
-		let setup_command_buffer = command_buffers[0];
-		let draw_command_buffer = command_buffers[1];
+		let commands = create_commands(&device, queue_family_index);
 
 		let present_images = swapchain_loader.get_swapchain_images(swapchain).unwrap();
 		eprintln!("Swapchain images count: {}", present_images.len());
-		let present_image_views: Vec<vk::ImageView> = present_images
-			.iter()
-			.map(|&image| {
-				let create_view_info = vk::ImageViewCreateInfo::default()
-					.view_type(vk::ImageViewType::TYPE_2D)
-					.format(surface_format.format)
-					.components(vk::ComponentMapping {
-						r: vk::ComponentSwizzle::R,
-						g: vk::ComponentSwizzle::G,
-						b: vk::ComponentSwizzle::B,
-						a: vk::ComponentSwizzle::A,
-					})
-					.subresource_range(vk::ImageSubresourceRange {
-						aspect_mask: vk::ImageAspectFlags::COLOR,
-						base_mip_level: 0,
-						level_count: 1,
-						base_array_layer: 0,
-						layer_count: 1,
-					})
-					.image(image);
-				device.create_image_view(&create_view_info, None).unwrap()
-			})
-			.collect();
-	
-		let device_memory_properties = instance.get_physical_device_memory_properties(pdevice);
-
-		let fence_create_info =
-			vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+		let present_image_views = create_present_image_views(&device, &present_images, surface_format);
 
-		let draw_commands_reuse_fence = device
-			.create_fence(&fence_create_info, None)
-			.expect("Create fence failed.");
-		let setup_commands_reuse_fence = device
-			.create_fence(&fence_create_info, None)
-			.expect("Create fence failed.");
-
-		let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+		let device_memory_properties = instance.get_physical_device_memory_properties(pdevice);
 
-		let present_complete_semaphore = device
-			.create_semaphore(&semaphore_create_info, None)
-			.unwrap();
-		let rendering_complete_semaphore = device
-			.create_semaphore(&semaphore_create_info, None)
-			.unwrap();
 		Self {
 			entry,
 			instance,
@@ -391,19 +516,241 @@ impl Base {
 			swapchain,
 			present_images,
 			present_image_views,
-			pool,
-			draw_command_buffer,
-			setup_command_buffer,
-			present_complete_semaphore,
-			rendering_complete_semaphore,
-			draw_commands_reuse_fence,
-			setup_commands_reuse_fence,
+			pool: commands.pool,
+			draw_command_buffer: commands.draw_command_buffer,
+			setup_command_buffer: commands.setup_command_buffer,
+			present_complete_semaphore: commands.present_complete_semaphore,
+			rendering_complete_semaphore: commands.rendering_complete_semaphore,
+			draw_commands_reuse_fence: commands.draw_commands_reuse_fence,
+			setup_commands_reuse_fence: commands.setup_commands_reuse_fence,
+			image_tracker: ImageTracker::new_ref(),
 			surface,
 			debug_call_back,
 			debug_utils_loader,
+			offscreen_memory: Vec::new(),
 		}
+	}}
+
+	pub fn new_headless_ref(size: vk::Extent2D) -> BaseRef {
+		Arc::new(RwLock::new(Self::new_headless(size)))
 	}
-}}
+
+	// no surface or swapchain, the present images are plain RGBA images the
+	// compositor renders into, see `LayerCompositor::capture`
+	pub fn new_headless(size: vk::Extent2D) -> Self {
+		Self::try_new_headless(size).expect("Couldn't find suitable device.")
+	}
+
+	// none without a vulkan driver or a device with a graphics queue
+	pub fn try_new_headless(size: vk::Extent2D) -> Option<Self> { unsafe {
+		let entry = Entry::linked();
+		let instance = create_instance(&entry, Vec::new()).ok()?;
+		let found = instance
+			.enumerate_physical_devices()
+			.unwrap_or_default()
+			.into_iter()
+			.find_map(|pdevice| {
+				instance
+					.get_physical_device_queue_family_properties(pdevice)
+					.iter()
+					.position(|info| info.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+					.map(|index| (pdevice, index as u32))
+			});
+		let (pdevice, queue_family_index) = match found {
+			Some(found) => found,
+			None => {
+				instance.destroy_instance(None);
+				return None;
+			}
+		};
+		let (debug_utils_loader, debug_call_back) = create_debug_messenger(&entry, &instance);
+		let device = create_device(&instance, pdevice, queue_family_index, &[]);
+		let present_queue = device.get_device_queue(queue_family_index, 0);
+		let surface_format = vk::SurfaceFormatKHR {
+			format: vk::Format::R8G8B8A8_UNORM,
+			color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+		};
+		let surface_loader = Surface::new(&entry, &instance);
+		let swapchain_loader = Swapchain::new(&instance, &device);
+		let device_memory_properties = instance.get_physical_device_memory_properties(pdevice);
+		let (present_images, offscreen_memory) = create_offscreen_images(
+			&device,
+			&device_memory_properties,
+			surface_format,
+			size,
+		);
+		let present_image_views = create_present_image_views(&device, &present_images, surface_format);
+		let commands = create_commands(&device, queue_family_index);
+		Some(Self {
+			entry,
+			instance,
+			device,
+			queue_family_index,
+			pdevice,
+			device_memory_properties,
+			surface_loader,
+			surface_format,
+			present_queue,
+			surface_resolution: size,
+			render_resolution: size,
+			swapchain_loader,
+			swapchain: vk::SwapchainKHR::null(),
+			present_images,
+			present_image_views,
+			pool: commands.pool,
+			draw_command_buffer: commands.draw_command_buffer,
+			setup_command_buffer: commands.setup_command_buffer,
+			present_complete_semaphore: commands.present_complete_semaphore,
+			rendering_complete_semaphore: commands.rendering_complete_semaphore,
+			draw_commands_reuse_fence: commands.draw_commands_reuse_fence,
+			setup_commands_reuse_fence: commands.setup_commands_reuse_fence,
+			image_tracker: ImageTracker::new_ref(),
+			surface: vk::SurfaceKHR::null(),
+			debug_call_back,
+			debug_utils_loader,
+			offscreen_memory,
+		})
+	}}
+
+	pub fn is_headless(&self) -> bool {
+		self.surface == vk::SurfaceKHR::null()
+	}
+
+	// copy `image` into host memory as tightly packed RGBA8, waits for the
+	// copy to complete
+	pub fn read_image(&self, image: vk::Image) -> image::RgbaImage { unsafe {
+		let device = &self.device;
+		let extent = self.render_resolution;
+		let size = extent.width as u64 * extent.height as u64 * 4;
+		let buffer_info = vk::BufferCreateInfo {
+			size,
+			usage: vk::BufferUsageFlags::TRANSFER_DST,
+			sharing_mode: vk::SharingMode::EXCLUSIVE,
+			..Default::default()
+		};
+		let buffer = device.create_buffer(&buffer_info, None).unwrap();
+		let memory_req = device.get_buffer_memory_requirements(buffer);
+		let memory_index = find_memorytype_index(
+			&memory_req,
+			&self.device_memory_properties,
+			vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+		).unwrap();
+		let allocate_info = vk::MemoryAllocateInfo {
+			allocation_size: memory_req.size,
+			memory_type_index: memory_index,
+			..Default::default()
+		};
+		let memory = device.allocate_memory(&allocate_info, None).unwrap();
+		device.bind_buffer_memory(buffer, memory, 0).unwrap();
+
+		record_submit_commandbuffer(
+			device,
+			self.setup_command_buffer,
+			self.setup_commands_reuse_fence,
+			self.present_queue,
+			&[],
+			&[],
+			&[],
+			|device, command_buffer| {
+				self.image_tracker.lock().unwrap().require(
+					device,
+					command_buffer,
+					image,
+					ImageUsage::TransferSrc,
+				);
+				let region = vk::BufferImageCopy {
+					image_subresource: vk::ImageSubresourceLayers {
+						aspect_mask: vk::ImageAspectFlags::COLOR,
+						mip_level: 0,
+						base_array_layer: 0,
+						layer_count: 1,
+					},
+					image_extent: extent.into(),
+					..Default::default()
+				};
+				device.cmd_copy_image_to_buffer(
+					command_buffer,
+					image,
+					vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+					buffer,
+					&[region],
+				);
+			},
+		);
+		device
+			.wait_for_fences(&[self.setup_commands_reuse_fence], true, std::u64::MAX)
+			.unwrap();
+
+		let ptr = device
+			.map_memory(memory, 0, size, vk::MemoryMapFlags::empty())
+			.unwrap();
+		let data = std::slice::from_raw_parts(ptr as *const u8, size as usize).to_vec();
+		device.unmap_memory(memory);
+		device.destroy_buffer(buffer, None);
+		device.free_memory(memory, None);
+		image::RgbaImage::from_raw(extent.width, extent.height, data).unwrap()
+	}}
+}
+
+impl Base {
+	// after a resize or an out of date swapchain, everything using the present
+	// images or the resolution has to be rebuilt, see `LayerCompositor::resize`
+	pub fn recreate_swapchain(&mut self, size: vk::Extent2D) { unsafe {
+		self.device.device_wait_idle().unwrap();
+		{
+			let mut tracker = self.image_tracker.lock().unwrap();
+			for &image in self.present_images.iter() {
+				tracker.forget(image);
+			}
+		}
+		for image_view in self.present_image_views.drain(..) {
+			self.device.destroy_image_view(image_view, None);
+		}
+		if self.is_headless() {
+			for &image in self.present_images.iter() {
+				self.device.destroy_image(image, None);
+			}
+			for memory in self.offscreen_memory.drain(..) {
+				self.device.free_memory(memory, None);
+			}
+			let (images, memory) = create_offscreen_images(
+				&self.device,
+				&self.device_memory_properties,
+				self.surface_format,
+				size,
+			);
+			self.surface_resolution = size;
+			self.render_resolution = size;
+			self.present_images = images;
+			self.offscreen_memory = memory;
+			self.present_image_views = create_present_image_views(
+				&self.device,
+				&self.present_images,
+				self.surface_format,
+			);
+			return;
+		}
+		let (swapchain, surface_resolution) = create_swapchain(
+			&self.surface_loader,
+			&self.swapchain_loader,
+			self.pdevice,
+			self.surface,
+			self.surface_format,
+			size,
+			self.swapchain,
+		);
+		self.swapchain_loader.destroy_swapchain(self.swapchain, None);
+		self.swapchain = swapchain;
+		self.surface_resolution = surface_resolution;
+		self.render_resolution = surface_resolution;
+		self.present_images = self.swapchain_loader.get_swapchain_images(swapchain).unwrap();
+		self.present_image_views = create_present_image_views(
+			&self.device,
+			&self.present_images,
+			self.surface_format,
+		);
+	}}
+}
 
 impl Drop for Base {
 	fn drop(&mut self) {
@@ -421,10 +768,21 @@ impl Drop for Base {
 				self.device.destroy_image_view(image_view, None);
 			}
 			self.device.destroy_command_pool(self.pool, None);
-			self.swapchain_loader
-				.destroy_swapchain(self.swapchain, None);
+			if self.is_headless() {
+				for &image in self.present_images.iter() {
+					self.device.destroy_image(image, None);
+				}
+				for &memory in self.offscreen_memory.iter() {
+					self.device.free_memory(memory, None);
+				}
+			} else {
+				self.swapchain_loader
+					.destroy_swapchain(self.swapchain, None);
+			}
 			self.device.destroy_device(None);
-			self.surface_loader.destroy_surface(self.surface, None);
+			if !self.is_headless() {
+				self.surface_loader.destroy_surface(self.surface, None);
+			}
 			self.debug_utils_loader
 				.destroy_debug_utils_messenger(self.debug_call_back, None);
 			self.instance.destroy_instance(None);
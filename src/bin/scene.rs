@@ -0,0 +1,46 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use vkwh::app::{self, App, AppConfig, AppEvent};
+use vkwh::scene::{Scene, SceneWatcher};
+
+// the scene file changed on disk
+struct Reload;
+
+fn load(app: &mut App<Reload>, path: &Path) {
+	let dir = path.parent().unwrap_or(Path::new(""));
+	let result = Scene::load(path)
+		.and_then(|scene| scene.build(&app.base, &mut app.compositor, dir));
+	match result {
+		Ok(()) => app.request_redraw(),
+		// keep showing the previous scene
+		Err(e) => eprintln!("{}", e),
+	}
+}
+
+fn main() {
+	let path: PathBuf = std::env::args()
+		.nth(1)
+		.expect("usage: scene <file.ron>")
+		.into();
+	let config = AppConfig {
+		title: path.display().to_string(),
+		..Default::default()
+	};
+	app::run(config, |app: &mut App<Reload>| {
+		load(app, &path);
+		let proxy = app.proxy();
+		let mut watcher = SceneWatcher::new(path.clone());
+		std::thread::spawn(move || loop {
+			std::thread::sleep(Duration::from_millis(250));
+			if watcher.changed() && proxy.send_event(Reload).is_err() {
+				break;
+			}
+		});
+		path
+	}, |app, path, event| {
+		if let AppEvent::User(Reload) = event {
+			load(app, path);
+		}
+	});
+}
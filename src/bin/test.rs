@@ -1,17 +1,126 @@
-use winit::event_loop::{ControlFlow, EventLoop};
-use winit::event::{Event, WindowEvent, VirtualKeyCode as Kc};
-use winit::window::WindowBuilder;
-use winit::platform::run_return::EventLoopExtRunReturn;
+use std::sync::{Arc, RwLock};
+use ash::vk;
+use winit::event::{MouseButton, VirtualKeyCode as Kc};
 
-use vkwh::base::*;
-use vkwh::compositor::LayerCompositor as Vkc;
+use vkwh::animation::{Easing, Tween};
+use vkwh::app::{self, AppConfig};
+use vkwh::input::{InputContext, InputEvent, InputHandler, Rect};
+use vkwh::layer::{Layer, RenderTarget};
 use vkwh::layer::triangles::{Triangles, Vertex};
 use vkwh::layer::monotext::Monotext;
 use vkwh::layer::monotext::label_stack::line::Line;
 use vkwh::layer::image_viewer::ImageViewer;
+use vkwh::layer::pipeline::{BlendMode, LoadOp};
 
 enum CustomEvent {}
 
+// moves the first triangle with the pointer, hjkl pans and scrolling zooms
+// the camera once clicked
+struct Draggable {
+	triangles: Triangles,
+	grab: Option<[f32; 2]>,
+}
+
+impl Draggable {
+	fn translate(&mut self, dx: f32, dy: f32) {
+		for vertex in self.triangles.vertices.iter_mut().take(3) {
+			vertex.pos[0] += dx;
+			vertex.pos[1] += dy;
+		}
+	}
+
+	fn pan(&mut self, dx: f32, dy: f32) {
+		let camera = &mut self.triangles.camera;
+		camera.center[0] += dx / camera.zoom;
+		camera.center[1] += dy / camera.zoom;
+	}
+}
+
+impl Layer for Draggable {
+	fn set_output(&mut self, image: Vec<vk::Image>) {
+		self.triangles.set_output(image);
+	}
+
+	fn resize(&mut self, extent: vk::Extent2D) {
+		self.triangles.resize(extent);
+	}
+
+	fn prepare(&mut self, idx: usize) -> bool {
+		self.triangles.prepare(idx)
+	}
+
+	fn render(&self, command_buffer: vk::CommandBuffer, idx: usize) {
+		self.triangles.render(command_buffer, idx);
+	}
+
+	fn target(&self, idx: usize) -> Option<RenderTarget> {
+		self.triangles.target(idx)
+	}
+
+	fn input(&mut self) -> Option<&mut dyn InputHandler> {
+		Some(self)
+	}
+}
+
+impl InputHandler for Draggable {
+	fn bounds(&self) -> Option<Rect> {
+		let mut rect = Rect {
+			min: [f32::MAX; 2],
+			max: [f32::MIN; 2],
+		};
+		for vertex in self.triangles.vertices.iter().take(3) {
+			for i in 0..2 {
+				rect.min[i] = rect.min[i].min(vertex.pos[i]);
+				rect.max[i] = rect.max[i].max(vertex.pos[i]);
+			}
+		}
+		Some(rect)
+	}
+
+	// positions are in world coordinates of the camera
+	fn to_local(&self, position: [f32; 2]) -> [f32; 2] {
+		self.triangles.to_world(position)
+	}
+
+	fn handle(&mut self, event: &InputEvent, ctx: &mut InputContext) -> bool {
+		let dx = 0.1;
+		match *event {
+			InputEvent::PointerButton { position, button: MouseButton::Left, pressed } => {
+				if pressed {
+					self.grab = Some(position);
+					ctx.capture();
+					ctx.focus();
+				} else {
+					self.grab = None;
+					ctx.release();
+				}
+			}
+			InputEvent::PointerMoved { position } => match self.grab {
+				Some(grab) => {
+					self.translate(position[0] - grab[0], position[1] - grab[1]);
+					self.grab = Some(position);
+				}
+				None => return false,
+			}
+			InputEvent::Key { key: Some(key), pressed: true, .. } => match key {
+				Kc::H => self.pan(-dx, 0.0),
+				Kc::L => self.pan(dx, 0.0),
+				Kc::J => self.pan(0.0, dx),
+				Kc::K => self.pan(0.0, -dx),
+				_ => return false,
+			}
+			InputEvent::Scroll { position, delta } => {
+				let ndc = self.triangles.to_ndc(position);
+				let extent = self.triangles.extent();
+				self.triangles.camera.zoom_at(ndc, 1.1f32.powf(delta[1]), extent);
+			}
+			_ => return false,
+		}
+		ctx.update();
+		true
+	}
+}
+
 fn main() {
 	let vertices = vec![
 		Vertex {
@@ -43,82 +152,42 @@ fn main() {
 	iter.next();
 	let file = iter.next().unwrap();
 	let image = image::open(file).unwrap().into_rgba8();
-	let mut el = EventLoop::<CustomEvent>::with_user_event();
-	let window = WindowBuilder::new()
-		.build(&el)
-		.unwrap();
-	let base = Base::new_ref(&window);
-	
-	let layer_t = Triangles::new_ref(base.clone());
-	let layer_i = ImageViewer::new_ref(base.clone(), image);
-	let layer_m = Monotext::new_ref(
-		base.clone(),
-		image::open("assets/images/font.png").unwrap().into_luma8(),
-	);
-	let txt = "hello, world".to_string();
-	{
-		let mut layer_m = layer_m.write().unwrap();
-		layer_m.label_stack.scaler = 2.0;
-		layer_m.label_stack.add_text("1", Line::new_colored(
-			txt.bytes().collect(),
-			[1.0, 0.0, 1.0, 0.0],
-		));
-		layer_m.label_stack.add_text("2", Line::new_colored(
-			txt.bytes().collect(),
-			[1.0, 1.0, 0.0, 0.0],
-		));
-	}
-	layer_t.write().unwrap().vertices = vertices;
-	let mut vkc = Vkc::new(base.clone());
-	vkc.new_cached_layer(layer_t.clone());
-	vkc.new_layer(layer_m.clone());
-	//vkc.new_layer(layer_i.clone());
-	let dx = 0.1;
-	el.run_return(|event, _, control_flow| {
-		match event {
-			Event::WindowEvent {
-				event,
-				..
-			} => match event {
-				WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-				WindowEvent::KeyboardInput {
-					input,
-					..
-				} => match input.virtual_keycode {
-					Some(Kc::H) => {
-						for vertex in layer_t.write().unwrap().vertices.iter_mut().take(3) {
-							vertex.pos[0] -= dx;
-						}
-						window.request_redraw();
-					}
-					Some(Kc::L) => {
-						for vertex in layer_t.write().unwrap().vertices.iter_mut().take(3) {
-							vertex.pos[0] += dx;
-						}
-						window.request_redraw();
-					}
-					Some(Kc::J) => {
-						for vertex in layer_t.write().unwrap().vertices.iter_mut().take(3) {
-							vertex.pos[1] += dx;
-						}
-						window.request_redraw();
-					}
-					Some(Kc::K) => {
-						for vertex in layer_t.write().unwrap().vertices.iter_mut().take(3) {
-							vertex.pos[1] -= dx;
-						}
-						window.request_redraw();
-					}
-					_ => {},
-				}
-				_ => {},
-			}
-			Event::RedrawRequested(_) => {
-				vkc.update_all();
-				vkc.render();
-				*control_flow = ControlFlow::Wait;
-			}
-			_ => {},
+	app::run::<CustomEvent, _, _, _>(AppConfig::default(), |app| {
+		let base = app.base.clone();
+		// the vertex alpha blends over the layers below
+		let mut triangles = Triangles::with_modes(base.clone(), BlendMode::Alpha, LoadOp::Load, 100);
+		triangles.vertices = vertices;
+		let layer_t = Arc::new(RwLock::new(Draggable {
+			triangles,
+			grab: None,
+		}));
+		let layer_i = ImageViewer::new_ref(base.clone(), image);
+		let layer_m = Monotext::new_ref(
+			base.clone(),
+			image::open("assets/images/font.png").unwrap().into_luma8(),
+		);
+		let txt = "hello, world".to_string();
+		{
+			let mut layer_m = layer_m.write().unwrap();
+			layer_m.label_stack.add_text("1", Line::new_colored(
+				txt.bytes().collect(),
+				[1.0, 0.0, 1.0, 0.0],
+			));
+			layer_m.label_stack.add_text("2", Line::new_colored(
+				txt.bytes().collect(),
+				[1.0, 1.0, 0.0, 0.0],
+			));
 		}
-	});
+		let vkc = &mut app.compositor;
+		vkc.new_cached_layer(layer_t);
+		vkc.new_layer(layer_m.clone());
+		//vkc.new_layer(layer_i.clone());
+		vkc.animate(
+			&[1],
+			Tween::new(1.0, 2.0, 0.6)
+				.easing(Easing::BackOut)
+				.apply(layer_m, |layer, scaler| layer.label_stack.scaler = scaler),
+		);
+		layer_i
+	}, |_, _, _| {});
 }
@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+use ash::vk;
+
+use vkwh::animation::FrameClock;
+use vkwh::base::Base;
+use vkwh::compositor::LayerCompositor;
+use vkwh::scene::Scene;
+
+const USAGE: &str = "usage: vkwh-render <scene.ron> <out.png> [--size WxH] [--frames N] [--fps F]";
+
+struct Options {
+	scene: PathBuf,
+	output: PathBuf,
+	size: vk::Extent2D,
+	frames: usize,
+	fps: f32,
+}
+
+fn parse_args() -> Options {
+	let mut args = std::env::args().skip(1);
+	let mut positional = Vec::new();
+	let mut size = vk::Extent2D { width: 800, height: 600 };
+	let mut frames = 1;
+	let mut fps = 60.0;
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--size" => {
+				let value = args.next().expect(USAGE);
+				let (w, h) = value.split_once('x').expect(USAGE);
+				size = vk::Extent2D {
+					width: w.parse().expect(USAGE),
+					height: h.parse().expect(USAGE),
+				};
+			}
+			"--frames" => frames = args.next().expect(USAGE).parse().expect(USAGE),
+			"--fps" => fps = args.next().expect(USAGE).parse().expect(USAGE),
+			_ => positional.push(PathBuf::from(arg)),
+		}
+	}
+	let [scene, output]: [PathBuf; 2] = positional.try_into().expect(USAGE);
+	Options {
+		scene,
+		output,
+		size,
+		frames,
+		fps,
+	}
+}
+
+// out.png for a single frame, out_0000.png, out_0001.png, ... otherwise
+fn frame_path(output: &Path, frame: usize, frames: usize) -> PathBuf {
+	if frames == 1 {
+		return output.to_path_buf();
+	}
+	let stem = output.file_stem().unwrap().to_string_lossy();
+	let name = match output.extension() {
+		Some(ext) => format!("{}_{:04}.{}", stem, frame, ext.to_string_lossy()),
+		None => format!("{}_{:04}", stem, frame),
+	};
+	output.with_file_name(name)
+}
+
+fn main() {
+	let options = parse_args();
+	let base = Base::new_headless_ref(options.size);
+	let mut compositor = LayerCompositor::new(base.clone());
+	compositor.set_clock(FrameClock::fixed(1.0 / options.fps));
+	let dir = options.scene.parent().unwrap_or(Path::new(""));
+	let scene = Scene::load(&options.scene).unwrap_or_else(|e| panic!("{}", e));
+	scene
+		.build(&base, &mut compositor, dir)
+		.unwrap_or_else(|e| panic!("{}", e));
+	for frame in 0..options.frames {
+		compositor.render();
+		let path = frame_path(&options.output, frame, options.frames);
+		compositor.capture().save(&path).unwrap();
+		eprintln!("{}", path.display());
+	}
+	// layers go before the base they were created with
+	drop(compositor);
+	drop(base);
+}
@@ -0,0 +1,444 @@
+use serde::Deserialize;
+
+// maps world coordinates to normalized device coordinates of an output. the
+// default camera is the identity, so clip space vertices stay where they are
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera2D {
+	// world point shown at the center of the output
+	pub center: [f32; 2],
+	pub zoom: f32,
+	// of the view, counterclockwise in radians
+	pub rotation: f32,
+	// none: one world unit is half the output at zoom 1 like clip space,
+	// some: one world unit is this many pixels at zoom 1
+	pub pixels_per_unit: Option<f32>,
+}
+
+impl Default for Camera2D {
+	fn default() -> Self {
+		Self {
+			center: [0.0, 0.0],
+			zoom: 1.0,
+			rotation: 0.0,
+			pixels_per_unit: None,
+		}
+	}
+}
+
+impl Camera2D {
+	// world units are pixels, with the origin at the top left corner of an
+	// output of `extent`
+	pub fn pixels(extent: [f32; 2]) -> Self {
+		Self {
+			center: [extent[0] / 2.0, extent[1] / 2.0],
+			pixels_per_unit: Some(1.0),
+			..Default::default()
+		}
+	}
+
+	fn scale(&self, extent: [f32; 2]) -> [f32; 2] {
+		match self.pixels_per_unit {
+			Some(ppu) => [
+				self.zoom * ppu * 2.0 / extent[0].max(1.0),
+				self.zoom * ppu * 2.0 / extent[1].max(1.0),
+			],
+			None => [self.zoom, self.zoom],
+		}
+	}
+
+	// column major, for an output of `extent` pixels
+	pub fn matrix(&self, extent: [f32; 2]) -> [f32; 16] {
+		let [sx, sy] = self.scale(extent);
+		let (s, c) = self.rotation.sin_cos();
+		let [cx, cy] = self.center;
+		[
+			sx * c, -sy * s, 0.0, 0.0,
+			sx * s, sy * c, 0.0, 0.0,
+			0.0, 0.0, 1.0, 0.0,
+			-sx * (c * cx + s * cy), sy * (s * cx - c * cy), 0.0, 1.0,
+		]
+	}
+
+	pub fn to_ndc(&self, [x, y]: [f32; 2], extent: [f32; 2]) -> [f32; 2] {
+		let m = self.matrix(extent);
+		[m[0] * x + m[4] * y + m[12], m[1] * x + m[5] * y + m[13]]
+	}
+
+	pub fn to_world(&self, [x, y]: [f32; 2], extent: [f32; 2]) -> [f32; 2] {
+		let [sx, sy] = self.scale(extent);
+		let (s, c) = self.rotation.sin_cos();
+		let (dx, dy) = (x / sx, y / sy);
+		[
+			self.center[0] + c * dx - s * dy,
+			self.center[1] + s * dx + c * dy,
+		]
+	}
+
+	// `position` in pixels of a window of `size`, like winit cursor positions
+	pub fn window_to_world(&self, position: [f32; 2], size: [f32; 2]) -> [f32; 2] {
+		let ndc = [
+			position[0] / size[0].max(1.0) * 2.0 - 1.0,
+			position[1] / size[1].max(1.0) * 2.0 - 1.0,
+		];
+		self.to_world(ndc, size)
+	}
+
+	// keeps the world point under `ndc` in place
+	pub fn zoom_at(&mut self, ndc: [f32; 2], factor: f32, extent: [f32; 2]) {
+		let anchor = self.to_world(ndc, extent);
+		self.zoom *= factor;
+		let moved = self.to_world(ndc, extent);
+		self.center[0] += anchor[0] - moved[0];
+		self.center[1] += anchor[1] - moved[1];
+	}
+
+	// like `matrix` for world coordinates in `coordinates`. other than `Ndc`
+	// the camera moves, zooms and rotates in their units, around the middle
+	// of the output: `center` is the offset of the point shown there and
+	// `pixels_per_unit` is ignored
+	pub fn matrix_in(&self, coordinates: Coordinates, extent: [f32; 2]) -> [f32; 16] {
+		if coordinates == Coordinates::Ndc {
+			return self.matrix(extent);
+		}
+		let [vx, vy] = coordinates.from_ndc([0.0, 0.0], extent);
+		let [qx, qy] = [vx + self.center[0], vy + self.center[1]];
+		let k = self.zoom;
+		let (s, c) = self.rotation.sin_cos();
+		let units = [
+			k * c, -k * s, 0.0, 0.0,
+			k * s, k * c, 0.0, 0.0,
+			0.0, 0.0, 1.0, 0.0,
+			vx - k * (c * qx + s * qy), vy - k * (c * qy - s * qx), 0.0, 1.0,
+		];
+		multiply(&coordinates.matrix(extent), &units)
+	}
+
+	pub fn to_ndc_in(&self, coordinates: Coordinates, [x, y]: [f32; 2], extent: [f32; 2]) -> [f32; 2] {
+		let m = self.matrix_in(coordinates, extent);
+		[m[0] * x + m[4] * y + m[12], m[1] * x + m[5] * y + m[13]]
+	}
+
+	pub fn to_world_in(&self, coordinates: Coordinates, ndc: [f32; 2], extent: [f32; 2]) -> [f32; 2] {
+		if coordinates == Coordinates::Ndc {
+			return self.to_world(ndc, extent);
+		}
+		let [vx, vy] = coordinates.from_ndc([0.0, 0.0], extent);
+		let [ux, uy] = coordinates.from_ndc(ndc, extent);
+		let (s, c) = self.rotation.sin_cos();
+		let (dx, dy) = ((ux - vx) / self.zoom, (uy - vy) / self.zoom);
+		[
+			vx + self.center[0] + c * dx - s * dy,
+			vy + self.center[1] + s * dx + c * dy,
+		]
+	}
+
+	// moves the view so that the world point under `from` ends up under `to`,
+	// both in normalized device coordinates
+	pub fn drag(&mut self, from: [f32; 2], to: [f32; 2], extent: [f32; 2]) {
+		let a = self.to_world(from, extent);
+		let b = self.to_world(to, extent);
+		self.center[0] += a[0] - b[0];
+		self.center[1] += a[1] - b[1];
+	}
+}
+
+// column major product `a * b`
+pub fn multiply(a: &[f32; 16], b: &[f32; 16]) -> [f32; 16] {
+	let mut m = [0.0; 16];
+	for col in 0..4 {
+		for row in 0..4 {
+			m[col * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum();
+		}
+	}
+	m
+}
+
+// the inverse transpose of the upper 3x3 of `m`, which keeps normals
+// perpendicular to their surface under non-uniform scale
+pub fn normal_matrix(m: &[f32; 16]) -> [f32; 16] {
+	let [a, b, c] = [0, 4, 8].map(|i| [m[i], m[i + 1], m[i + 2]]);
+	let det = dot(a, cross(b, c));
+	let [x, y, z] = [cross(b, c), cross(c, a), cross(a, b)].map(|v| v.map(|v| v / det));
+	[
+		x[0], x[1], x[2], 0.0,
+		y[0], y[1], y[2], 0.0,
+		z[0], z[1], z[2], 0.0,
+		0.0, 0.0, 0.0, 1.0,
+	]
+}
+
+pub const IDENTITY: [f32; 16] = [
+	1.0, 0.0, 0.0, 0.0,
+	0.0, 1.0, 0.0, 0.0,
+	0.0, 0.0, 1.0, 0.0,
+	0.0, 0.0, 0.0, 1.0,
+];
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+	[a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+	a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+	[
+		a[1] * b[2] - a[2] * b[1],
+		a[2] * b[0] - a[0] * b[2],
+		a[0] * b[1] - a[1] * b[0],
+	]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+	let len = dot(a, a).sqrt().max(f32::EPSILON);
+	[a[0] / len, a[1] / len, a[2] / len]
+}
+
+// right handed, y up in world space and on the output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera3D {
+	pub eye: [f32; 3],
+	pub target: [f32; 3],
+	pub up: [f32; 3],
+	// vertical, in radians
+	pub fov_y: f32,
+	pub near: f32,
+	pub far: f32,
+}
+
+impl Default for Camera3D {
+	fn default() -> Self {
+		Self {
+			eye: [0.0, 0.0, 3.0],
+			target: [0.0, 0.0, 0.0],
+			up: [0.0, 1.0, 0.0],
+			fov_y: std::f32::consts::FRAC_PI_3,
+			near: 0.1,
+			far: 100.0,
+		}
+	}
+}
+
+impl Camera3D {
+	pub fn view(&self) -> [f32; 16] {
+		let f = normalize(sub(self.target, self.eye));
+		let s = normalize(cross(f, self.up));
+		let u = cross(s, f);
+		[
+			s[0], u[0], -f[0], 0.0,
+			s[1], u[1], -f[1], 0.0,
+			s[2], u[2], -f[2], 0.0,
+			-dot(s, self.eye), -dot(u, self.eye), dot(f, self.eye), 1.0,
+		]
+	}
+
+	// vulkan clip space, y flipped and depth from 0 at `near` to 1 at `far`
+	pub fn projection(&self, extent: [f32; 2]) -> [f32; 16] {
+		let aspect = extent[0] / extent[1].max(1.0);
+		let t = 1.0 / (self.fov_y / 2.0).tan();
+		let (n, f) = (self.near, self.far);
+		[
+			t / aspect, 0.0, 0.0, 0.0,
+			0.0, -t, 0.0, 0.0,
+			0.0, 0.0, f / (n - f), -1.0,
+			0.0, 0.0, n * f / (n - f), 0.0,
+		]
+	}
+}
+
+// where layers place their content on an output of `extent` pixels, the
+// same positions line up across layers and follow resizes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub enum Coordinates {
+	// -1..1 from the top left corner, vulkan clip space
+	#[default]
+	Ndc,
+	// 0..1 from the top left corner
+	Normalized,
+	// logical pixels from the top left corner, `scale` physical pixels each
+	Pixels { scale: f32 },
+}
+
+impl Coordinates {
+	// the camera showing these coordinates as they are
+	pub fn camera(self, extent: [f32; 2]) -> Camera2D {
+		match self {
+			Coordinates::Ndc => Camera2D::default(),
+			Coordinates::Normalized => Camera2D {
+				center: [0.5, 0.5],
+				zoom: 2.0,
+				..Default::default()
+			},
+			Coordinates::Pixels { scale } => Camera2D {
+				center: [extent[0] / scale / 2.0, extent[1] / scale / 2.0],
+				pixels_per_unit: Some(scale),
+				..Default::default()
+			},
+		}
+	}
+
+	pub fn matrix(self, extent: [f32; 2]) -> [f32; 16] {
+		self.camera(extent).matrix(extent)
+	}
+
+	pub fn to_ndc(self, position: [f32; 2], extent: [f32; 2]) -> [f32; 2] {
+		self.camera(extent).to_ndc(position, extent)
+	}
+
+	pub fn from_ndc(self, ndc: [f32; 2], extent: [f32; 2]) -> [f32; 2] {
+		self.camera(extent).to_world(ndc, extent)
+	}
+
+	pub fn top_left(self) -> [f32; 2] {
+		match self {
+			Coordinates::Ndc => [-1.0, -1.0],
+			_ => [0.0, 0.0],
+		}
+	}
+
+	// physical pixels per pixel of pixel sized content like glyphs
+	pub fn pixel_scale(self) -> f32 {
+		match self {
+			Coordinates::Pixels { scale } => scale,
+			_ => 1.0,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Camera2D, Coordinates};
+
+	const EXTENT: [f32; 2] = [800.0, 600.0];
+
+	fn assert_near(a: [f32; 2], b: [f32; 2]) {
+		assert!((a[0] - b[0]).abs() < 1e-4 && (a[1] - b[1]).abs() < 1e-4, "{:?} != {:?}", a, b);
+	}
+
+	fn cameras() -> Vec<Camera2D> {
+		vec![
+			Camera2D::default(),
+			Camera2D { center: [3.0, -2.0], zoom: 0.5, rotation: 0.7, pixels_per_unit: None },
+			Camera2D { center: [10.0, 20.0], zoom: 2.0, rotation: -1.2, pixels_per_unit: Some(32.0) },
+			Camera2D::pixels(EXTENT),
+		]
+	}
+
+	#[test]
+	fn world_round_trip() {
+		for camera in cameras() {
+			for world in [[0.0, 0.0], [1.5, -4.0], [-120.0, 35.0]] {
+				assert_near(camera.to_world(camera.to_ndc(world, EXTENT), EXTENT), world);
+			}
+		}
+	}
+
+	#[test]
+	fn center_is_in_the_middle() {
+		for camera in cameras() {
+			assert_near(camera.to_ndc(camera.center, EXTENT), [0.0, 0.0]);
+		}
+	}
+
+	#[test]
+	fn pixels_from_top_left() {
+		let camera = Camera2D::pixels(EXTENT);
+		assert_near(camera.to_ndc([0.0, 0.0], EXTENT), [-1.0, -1.0]);
+		assert_near(camera.to_ndc(EXTENT, EXTENT), [1.0, 1.0]);
+		assert_near(camera.window_to_world([200.0, 150.0], EXTENT), [200.0, 150.0]);
+	}
+
+	#[test]
+	fn zoom_at_keeps_anchor() {
+		for mut camera in cameras() {
+			let anchor = [0.3, -0.6];
+			let world = camera.to_world(anchor, EXTENT);
+			camera.zoom_at(anchor, 1.7, EXTENT);
+			assert_near(camera.to_ndc(world, EXTENT), anchor);
+		}
+	}
+
+	#[test]
+	fn drag_moves_point() {
+		for mut camera in cameras() {
+			let world = camera.to_world([-0.5, 0.5], EXTENT);
+			camera.drag([-0.5, 0.5], [0.25, 0.0], EXTENT);
+			assert_near(camera.to_ndc(world, EXTENT), [0.25, 0.0]);
+		}
+	}
+
+	fn coordinates() -> Vec<Coordinates> {
+		vec![Coordinates::Ndc, Coordinates::Normalized, Coordinates::Pixels { scale: 2.0 }]
+	}
+
+	#[test]
+	fn coordinate_mappings() {
+		let pixels = Coordinates::Pixels { scale: 2.0 };
+		assert_near(pixels.to_ndc([0.0, 0.0], EXTENT), [-1.0, -1.0]);
+		assert_near(pixels.to_ndc([400.0, 300.0], EXTENT), [1.0, 1.0]);
+		assert_near(pixels.to_ndc([100.0, 225.0], EXTENT), [-0.5, 0.5]);
+		assert_near(Coordinates::Normalized.to_ndc([0.0, 0.0], EXTENT), [-1.0, -1.0]);
+		assert_near(Coordinates::Normalized.to_ndc([1.0, 1.0], EXTENT), [1.0, 1.0]);
+		assert_near(Coordinates::Normalized.to_ndc([0.5, 0.5], EXTENT), [0.0, 0.0]);
+		assert_near(Coordinates::Ndc.to_ndc([0.3, -0.7], EXTENT), [0.3, -0.7]);
+		for coordinates in coordinates() {
+			assert_near(coordinates.to_ndc(coordinates.top_left(), EXTENT), [-1.0, -1.0]);
+			let position = [0.25, 0.75];
+			assert_near(coordinates.from_ndc(coordinates.to_ndc(position, EXTENT), EXTENT), position);
+		}
+	}
+
+	#[test]
+	fn world_round_trip_in_coordinates() {
+		for coordinates in coordinates() {
+			for camera in cameras() {
+				for world in [[0.0, 0.0], [1.5, -4.0], [-120.0, 35.0]] {
+					let ndc = camera.to_ndc_in(coordinates, world, EXTENT);
+					assert_near(camera.to_world_in(coordinates, ndc, EXTENT), world);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn default_camera_shows_the_coordinates() {
+		for coordinates in coordinates() {
+			let position = [0.25, 0.75];
+			assert_near(
+				Camera2D::default().to_ndc_in(coordinates, position, EXTENT),
+				coordinates.to_ndc(position, EXTENT),
+			);
+		}
+	}
+
+	#[test]
+	fn camera_acts_in_units() {
+		let coordinates = Coordinates::Pixels { scale: 1.0 };
+		let camera = Camera2D { center: [10.0, 0.0], zoom: 2.0, ..Default::default() };
+		// the middle of the output shows the point `center` to its right
+		assert_near(camera.to_world_in(coordinates, [0.0, 0.0], EXTENT), [410.0, 300.0]);
+		// one unit is `zoom` pixels, pixels_per_unit has no say
+		let camera = Camera2D { pixels_per_unit: Some(32.0), ..camera };
+		let a = camera.to_ndc_in(coordinates, [410.0, 300.0], EXTENT);
+		let b = camera.to_ndc_in(coordinates, [411.0, 300.0], EXTENT);
+		assert_near([(b[0] - a[0]) * EXTENT[0] / 2.0, b[1] - a[1]], [2.0, 0.0]);
+	}
+
+	#[test]
+	fn rotated_pixel_squares_stay_square() {
+		let coordinates = Coordinates::Pixels { scale: 1.0 };
+		let camera = Camera2D { rotation: 0.5, zoom: 1.5, ..Default::default() };
+		let corners = [[100.0, 100.0], [110.0, 100.0], [110.0, 110.0], [100.0, 110.0]].map(|world| {
+			let [x, y] = camera.to_ndc_in(coordinates, world, EXTENT);
+			[(x + 1.0) / 2.0 * EXTENT[0], (y + 1.0) / 2.0 * EXTENT[1]]
+		});
+		let distance = |a: [f32; 2], b: [f32; 2]| ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt();
+		for i in 0..4 {
+			let side = distance(corners[i], corners[(i + 1) % 4]);
+			assert!((side - 15.0).abs() < 1e-3, "{:?}", corners);
+		}
+		let diagonal = distance(corners[0], corners[2]);
+		assert!((diagonal - 15.0 * 2f32.sqrt()).abs() < 1e-3, "{:?}", corners);
+	}
+}
@@ -1,181 +1,951 @@
 use ash::vk;
+use std::mem;
+use winit::event::WindowEvent;
 
-use crate::layer::LayerRef;
+use crate::animation::{Animation, FrameClock, FrameTime, Playing};
+use crate::effect::{Effect, EffectConstants, EffectPass};
+use crate::layer::{Layer, LayerRef, record_inline};
+use crate::recorder::{RecordJob, Recorder, Secondary};
 use crate::base::{Base, BaseRef, record_submit_commandbuffer, find_memorytype_index};
+use crate::graph::{RenderGraph, ResourceId, TransientDesc, TransientPool};
+use crate::input::{InputContext, InputEvent, InputTranslator};
+use crate::layer::perf_hud::PerfHud;
+use crate::profiler::{FrameStats, Profiler};
+use crate::tracker::{ImageState, ImageTrackerRef, ImageUsage};
 
 pub struct LayerCache {
 	pub image: vk::Image,
 	pub memory: vk::DeviceMemory,
+	pub size: vk::DeviceSize,
 	pub damage: bool,
 }
 
 pub struct LayerObject {
 	device: ash::Device,
+	tracker: ImageTrackerRef,
 	layer: LayerRef,
 	// no cache: render every time
 	cache: Option<LayerCache>,
+	// indices of cached layers sampled by this layer
+	inputs: Vec<usize>,
+	// secondary command buffer per output image, reused until marked updated
+	secondaries: Vec<Secondary>,
+	recorded: Vec<bool>,
 }
 
 impl LayerObject {
 	pub fn new(base: &Base, layer: LayerRef) -> Self {
 		Self {
 			device: base.device.clone(),
+			tracker: base.image_tracker.clone(),
 			layer,
 			cache: None,
+			inputs: Vec::new(),
+			secondaries: Vec::new(),
+			recorded: Vec::new(),
 		}
 	}
 
-	pub fn build_instant(self, base: &Base) -> Self {
-		self.layer.write().unwrap().set_output(base.present_images.clone());
+	pub fn build_instant(mut self, outputs: Vec<vk::Image>) -> Self {
+		self.secondaries = vec![Secondary::default(); outputs.len()];
+		self.recorded = vec![false; outputs.len()];
+		self.layer.write().unwrap().set_output(outputs);
 		self
 	}
 
 	pub fn build_cache(mut self, base: &Base) -> Self {
-		let (image, memory) = unsafe {
-			let create_info = vk::ImageCreateInfo::default()
-				.image_type(vk::ImageType::TYPE_2D)
-				.format(base.surface_format.format)
-				.extent(base.render_resolution.into())
-				.mip_levels(1)
-				.array_layers(1)
-				.samples(vk::SampleCountFlags::TYPE_1)
-				.tiling(vk::ImageTiling::OPTIMAL)
-				.usage(vk::ImageUsageFlags::COLOR_ATTACHMENT |
-					vk::ImageUsageFlags::TRANSFER_DST |
-					vk::ImageUsageFlags::TRANSFER_SRC);
-			let image = base.device.create_image(&create_info, None).unwrap();
-			let memory_req = base.device.get_image_memory_requirements(image);
-			let memory_index = find_memorytype_index(
-				&memory_req,
-				&base.device_memory_properties,
-				vk::MemoryPropertyFlags::DEVICE_LOCAL,
-			).unwrap();
-			let allocate_info = vk::MemoryAllocateInfo {
-				allocation_size: memory_req.size,
-				memory_type_index: memory_index,
-				..Default::default()
-			};
-			let memory = base
-				.device
-				.allocate_memory(&allocate_info, None)
-				.unwrap();
-			base.device
-				.bind_image_memory(image, memory, 0)
-				.expect("Unable to bind depth image memory");
-			(image, memory)
+		let cache = Self::create_cache(base);
+		self.layer.write().unwrap().set_output(vec![cache.image]);
+		self.secondaries = vec![Secondary::default()];
+		self.recorded = vec![false];
+		self.cache = Some(cache);
+		self
+	}
+
+	fn create_cache(base: &Base) -> LayerCache { unsafe {
+		let create_info = vk::ImageCreateInfo::default()
+			.image_type(vk::ImageType::TYPE_2D)
+			.format(base.surface_format.format)
+			.extent(base.render_resolution.into())
+			.mip_levels(1)
+			.array_layers(1)
+			.samples(vk::SampleCountFlags::TYPE_1)
+			.tiling(vk::ImageTiling::OPTIMAL)
+			.usage(vk::ImageUsageFlags::COLOR_ATTACHMENT |
+				vk::ImageUsageFlags::SAMPLED |
+				vk::ImageUsageFlags::TRANSFER_DST |
+				vk::ImageUsageFlags::TRANSFER_SRC);
+		let image = base.device.create_image(&create_info, None).unwrap();
+		let memory_req = base.device.get_image_memory_requirements(image);
+		let memory_index = find_memorytype_index(
+			&memory_req,
+			&base.device_memory_properties,
+			vk::MemoryPropertyFlags::DEVICE_LOCAL,
+		).unwrap();
+		let allocate_info = vk::MemoryAllocateInfo {
+			allocation_size: memory_req.size,
+			memory_type_index: memory_index,
+			..Default::default()
 		};
-		self.layer.write().unwrap().set_output(vec![image]);
-		self.cache = Some(LayerCache {
+		let memory = base
+			.device
+			.allocate_memory(&allocate_info, None)
+			.unwrap();
+		base.device
+			.bind_image_memory(image, memory, 0)
+			.expect("Unable to bind depth image memory");
+		LayerCache {
 			image,
 			memory,
+			size: memory_req.size,
 			damage: true,
-		});
-		self
+		}
+	}}
+
+	// the recorder's pools outlive the layer objects, see `LayerStack`
+	fn free_secondaries(&mut self) {
+		for secondary in self.secondaries.iter_mut() {
+			secondary.free(&self.device);
+		}
 	}
-}
 
-impl Drop for LayerObject {
-	fn drop(&mut self) { unsafe {
-		self.device.device_wait_idle().unwrap();
+	fn destroy_cache(&mut self) { unsafe {
 		if let Some(cache) = self.cache.take() {
+			self.tracker.lock().unwrap().forget(cache.image);
 			self.device.destroy_image(cache.image, None);
 			self.device.free_memory(cache.memory, None);
 		}
 	}}
+
+	// new output images or resolution, the device must be idle
+	fn resize(&mut self, base: &Base, outputs: &[vk::Image]) {
+		let outputs = if self.cache.is_some() {
+			self.destroy_cache();
+			let cache = Self::create_cache(base);
+			let outputs = vec![cache.image];
+			self.cache = Some(cache);
+			outputs
+		} else {
+			outputs.to_vec()
+		};
+		self.free_secondaries();
+		self.secondaries = vec![Secondary::default(); outputs.len()];
+		self.recorded = vec![false; outputs.len()];
+		let mut layer = self.layer.write().unwrap();
+		layer.resize(base.render_resolution);
+		layer.set_output(outputs);
+	}
+}
+
+impl LayerObject {
+	// cached layers are rendered again, every layer is recorded again
+	fn mark_update(&mut self) {
+		if let Some(cache) = self.cache.as_mut() {
+			cache.damage = true;
+		}
+		self.recorded.fill(false);
+	}
 }
 
-struct BarrierBuilder {
-	pub subresource_range: vk::ImageSubresourceRange,
-	pub device: ash::Device,
-	pub command: vk::CommandBuffer,
+impl Drop for LayerObject {
+	fn drop(&mut self) { unsafe {
+		self.device.device_wait_idle().unwrap();
+		self.free_secondaries();
+		self.destroy_cache();
+	}}
+}
+
+// execute the layer's recording if it has one, record it inline otherwise
+fn draw_layer(
+	device: &ash::Device,
+	command_buffer: vk::CommandBuffer,
+	layer: &LayerRef,
+	idx: usize,
+	secondary: Option<vk::CommandBuffer>,
+) {
+	let layer = layer.read().unwrap();
+	match (secondary, layer.target(idx)) {
+		(Some(secondary), Some(target)) => unsafe {
+			target.begin(
+				device,
+				command_buffer,
+				vk::SubpassContents::SECONDARY_COMMAND_BUFFERS,
+			);
+			device.cmd_execute_commands(command_buffer, &[secondary]);
+			device.cmd_end_render_pass(command_buffer);
+		},
+		_ => record_inline(device, command_buffer, &*layer, idx),
+	}
 }
 
-impl BarrierBuilder {
-	pub fn new(device: ash::Device, command: vk::CommandBuffer) -> Self {
-		let subresource_range = vk::ImageSubresourceRange {
+pub struct LayerStack {
+	// dropped before `recorder`, they free their secondaries into its pools
+	los: Vec<LayerObject>,
+	outputs: Vec<vk::Image>,
+	recorder: Recorder,
+	// layer receiving keyboard events first
+	focus: Option<usize>,
+	// layer receiving every pointer event
+	capture: Option<usize>,
+	// topmost layer under the pointer
+	hover: Option<usize>,
+}
+
+impl LayerStack {
+	pub fn new(base: &Base, outputs: Vec<vk::Image>) -> Self {
+		Self {
+			los: Vec::new(),
+			outputs,
+			recorder: Recorder::new(base),
+			focus: None,
+			capture: None,
+			hover: None,
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.los.clear();
+		self.focus = None;
+		self.capture = None;
+		self.hover = None;
+	}
+
+	pub fn len(&self) -> usize {
+		self.los.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.los.is_empty()
+	}
+
+	pub fn new_layer(&mut self, base: &Base, layer: LayerRef) {
+		self.los.push(LayerObject::new(base, layer).build_instant(self.outputs.clone()));
+	}
+
+	pub fn new_cached_layer(&mut self, base: &Base, layer: LayerRef) {
+		self.los.push(LayerObject::new(base, layer).build_cache(base));
+	}
+
+	// recreate caches and outputs of every layer, the device must be idle
+	pub fn resize(&mut self, base: &Base, outputs: Vec<vk::Image>) {
+		self.outputs = outputs;
+		for lo in self.los.iter_mut() {
+			lo.resize(base, &self.outputs);
+		}
+		// cache images sampled by other layers are new
+		for idx in 0..self.los.len() {
+			if !self.los[idx].inputs.is_empty() {
+				let inputs = self.los[idx].inputs.clone();
+				self.set_inputs(idx, inputs);
+			}
+		}
+	}
+
+	pub fn update_all(&mut self) {
+		for lo in self.los.iter_mut() {
+			lo.mark_update();
+		}
+	}
+
+	pub fn mark_update(&mut self, idx: usize) {
+		self.los[idx].mark_update();
+	}
+
+	// let layer `idx` sample the cache images of `inputs`, in that order
+	pub fn set_inputs(&mut self, idx: usize, inputs: Vec<usize>) {
+		let images = inputs
+			.iter()
+			.map(|&input| self.los[input]
+				.cache
+				.as_ref()
+				.expect("only cached layers can be used as input")
+				.image
+			)
+			.collect();
+		self.los[idx].layer.write().unwrap().set_input(images);
+		self.los[idx].inputs = inputs;
+		// recorded secondaries point at the old descriptors
+		self.los[idx].mark_update();
+	}
+
+	pub fn focus(&self) -> Option<usize> {
+		self.focus
+	}
+
+	pub fn set_focus(&mut self, focus: Option<usize>) {
+		self.focus = focus;
+	}
+
+	// tick every layer, true if any changed
+	pub fn tick(&mut self, time: &FrameTime) -> bool {
+		let mut changed = false;
+		for lo in self.los.iter_mut() {
+			if lo.layer.write().unwrap().tick(time) {
+				lo.mark_update();
+				changed = true;
+			}
+		}
+		changed
+	}
+
+	// whether the pointer position hits layer `idx`, false without handler
+	fn hit(&self, idx: usize, position: [f32; 2]) -> bool {
+		let mut layer = self.los[idx].layer.write().unwrap();
+		match layer.input() {
+			Some(handler) => handler
+				.bounds()
+				.map_or(true, |bounds| bounds.contains(handler.to_local(position))),
+			None => false,
+		}
+	}
+
+	// none if the layer has no handler
+	fn deliver(
+		&mut self,
+		idx: usize,
+		event: &InputEvent,
+		parent: &mut InputContext,
+	) -> Option<bool> {
+		let mut ctx = InputContext::default();
+		let consumed = {
+			let mut layer = self.los[idx].layer.write().unwrap();
+			let handler = layer.input()?;
+			let event = event.map_position(|position| handler.to_local(position));
+			handler.handle(&event, &mut ctx)
+		};
+		match ctx.capture_request() {
+			Some(true) => self.capture = Some(idx),
+			Some(false) if self.capture == Some(idx) => self.capture = None,
+			_ => {},
+		}
+		match ctx.focus_request() {
+			Some(true) => self.focus = Some(idx),
+			Some(false) if self.focus == Some(idx) => self.focus = None,
+			_ => {},
+		}
+		if ctx.update_requested() {
+			self.los[idx].mark_update();
+			parent.update();
+		}
+		Some(consumed)
+	}
+
+	// route an event top-down until a layer consumes it, `ctx` receives the
+	// requests of the stack as a whole for nesting
+	pub fn dispatch(&mut self, event: &InputEvent, ctx: &mut InputContext) -> bool {
+		let consumed = if event.is_pointer() {
+			self.dispatch_pointer(event, ctx)
+		} else {
+			self.dispatch_key(event, ctx)
+		};
+		match self.capture {
+			Some(_) => ctx.capture(),
+			None => ctx.release(),
+		}
+		match self.focus {
+			Some(_) => ctx.focus(),
+			None => ctx.blur(),
+		}
+		consumed
+	}
+
+	fn dispatch_pointer(&mut self, event: &InputEvent, ctx: &mut InputContext) -> bool {
+		let position = match event.position() {
+			Some(position) => position,
+			None => {
+				self.capture = None;
+				return match self.hover.take() {
+					Some(hover) => self.deliver(hover, event, ctx).unwrap_or(false),
+					None => false,
+				};
+			}
+		};
+		if let Some(capture) = self.capture {
+			return self.deliver(capture, event, ctx).unwrap_or(false);
+		}
+		let hits: Vec<usize> = (0..self.los.len())
+			.rev()
+			.filter(|&idx| self.hit(idx, position))
+			.collect();
+		let hover = hits.first().copied();
+		if hover != self.hover {
+			if let Some(old) = self.hover {
+				self.deliver(old, &InputEvent::PointerLeft, ctx);
+			}
+			self.hover = hover;
+		}
+		hits.into_iter().any(|idx| self.deliver(idx, event, ctx) == Some(true))
+	}
+
+	fn dispatch_key(&mut self, event: &InputEvent, ctx: &mut InputContext) -> bool {
+		let focus = self.focus;
+		if let Some(focus) = focus {
+			if self.deliver(focus, event, ctx) == Some(true) {
+				return true;
+			}
+		}
+		(0..self.los.len())
+			.rev()
+			.filter(|&idx| Some(idx) != focus)
+			.any(|idx| self.deliver(idx, event, ctx) == Some(true))
+	}
+
+	// let the layers recorded for outputs[idx] this frame do their cpu work,
+	// must come before `build`
+	pub fn prepare(&mut self, idx: usize) {
+		for lo in self.los.iter_mut() {
+			let out_idx = match &lo.cache {
+				Some(cache) if cache.damage => 0,
+				Some(_) => continue,
+				None => idx,
+			};
+			// a recorded secondary is executed as is unless its commands changed
+			if lo.layer.write().unwrap().prepare(out_idx) {
+				lo.recorded[out_idx] = false;
+			}
+		}
+	}
+
+	// composite all layers into outputs[idx]
+	pub fn record(
+		&mut self,
+		base: &Base,
+		pool: &mut TransientPool,
+		command_buffer: vk::CommandBuffer,
+		idx: usize,
+	) {
+		let image = self.outputs[idx];
+		base.image_tracker.lock().unwrap().discard(image);
+		let mut graph = RenderGraph::new();
+		let output = graph.import(image);
+		graph.output(output);
+		self.build(base, &mut graph, output, idx);
+		graph.execute(base, pool, command_buffer);
+	}
+
+	// add the passes compositing all layers into outputs[idx], imported into
+	// the graph as `output`
+	pub fn build<'a>(
+		&'a mut self,
+		base: &Base,
+		graph: &mut RenderGraph<'a>,
+		output: ResourceId,
+		idx: usize,
+	) {
+		// layers with their own render pass are recorded in parallel first
+		let workers = self.recorder.workers();
+		let mut jobs = Vec::new();
+		for (lo_idx, lo) in self.los.iter_mut().enumerate() {
+			let out_idx = match &lo.cache {
+				Some(cache) if cache.damage => 0,
+				Some(_) => continue,
+				None => idx,
+			};
+			if lo.recorded[out_idx] || lo.layer.read().unwrap().target(out_idx).is_none() {
+				continue;
+			}
+			lo.recorded[out_idx] = true;
+			jobs.push(RecordJob {
+				layer: &lo.layer,
+				idx: out_idx,
+				worker: lo_idx % workers,
+				secondary: &mut lo.secondaries[out_idx],
+			});
+		}
+		self.recorder.record(jobs);
+
+		let caches: Vec<_> = self.los
+			.iter()
+			.map(|lo| lo.cache.as_ref().map(|cache| graph.import(cache.image)))
+			.collect();
+
+		// (cache to copy or layer to draw, inputs) in stack order
+		let mut composite = Vec::new();
+		for (lo_idx, (lo, &cache_id)) in self.los.iter_mut().zip(caches.iter()).enumerate() {
+			let LayerObject { layer, cache, inputs, secondaries, recorded, .. } = lo;
+			let layer: &LayerRef = layer;
+			let out_idx = if cache.is_some() { 0 } else { idx };
+			let command_buffer = secondaries[out_idx].command_buffer;
+			let secondary = if recorded[out_idx] && command_buffer != vk::CommandBuffer::null() {
+				Some(command_buffer)
+			} else {
+				None
+			};
+			let reads: Vec<_> = inputs
+				.iter()
+				.map(|&input| (caches[input].unwrap(), ImageUsage::Sampled))
+				.collect();
+			let usage = layer.read().unwrap().usage();
+			match (cache.as_mut(), cache_id) {
+				(Some(cache), Some(cache_id)) => {
+					if cache.damage {
+						let damage = &mut cache.damage;
+						let pass = graph.add_pass(&reads, &[(cache_id, usage)], move |ctx| {
+							draw_layer(ctx.device, ctx.command_buffer, layer, 0, secondary);
+							*damage = false;
+						});
+						graph.set_scope(pass, lo_idx);
+					}
+					composite.push((lo_idx, Ok(cache_id), reads));
+				}
+				_ => composite.push((lo_idx, Err((layer, usage, secondary)), reads)),
+			}
+		}
+
+		graph.add_pass(&[], &[(output, ImageUsage::TransferDst)], |ctx| unsafe {
+			let subresource_range = vk::ImageSubresourceRange {
+				aspect_mask: vk::ImageAspectFlags::COLOR,
+				level_count: 1,
+				layer_count: 1,
+				..Default::default()
+			};
+			ctx.device.cmd_clear_color_image(
+				ctx.command_buffer,
+				ctx.image(output),
+				vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+				&vk::ClearColorValue {
+					float32: [0.0, 0.0, 0.0, 0.0],
+				},
+				&[subresource_range],
+			);
+		});
+		let subresource = vk::ImageSubresourceLayers {
 			aspect_mask: vk::ImageAspectFlags::COLOR,
-			level_count: 1,
+			mip_level: 0,
+			base_array_layer: 0,
 			layer_count: 1,
+		};
+		let whole_region = vk::ImageCopy {
+			src_subresource: subresource,
+			dst_subresource: subresource,
+			extent: base.render_resolution.into(),
 			..Default::default()
 		};
+		for (lo_idx, item, reads) in composite.into_iter() {
+			let pass = match item {
+				Ok(cache_id) => graph.add_pass(
+					&[(cache_id, ImageUsage::TransferSrc)],
+					&[(output, ImageUsage::TransferDst)],
+					move |ctx| unsafe {
+						ctx.device.cmd_copy_image(
+							ctx.command_buffer,
+							ctx.image(cache_id),
+							vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+							ctx.image(output),
+							vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+							&[whole_region],
+						);
+					},
+				),
+				Err((layer, usage, secondary)) => graph.add_pass(
+					&reads,
+					&[(output, usage)],
+					move |ctx| draw_layer(ctx.device, ctx.command_buffer, layer, idx, secondary),
+				),
+			};
+			graph.set_scope(pass, lo_idx);
+		}
+	}
+
+	pub fn names(&self) -> Vec<String> {
+		self.los
+			.iter()
+			.map(|lo| lo.layer.read().unwrap().name().to_string())
+			.collect()
+	}
+
+	// bytes of the cache images
+	pub fn memory(&self) -> u64 {
+		self.los
+			.iter()
+			.filter_map(|lo| lo.cache.as_ref())
+			.map(|cache| cache.size)
+			.sum()
+	}
+}
+
+struct EffectSlot {
+	effect: Effect,
+	enabled: bool,
+	// fragment shader of a custom effect
+	spirv: Option<Vec<u32>>,
+	// recreated when the effect is changed to another kind
+	kind: mem::Discriminant<Effect>,
+	pass: EffectPass,
+}
+
+impl EffectSlot {
+	fn new(base: &Base, effect: Effect, spirv: Option<Vec<u32>>) -> Self {
+		let pass = EffectPass::new(base, &effect, spirv.as_deref());
 		Self {
-			subresource_range,
-			device,
-			command,
+			kind: mem::discriminant(&effect),
+			effect,
+			enabled: true,
+			spirv,
+			pass,
 		}
 	}
 
-	pub fn build(&self, image: vk::Image, from: vk::ImageLayout, to: vk::ImageLayout) { unsafe {
-		let barrier = vk::ImageMemoryBarrier {
-			dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
-			old_layout: from,
-			new_layout: to,
-			image,
-			subresource_range: self.subresource_range,
-			..Default::default()
-		};
-		self.device.cmd_pipeline_barrier(
-			self.command,
-			vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-			vk::PipelineStageFlags::TRANSFER,
-			vk::DependencyFlags::empty(),
-			&[],
-			&[],
-			&[barrier],
-		);
-	}}
+	fn refresh(&mut self, base: &Base) {
+		if self.kind != mem::discriminant(&self.effect) || self.pass.extent() != base.render_resolution {
+			self.pass = EffectPass::new(base, &self.effect, self.spirv.as_deref());
+			self.kind = mem::discriminant(&self.effect);
+		}
+	}
 }
 
 pub struct LayerCompositor {
 	// TODO: prevent base lock
 	base: BaseRef,
-	los: Vec<LayerObject>,
+	stack: LayerStack,
+	pool: TransientPool,
+	// applied in order to the composited frame
+	effects: Vec<EffectSlot>,
+	input: InputTranslator,
+	clock: FrameClock,
+	playing: Vec<Playing>,
+	// a layer changed in its last tick
+	ticking: bool,
+	// window size, used when the surface does not dictate the extent
+	size: vk::Extent2D,
+	// the swapchain no longer matches the surface
+	stale: bool,
+	profiler: Profiler,
+	// drawn over the frame after the effects
+	hud: Option<PerfHud>,
+	hud_visible: bool,
+	// present image of the last offscreen frame
+	last_image: usize,
 }
 
 impl LayerCompositor {
 	pub fn new(base: BaseRef) -> Self {
+		let (stack, pool, size, profiler) = {
+			let base = base.read().unwrap();
+			(
+				LayerStack::new(&base, base.present_images.clone()),
+				TransientPool::new(&base),
+				base.surface_resolution,
+				Profiler::new(&base),
+			)
+		};
 		Self {
 			base,
-			los: Vec::new(),
+			stack,
+			pool,
+			effects: Vec::new(),
+			input: InputTranslator::new(),
+			clock: FrameClock::new(),
+			playing: Vec::new(),
+			ticking: false,
+			size,
+			stale: false,
+			profiler,
+			hud: None,
+			hud_visible: false,
+			last_image: 0,
 		}
 	}
 
 	pub fn new_layer(&mut self, layer: LayerRef) {
 		let base = self.base.read().unwrap();
-		self.los.push(LayerObject::new(&base, layer).build_instant(&base));
+		self.stack.new_layer(&base, layer);
 	}
 
 	pub fn new_cached_layer(&mut self, layer: LayerRef) {
 		let base = self.base.read().unwrap();
-		self.los.push(LayerObject::new(&base, layer).build_cache(&base));
+		self.stack.new_cached_layer(&base, layer);
+	}
+
+	// drop every layer and stop the animations playing on them
+	pub fn clear(&mut self) {
+		self.stack.clear();
+		self.playing.clear();
 	}
 
 	pub fn update_all(&mut self) {
-		for lo in self.los.iter_mut() {
-			if let Some(mut cache) = lo.cache.as_mut() {
-				cache.damage = true;
+		self.stack.update_all();
+	}
+
+	pub fn mark_update(&mut self, idx: usize) {
+		self.stack.mark_update(idx);
+	}
+
+	pub fn set_inputs(&mut self, idx: usize, inputs: Vec<usize>) {
+		self.stack.set_inputs(idx, inputs);
+	}
+
+	// route a window event to the layers, true if one consumed it
+	pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+		let size = {
+			let base = self.base.read().unwrap();
+			[base.render_resolution.width, base.render_resolution.height]
+		};
+		match self.input.translate(event, size) {
+			Some(event) => self.stack.dispatch(&event, &mut InputContext::default()),
+			None => false,
+		}
+	}
+
+	pub fn focus(&self) -> Option<usize> {
+		self.stack.focus()
+	}
+
+	pub fn set_focus(&mut self, focus: Option<usize>) {
+		self.stack.set_focus(focus);
+	}
+
+	// play `animation` from the next frame on, damaging `layers` until it
+	// has finished
+	pub fn animate(&mut self, layers: &[usize], animation: impl Animation + 'static) {
+		self.playing.push(Playing {
+			layers: layers.to_vec(),
+			start: None,
+			animation: Box::new(animation),
+		});
+	}
+
+	pub fn stop_animations(&mut self) {
+		self.playing.clear();
+	}
+
+	// true if another frame should be rendered right away
+	pub fn is_animating(&self) -> bool {
+		self.ticking || !self.playing.is_empty()
+	}
+
+	pub fn frame_time(&self) -> FrameTime {
+		self.clock.now()
+	}
+
+	// e.g. `FrameClock::fixed` for offscreen rendering
+	pub fn set_clock(&mut self, clock: FrameClock) {
+		self.clock = clock;
+	}
+
+	// advance the clock, animations and layers by one frame
+	fn tick(&mut self) {
+		let time = self.clock.tick();
+		let stack = &mut self.stack;
+		self.playing.retain_mut(|playing| {
+			let running = playing.advance(&time);
+			for &layer in playing.layers.iter() {
+				stack.mark_update(layer);
 			}
+			running
+		});
+		self.ticking = self.stack.tick(&time);
+	}
+
+	pub fn push_effect(&mut self, effect: Effect) -> usize {
+		assert!(
+			!matches!(effect, Effect::Custom { .. }),
+			"custom effects need a shader, use push_custom_effect",
+		);
+		let base = self.base.read().unwrap();
+		self.effects.push(EffectSlot::new(&base, effect, None));
+		self.effects.len() - 1
+	}
+
+	// `spirv` is a fragment shader using the push constants and sampler of
+	// src/shader/blur.frag, `params` is passed as is
+	pub fn push_custom_effect(&mut self, spirv: Vec<u32>, params: [[f32; 4]; 5]) -> usize {
+		let base = self.base.read().unwrap();
+		self.effects.push(EffectSlot::new(&base, Effect::Custom { params }, Some(spirv)));
+		self.effects.len() - 1
+	}
+
+	// parameters are read every frame
+	pub fn effect_mut(&mut self, idx: usize) -> &mut Effect {
+		&mut self.effects[idx].effect
+	}
+
+	pub fn set_effect_enabled(&mut self, idx: usize, enabled: bool) {
+		self.effects[idx].enabled = enabled;
+	}
+
+	pub fn remove_effect(&mut self, idx: usize) -> Effect {
+		self.effects.remove(idx).effect
+	}
+
+	// shown right away
+	pub fn set_hud(&mut self, mut hud: PerfHud) {
+		let base = self.base.read().unwrap();
+		hud.resize(base.render_resolution);
+		hud.set_output(base.present_images.clone());
+		drop(base);
+		self.hud = Some(hud);
+		self.hud_visible = true;
+	}
+
+	pub fn has_hud(&self) -> bool {
+		self.hud.is_some()
+	}
+
+	pub fn set_hud_visible(&mut self, visible: bool) {
+		self.hud_visible = visible;
+	}
+
+	pub fn toggle_hud(&mut self) {
+		self.hud_visible = !self.hud_visible;
+	}
+
+	// statistics of the last completed frame
+	pub fn stats(&self) -> &FrameStats {
+		self.profiler.stats()
+	}
+
+	fn record(&mut self, base: &Base, command_buffer: vk::CommandBuffer, idx: usize) {
+		let image = base.present_images[idx];
+		let view = base.present_image_views[idx];
+		for slot in self.effects.iter_mut() {
+			slot.refresh(base);
+		}
+		let draws: Vec<_> = self.effects
+			.iter()
+			.filter(|slot| slot.enabled)
+			.flat_map(|slot| slot
+				.effect
+				.constants(base.render_resolution)
+				.into_iter()
+				.map(move |constants| (&slot.pass, constants))
+			)
+			.collect();
+
+		let mut graph = RenderGraph::new();
+		if let Some(timer) = self.profiler.timer.as_mut() {
+			graph.profile(timer);
+		}
+		let composited = graph.import_view(image, view);
+		self.stack.build(base, &mut graph, composited, idx);
+		let output = if draws.is_empty() {
+			composited
+		} else {
+			// the present image is imported again as the last effect's target, so
+			// that reading the composited frame does not wait for that write
+			let output = graph.import_view(image, view);
+			build_effects(base, &mut graph, draws, composited, output);
+			output
+		};
+		if let (true, Some(hud)) = (self.hud_visible, self.hud.as_ref()) {
+			graph.add_pass(
+				&[],
+				&[(output, ImageUsage::ColorAttachment)],
+				move |ctx| record_inline(ctx.device, ctx.command_buffer, hud, idx),
+			);
 		}
+		graph.output(output);
+		graph.execute(base, &mut self.pool, command_buffer);
 	}
 
-	pub fn mark_update(&mut self, idx: usize) {
-		if let Some(mut cache) = self.los[idx].cache.as_mut() {
-			cache.damage = true;
+	// the window was resized, the swapchain is recreated before the next frame
+	pub fn resize(&mut self, width: u32, height: u32) {
+		self.size = vk::Extent2D { width, height };
+		self.stale = true;
+	}
+
+	fn minimized(&self) -> bool {
+		self.size.width == 0 || self.size.height == 0
+	}
+
+	fn recreate_swapchain(&mut self) {
+		self.base.write().unwrap().recreate_swapchain(self.size);
+		let base = self.base.read().unwrap();
+		self.stack.resize(&base, base.present_images.clone());
+		self.pool = TransientPool::new(&base);
+		// same sized passes are kept, but not their framebuffers and sets
+		for slot in self.effects.iter_mut() {
+			slot.pass.forget_views();
 		}
+		if let Some(hud) = self.hud.as_mut() {
+			hud.resize(base.render_resolution);
+			hud.set_output(base.present_images.clone());
+		}
+		self.stale = false;
 	}
 
 	pub fn render(&mut self) {
+		if self.minimized() {
+			return;
+		}
+		if self.stale {
+			self.recreate_swapchain();
+		}
+		self.profiler.begin_cpu();
+		self.tick();
+		if self.base.read().unwrap().is_headless() {
+			self.render_offscreen();
+		} else if !self.present() {
+			self.stale = true;
+		}
+		self.profiler.end_cpu();
+	}
+
+	// called once the frame's fence was waited, so the previous frame's
+	// queries are ready
+	fn record_frame(&mut self, base: &Base, command_buffer: vk::CommandBuffer, idx: usize) {
+		let memory = self.stack.memory() + self.pool.memory();
+		self.profiler.resolve(self.stack.names(), memory);
+		if let (true, Some(hud)) = (self.hud_visible, self.hud.as_mut()) {
+			hud.update(self.profiler.stats());
+			hud.prepare(idx);
+		}
+		self.stack.prepare(idx);
+		let frame = self.profiler.timer.as_mut().and_then(|timer| {
+			timer.reset(command_buffer);
+			timer.begin(command_buffer, None)
+		});
+		self.record(base, command_buffer, idx);
+		if let (Some(timer), Some(query)) = (self.profiler.timer.as_mut(), frame) {
+			timer.end(command_buffer, query);
+		}
+	}
+
+	// headless base: render into the next offscreen image, see `capture`
+	fn render_offscreen(&mut self) {
+		let base = self.base.clone();
+		let base = base.read().unwrap();
+		let idx = (self.last_image + 1) % base.present_images.len();
+		record_submit_commandbuffer(
+			&base.device,
+			base.draw_command_buffer,
+			base.draw_commands_reuse_fence,
+			base.present_queue,
+			&[],
+			&[],
+			&[],
+			|_, command_buffer| {
+				base.image_tracker.lock().unwrap().discard(base.present_images[idx]);
+				self.record_frame(&base, command_buffer, idx);
+			},
+		);
+		self.last_image = idx;
+	}
+
+	// the last frame rendered by a headless compositor, waits for it
+	pub fn capture(&self) -> image::RgbaImage {
+		let base = self.base.read().unwrap();
+		assert!(base.is_headless(), "capture needs a headless base");
+		base.read_image(base.present_images[self.last_image])
+	}
+
+	// false if the swapchain is out of date
+	fn present(&mut self) -> bool {
 		unsafe {
-			let base = self.base.read().unwrap();
-			let (present_index, _) = base
+			let base = self.base.clone();
+			let base = base.read().unwrap();
+			let present_index = match base
 				.swapchain_loader
 				.acquire_next_image(
 					base.swapchain,
 					std::u64::MAX,
 					base.present_complete_semaphore,
 					vk::Fence::null(),
-				)
-				.unwrap();
+				) {
+				Ok((present_index, _)) => present_index,
+				Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return false,
+				Err(e) => panic!("acquire_next_image: {:?}", e),
+			};
 			record_submit_commandbuffer(
 				&base.device,
 				base.draw_command_buffer,
@@ -185,92 +955,20 @@ impl LayerCompositor {
 				&[base.present_complete_semaphore],
 				&[base.rendering_complete_semaphore],
 				|device, command_buffer| {
-					for lo in self.los.iter_mut() {
-						if let Some(cache) = lo.cache.as_mut() {
-							if cache.damage {
-								let layer = lo.layer.read().unwrap();
-								layer.render(command_buffer, 0);
-								cache.damage = false;
-							}
-						}
-					}
 					let image = base.present_images[present_index as usize];
-					let bb = BarrierBuilder::new(device.clone(), command_buffer);
-					bb.build(
-						image,
-						vk::ImageLayout::UNDEFINED,
-						vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-					);
-					device.cmd_clear_color_image(
+					// the acquire semaphore is waited at COLOR_ATTACHMENT_OUTPUT
+					base.image_tracker.lock().unwrap().assume(image, ImageState {
+						layout: vk::ImageLayout::UNDEFINED,
+						access: vk::AccessFlags::empty(),
+						stage: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+					});
+					self.record_frame(&base, command_buffer, present_index as usize);
+					base.image_tracker.lock().unwrap().require(
+						device,
 						command_buffer,
 						image,
-						vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-						&vk::ClearColorValue {
-							float32: [0.0, 0.0, 0.0, 0.0],
-						},
-						&[bb.subresource_range],
-					);
-					bb.build(
-						image,
-						vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-						vk::ImageLayout::PRESENT_SRC_KHR,
+						ImageUsage::Present,
 					);
-					let subresource = vk::ImageSubresourceLayers {
-						aspect_mask: vk::ImageAspectFlags::COLOR,
-						mip_level: 0,
-						base_array_layer: 0,
-						layer_count: 1,
-					};
-					let whole_region = vk::ImageCopy {
-						src_subresource: subresource,
-						dst_subresource: subresource,
-						extent: base.render_resolution.into(),
-						..Default::default()
-					};
-					let mut prev_copy = false;
-					for lo in self.los.iter() {
-						if let Some(cache) = &lo.cache {
-							if !prev_copy {
-								bb.build(
-									image,
-									vk::ImageLayout::PRESENT_SRC_KHR,
-									vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-								);
-							}
-							prev_copy = true;
-							bb.build(
-								cache.image,
-								vk::ImageLayout::PRESENT_SRC_KHR,
-								vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-							);
-							device.cmd_copy_image(
-								command_buffer,
-								cache.image,
-								vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-								image,
-								vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-								&[whole_region],
-							);
-						} else {
-							if prev_copy {
-								bb.build(
-									image,
-									vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-									vk::ImageLayout::PRESENT_SRC_KHR,
-								);
-							}
-							prev_copy = false;
-							let layer = lo.layer.read().unwrap();
-							layer.render(command_buffer, present_index as usize);
-						}
-					}
-					if prev_copy {
-						bb.build(
-							image,
-							vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-							vk::ImageLayout::PRESENT_SRC_KHR,
-						);
-					}
 				},
 			);
 			let wait_semaphors = [base.rendering_complete_semaphore];
@@ -281,9 +979,76 @@ impl LayerCompositor {
 				.swapchains(&swapchains)
 				.image_indices(&image_indices);
 	
-			base.swapchain_loader
-				.queue_present(base.present_queue, &present_info)
-				.unwrap();
+			match base.swapchain_loader.queue_present(base.present_queue, &present_info) {
+				Ok(suboptimal) => !suboptimal,
+				Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => false,
+				Err(e) => panic!("queue_present: {:?}", e),
+			}
 		}
 	}
+
+	// the next frame recreates the swapchain
+	pub fn is_stale(&self) -> bool {
+		self.stale && !self.minimized()
+	}
+}
+
+// copy the composited frame into a transient and chain the effect draws
+// through transients, the last one writing `output`
+fn build_effects<'a>(
+	base: &Base,
+	graph: &mut RenderGraph<'a>,
+	draws: Vec<(&'a EffectPass, EffectConstants)>,
+	composited: ResourceId,
+	output: ResourceId,
+) {
+	let desc = TransientDesc {
+		extent: base.render_resolution,
+		format: base.surface_format.format,
+		usage: vk::ImageUsageFlags::COLOR_ATTACHMENT
+			| vk::ImageUsageFlags::SAMPLED
+			| vk::ImageUsageFlags::TRANSFER_DST,
+	};
+	let mut src = graph.transient(desc);
+	let subresource = vk::ImageSubresourceLayers {
+		aspect_mask: vk::ImageAspectFlags::COLOR,
+		mip_level: 0,
+		base_array_layer: 0,
+		layer_count: 1,
+	};
+	let whole_region = vk::ImageCopy {
+		src_subresource: subresource,
+		dst_subresource: subresource,
+		extent: base.render_resolution.into(),
+		..Default::default()
+	};
+	graph.add_pass(
+		&[(composited, ImageUsage::TransferSrc)],
+		&[(src, ImageUsage::TransferDst)],
+		move |ctx| unsafe {
+			ctx.device.cmd_copy_image(
+				ctx.command_buffer,
+				ctx.image(composited),
+				vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+				ctx.image(src),
+				vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+				&[whole_region],
+			);
+		},
+	);
+	// ping-pong between transients, the pool aliases the ones no longer read
+	let count = draws.len();
+	for (i, (pass, constants)) in draws.into_iter().enumerate() {
+		let dst = if i + 1 == count {
+			output
+		} else {
+			graph.transient(desc)
+		};
+		graph.add_pass(
+			&[(src, ImageUsage::Sampled)],
+			&[(dst, ImageUsage::ColorAttachment)],
+			move |ctx| pass.draw(ctx.command_buffer, ctx.view(src), ctx.view(dst), &constants),
+		);
+		src = dst;
+	}
 }
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::default::Default;
+use std::io::Cursor;
+use std::mem;
+use std::sync::Mutex;
+use ash::util::*;
+use ash::vk;
+
+use crate::base::Base;
+use crate::layer::pipeline::{Pipeline, PipelineBuilder};
+
+#[derive(Clone, Debug)]
+pub enum Effect {
+	GaussianBlur {
+		sigma: f32,
+	},
+	// color = matrix * color + offset, matrix is column major
+	ColorMatrix {
+		matrix: [[f32; 4]; 4],
+		offset: [f32; 4],
+	},
+	Vignette {
+		radius: f32,
+		softness: f32,
+		strength: f32,
+	},
+	Crt {
+		scanline: f32,
+		curvature: f32,
+	},
+	// fragment shader given to `LayerCompositor::push_custom_effect`
+	Custom {
+		params: [[f32; 4]; 5],
+	},
+}
+
+// push constants of every effect shader, see src/shader/fullscreen.vert
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub struct EffectConstants {
+	pub params: [[f32; 4]; 5],
+	pub resolution: [f32; 2],
+	pub direction: [f32; 2],
+}
+
+impl Effect {
+	fn fragment_spv(&self) -> Option<&'static [u8]> {
+		Some(match self {
+			Self::GaussianBlur { .. } =>
+				&include_bytes!("../assets/spvs/blur_frag.spv")[..],
+			Self::ColorMatrix { .. } =>
+				&include_bytes!("../assets/spvs/color_matrix_frag.spv")[..],
+			Self::Vignette { .. } =>
+				&include_bytes!("../assets/spvs/vignette_frag.spv")[..],
+			Self::Crt { .. } =>
+				&include_bytes!("../assets/spvs/crt_frag.spv")[..],
+			Self::Custom { .. } => return None,
+		})
+	}
+
+	// one entry per draw, the blur is separable
+	pub fn constants(&self, resolution: vk::Extent2D) -> Vec<EffectConstants> {
+		let base = EffectConstants {
+			resolution: [resolution.width as f32, resolution.height as f32],
+			..Default::default()
+		};
+		match *self {
+			Self::GaussianBlur { sigma } => {
+				let mut params = [[0.0; 4]; 5];
+				params[0][0] = sigma;
+				vec![
+					EffectConstants { params, direction: [1.0, 0.0], ..base },
+					EffectConstants { params, direction: [0.0, 1.0], ..base },
+				]
+			}
+			Self::ColorMatrix { matrix, offset } => {
+				let params = [matrix[0], matrix[1], matrix[2], matrix[3], offset];
+				vec![EffectConstants { params, ..base }]
+			}
+			Self::Vignette { radius, softness, strength } => {
+				let mut params = [[0.0; 4]; 5];
+				params[0] = [radius, softness, strength, 0.0];
+				vec![EffectConstants { params, ..base }]
+			}
+			Self::Crt { scanline, curvature } => {
+				let mut params = [[0.0; 4]; 5];
+				params[0] = [scanline, curvature, 0.0, 0.0];
+				vec![EffectConstants { params, ..base }]
+			}
+			Self::Custom { params } => vec![EffectConstants { params, ..base }],
+		}
+	}
+}
+
+// a fullscreen draw sampling one image into another
+pub struct EffectPass {
+	device: ash::Device,
+	pipeline: Pipeline,
+	sampler: vk::Sampler,
+	// sets of the pipeline's layout, one per source
+	descriptor_pool: vk::DescriptorPool,
+	// keyed by image view, emptied by `forget_views` when the pooled and
+	// present images are replaced
+	framebuffers: Mutex<HashMap<vk::ImageView, vk::Framebuffer>>,
+	descriptor_sets: Mutex<HashMap<vk::ImageView, vk::DescriptorSet>>,
+}
+
+const MAX_SOURCES: u32 = 32;
+
+impl EffectPass {
+	pub fn new(base: &Base, effect: &Effect, custom_spv: Option<&[u32]>) -> Self { unsafe {
+		let device = &base.device;
+
+		let vertex_code = read_spv(&mut Cursor::new(
+			&include_bytes!("../assets/spvs/fullscreen_vert.spv")[..],
+		))
+			.expect("Failed to read vertex shader spv file");
+		let frag_code = match (effect.fragment_spv(), custom_spv) {
+			(Some(spv), _) => read_spv(&mut Cursor::new(spv))
+				.expect("Failed to read fragment shader spv file"),
+			(None, Some(code)) => code.to_vec(),
+			(None, None) => panic!("custom effect without fragment shader"),
+		};
+		// every pixel is written, the destination is not loaded
+		let pipeline = PipelineBuilder::from_code(vertex_code, frag_code)
+			.load_op(vk::AttachmentLoadOp::DONT_CARE)
+			.texture(0)
+			.push_constants(
+				vk::ShaderStageFlags::FRAGMENT,
+				mem::size_of::<EffectConstants>() as u32,
+			)
+			.build(base);
+
+		let sampler_info = vk::SamplerCreateInfo {
+			mag_filter: vk::Filter::LINEAR,
+			min_filter: vk::Filter::LINEAR,
+			mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+			address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+			address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+			address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+			max_anisotropy: 1.0,
+			border_color: vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+			compare_op: vk::CompareOp::NEVER,
+			..Default::default()
+		};
+		let sampler = device.create_sampler(&sampler_info, None).unwrap();
+
+		let descriptor_sizes = [
+			vk::DescriptorPoolSize {
+				ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+				descriptor_count: MAX_SOURCES,
+			},
+		];
+		let descriptor_pool_info = vk::DescriptorPoolCreateInfo::default()
+			.pool_sizes(&descriptor_sizes)
+			.max_sets(MAX_SOURCES);
+		let descriptor_pool = device
+			.create_descriptor_pool(&descriptor_pool_info, None)
+			.unwrap();
+
+		Self {
+			device: device.clone(),
+			pipeline,
+			sampler,
+			descriptor_pool,
+			framebuffers: Mutex::new(HashMap::new()),
+			descriptor_sets: Mutex::new(HashMap::new()),
+		}
+	}}
+
+	pub fn extent(&self) -> vk::Extent2D {
+		self.pipeline.extent()
+	}
+
+	// the views died with their images, new ones may reuse the handles.
+	// nothing drawn by this pass may still be executing
+	pub fn forget_views(&mut self) { unsafe {
+		for (_, framebuffer) in self.framebuffers.get_mut().unwrap().drain() {
+			self.device.destroy_framebuffer(framebuffer, None);
+		}
+		self.descriptor_sets.get_mut().unwrap().clear();
+		self.device
+			.reset_descriptor_pool(self.descriptor_pool, vk::DescriptorPoolResetFlags::empty())
+			.unwrap();
+	}}
+
+	unsafe fn framebuffer(&self, view: vk::ImageView) -> vk::Framebuffer {
+		*self.framebuffers.lock().unwrap().entry(view).or_insert_with(|| {
+			let framebuffer_attachments = [view];
+			let frame_buffer_create_info = vk::FramebufferCreateInfo::default()
+				.render_pass(self.pipeline.render_pass())
+				.attachments(&framebuffer_attachments)
+				.width(self.extent().width)
+				.height(self.extent().height)
+				.layers(1);
+			self.device
+				.create_framebuffer(&frame_buffer_create_info, None)
+				.unwrap()
+		})
+	}
+
+	unsafe fn descriptor_set(&self, view: vk::ImageView) -> vk::DescriptorSet {
+		*self.descriptor_sets.lock().unwrap().entry(view).or_insert_with(|| {
+			let set_layouts = [self.pipeline.descriptor_set_layout()];
+			let desc_alloc_info = vk::DescriptorSetAllocateInfo::default()
+				.descriptor_pool(self.descriptor_pool)
+				.set_layouts(&set_layouts);
+			let descriptor_set = self.device
+				.allocate_descriptor_sets(&desc_alloc_info)
+				.expect("too many effect sources")[0];
+			let image_descriptor = vk::DescriptorImageInfo {
+				image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+				image_view: view,
+				sampler: self.sampler,
+			};
+			let write_desc_sets = [
+				vk::WriteDescriptorSet {
+					dst_set: descriptor_set,
+					descriptor_count: 1,
+					descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+					p_image_info: &image_descriptor,
+					..Default::default()
+				},
+			];
+			self.device.update_descriptor_sets(&write_desc_sets, &[]);
+			descriptor_set
+		})
+	}
+
+	// `src` must be SHADER_READ_ONLY_OPTIMAL and `dst` COLOR_ATTACHMENT_OPTIMAL
+	pub fn draw(
+		&self,
+		command_buffer: vk::CommandBuffer,
+		src: vk::ImageView,
+		dst: vk::ImageView,
+		constants: &EffectConstants,
+	) { unsafe {
+		let device = &self.device;
+		let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+			.render_pass(self.pipeline.render_pass())
+			.framebuffer(self.framebuffer(dst))
+			.render_area(self.extent().into());
+		device.cmd_begin_render_pass(
+			command_buffer,
+			&render_pass_begin_info,
+			vk::SubpassContents::INLINE,
+		);
+		self.pipeline.bind_with(command_buffer, self.descriptor_set(src));
+		device.cmd_push_constants(
+			command_buffer,
+			self.pipeline.layout(),
+			vk::ShaderStageFlags::FRAGMENT,
+			0,
+			std::slice::from_raw_parts(
+				constants as *const EffectConstants as *const u8,
+				mem::size_of::<EffectConstants>(),
+			),
+		);
+		device.cmd_draw(command_buffer, 3, 1, 0, 0);
+		device.cmd_end_render_pass(command_buffer);
+	}}
+}
+
+impl Drop for EffectPass {
+	fn drop(&mut self) { unsafe {
+		let device = &self.device;
+		device.device_wait_idle().unwrap();
+		for (_, framebuffer) in self.framebuffers.get_mut().unwrap().drain() {
+			device.destroy_framebuffer(framebuffer, None);
+		}
+		device.destroy_descriptor_pool(self.descriptor_pool, None);
+		device.destroy_sampler(self.sampler, None);
+	}}
+}
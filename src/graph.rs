@@ -0,0 +1,519 @@
+use ash::vk;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::base::{Base, find_memorytype_index};
+use crate::profiler::GpuTimer;
+use crate::tracker::{ImageTrackerRef, ImageUsage};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PassId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransientDesc {
+	pub extent: vk::Extent2D,
+	pub format: vk::Format,
+	pub usage: vk::ImageUsageFlags,
+}
+
+enum Resource {
+	Imported(vk::Image, vk::ImageView),
+	Transient(TransientDesc),
+}
+
+pub struct PassContext<'b> {
+	pub device: &'b ash::Device,
+	pub command_buffer: vk::CommandBuffer,
+	images: &'b [vk::Image],
+	views: &'b [vk::ImageView],
+}
+
+impl<'b> PassContext<'b> {
+	pub fn image(&self, id: ResourceId) -> vk::Image {
+		self.images[id.0]
+	}
+
+	// null for resources imported without a view
+	pub fn view(&self, id: ResourceId) -> vk::ImageView {
+		self.views[id.0]
+	}
+}
+
+type RecordFn<'a> = Box<dyn FnOnce(&PassContext) + 'a>;
+
+struct Pass<'a> {
+	reads: Vec<(ResourceId, ImageUsage)>,
+	writes: Vec<(ResourceId, ImageUsage)>,
+	record: RecordFn<'a>,
+	// gpu time is accumulated per scope when profiled
+	scope: Option<usize>,
+}
+
+// passes run in dependency order: a pass reading a resource runs after every
+// pass writing it, and writers of the same resource keep insertion order
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+	resources: Vec<Resource>,
+	outputs: Vec<ResourceId>,
+	passes: Vec<Pass<'a>>,
+	timer: Option<&'a mut GpuTimer>,
+}
+
+impl<'a> RenderGraph<'a> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn import(&mut self, image: vk::Image) -> ResourceId {
+		self.import_view(image, vk::ImageView::null())
+	}
+
+	pub fn import_view(&mut self, image: vk::Image, view: vk::ImageView) -> ResourceId {
+		self.resources.push(Resource::Imported(image, view));
+		ResourceId(self.resources.len() - 1)
+	}
+
+	// backed by a pooled image only for the passes using it, content is
+	// undefined before the first pass
+	pub fn transient(&mut self, desc: TransientDesc) -> ResourceId {
+		self.resources.push(Resource::Transient(desc));
+		ResourceId(self.resources.len() - 1)
+	}
+
+	// passes not contributing to any output are culled
+	pub fn output(&mut self, id: ResourceId) {
+		self.outputs.push(id);
+	}
+
+	pub fn add_pass<F: FnOnce(&PassContext) + 'a>(
+		&mut self,
+		reads: &[(ResourceId, ImageUsage)],
+		writes: &[(ResourceId, ImageUsage)],
+		record: F,
+	) -> PassId {
+		self.passes.push(Pass {
+			reads: reads.to_vec(),
+			writes: writes.to_vec(),
+			record: Box::new(record),
+			scope: None,
+		});
+		PassId(self.passes.len() - 1)
+	}
+
+	pub fn set_scope(&mut self, pass: PassId, scope: usize) {
+		self.passes[pass.0].scope = Some(scope);
+	}
+
+	// time scoped passes with `timer`
+	pub fn profile(&mut self, timer: &'a mut GpuTimer) {
+		self.timer = Some(timer);
+	}
+
+	fn writers(&self) -> Vec<Vec<usize>> {
+		let mut writers = vec![Vec::new(); self.resources.len()];
+		for (idx, pass) in self.passes.iter().enumerate() {
+			for &(id, _) in pass.writes.iter() {
+				writers[id.0].push(idx);
+			}
+		}
+		writers
+	}
+
+	fn live_passes(&self, writers: &[Vec<usize>]) -> Vec<bool> {
+		let mut live = vec![false; self.passes.len()];
+		let mut stack: Vec<usize> = self.outputs
+			.iter()
+			.flat_map(|id| writers[id.0].iter().copied())
+			.collect();
+		while let Some(idx) = stack.pop() {
+			if live[idx] {
+				continue;
+			}
+			live[idx] = true;
+			for &(id, _) in self.passes[idx].reads.iter() {
+				stack.extend(writers[id.0].iter().copied());
+			}
+		}
+		live
+	}
+
+	fn order(&self) -> Vec<usize> {
+		let writers = self.writers();
+		let live = self.live_passes(&writers);
+		let mut deps = vec![Vec::new(); self.passes.len()];
+		for (idx, pass) in self.passes.iter().enumerate() {
+			if !live[idx] {
+				continue;
+			}
+			for &(id, _) in pass.writes.iter() {
+				if let Some(&prev) = writers[id.0].iter().rev().find(|&&w| w < idx) {
+					deps[idx].push(prev);
+				}
+			}
+			for &(id, _) in pass.reads.iter() {
+				deps[idx].extend(writers[id.0].iter().copied().filter(|&w| w != idx));
+			}
+		}
+		let mut dependents = vec![Vec::new(); self.passes.len()];
+		let mut pending = vec![0; self.passes.len()];
+		for (idx, list) in deps.iter_mut().enumerate() {
+			list.sort_unstable();
+			list.dedup();
+			pending[idx] = list.len();
+			for &dep in list.iter() {
+				dependents[dep].push(idx);
+			}
+		}
+		// prefer insertion order among ready passes
+		let mut ready: BinaryHeap<Reverse<usize>> = (0..self.passes.len())
+			.filter(|&idx| live[idx] && pending[idx] == 0)
+			.map(Reverse)
+			.collect();
+		let mut result = Vec::new();
+		while let Some(Reverse(idx)) = ready.pop() {
+			result.push(idx);
+			for &next in dependents[idx].iter() {
+				pending[next] -= 1;
+				if pending[next] == 0 {
+					ready.push(Reverse(next));
+				}
+			}
+		}
+		assert_eq!(
+			result.len(),
+			live.iter().filter(|&&x| x).count(),
+			"render graph has a cycle",
+		);
+		result
+	}
+
+	// first and last execution position using each resource
+	fn lifetimes(&self, order: &[usize]) -> Vec<Option<(usize, usize)>> {
+		let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.resources.len()];
+		for (pos, &idx) in order.iter().enumerate() {
+			let pass = &self.passes[idx];
+			for &(id, _) in pass.reads.iter().chain(pass.writes.iter()) {
+				let lifetime = lifetimes[id.0].get_or_insert((pos, pos));
+				lifetime.1 = pos;
+			}
+		}
+		lifetimes
+	}
+
+	pub fn execute(
+		mut self,
+		base: &Base,
+		pool: &mut TransientPool,
+		command_buffer: vk::CommandBuffer,
+	) {
+		let order = self.order();
+		let lifetimes = self.lifetimes(&order);
+
+		let mut images = vec![vk::Image::null(); self.resources.len()];
+		let mut views = vec![vk::ImageView::null(); self.resources.len()];
+		let mut first_use = vec![Vec::new(); order.len()];
+		let mut transients: Vec<(usize, usize, usize, TransientDesc)> = Vec::new();
+		for (id, resource) in self.resources.iter().enumerate() {
+			match resource {
+				Resource::Imported(image, view) => {
+					images[id] = *image;
+					views[id] = *view;
+				}
+				Resource::Transient(desc) => if let Some((first, last)) = lifetimes[id] {
+					transients.push((first, last, id, *desc));
+				},
+			}
+		}
+		transients.sort_by_key(|t| t.0);
+		pool.begin_frame();
+		for (first, last, id, desc) in transients.into_iter() {
+			let (image, view) = pool.acquire(base, desc, first, last);
+			images[id] = image;
+			views[id] = view;
+			first_use[first].push(image);
+		}
+
+		let mut passes: Vec<Option<Pass>> = self.passes.into_iter().map(Some).collect();
+		for (pos, &idx) in order.iter().enumerate() {
+			let pass = passes[idx].take().unwrap();
+			{
+				let mut tracker = base.image_tracker.lock().unwrap();
+				for &image in first_use[pos].iter() {
+					tracker.discard(image);
+				}
+				for &(id, usage) in pass.reads.iter().chain(pass.writes.iter()) {
+					tracker.require(&base.device, command_buffer, images[id.0], usage);
+				}
+			}
+			let ctx = PassContext {
+				device: &base.device,
+				command_buffer,
+				images: &images,
+				views: &views,
+			};
+			let timer = self.timer.as_mut().filter(|_| pass.scope.is_some());
+			let query = timer.and_then(|timer| timer.begin(command_buffer, pass.scope));
+			(pass.record)(&ctx);
+			if let (Some(timer), Some(query)) = (self.timer.as_mut(), query) {
+				timer.end(command_buffer, query);
+			}
+		}
+	}
+}
+
+struct PooledImage {
+	desc: TransientDesc,
+	image: vk::Image,
+	memory: vk::DeviceMemory,
+	view: vk::ImageView,
+	size: vk::DeviceSize,
+	// last execution position using it in the current frame
+	busy_until: Option<usize>,
+}
+
+// a pooled image matching `desc` that no pass uses from `first` on, which
+// is then busy until `last`
+fn claim(images: &mut [PooledImage], desc: TransientDesc, first: usize, last: usize) -> Option<usize> {
+	let idx = images.iter().position(|pooled| {
+		pooled.desc == desc && pooled.busy_until.map_or(true, |until| until < first)
+	})?;
+	images[idx].busy_until = Some(last);
+	Some(idx)
+}
+
+// physical images behind transient resources, kept across frames and shared
+// by transients whose lifetimes do not overlap
+pub struct TransientPool {
+	device: ash::Device,
+	tracker: ImageTrackerRef,
+	images: Vec<PooledImage>,
+}
+
+impl TransientPool {
+	pub fn new(base: &Base) -> Self {
+		Self {
+			device: base.device.clone(),
+			tracker: base.image_tracker.clone(),
+			images: Vec::new(),
+		}
+	}
+
+	// bytes of all pooled images
+	pub fn memory(&self) -> u64 {
+		self.images.iter().map(|pooled| pooled.size).sum()
+	}
+
+	fn begin_frame(&mut self) {
+		for pooled in self.images.iter_mut() {
+			pooled.busy_until = None;
+		}
+	}
+
+	fn acquire(
+		&mut self,
+		base: &Base,
+		desc: TransientDesc,
+		first: usize,
+		last: usize,
+	) -> (vk::Image, vk::ImageView) {
+		if let Some(idx) = claim(&mut self.images, desc, first, last) {
+			return (self.images[idx].image, self.images[idx].view);
+		}
+		let pooled = unsafe { Self::create(base, desc) };
+		let result = (pooled.image, pooled.view);
+		self.images.push(PooledImage {
+			busy_until: Some(last),
+			..pooled
+		});
+		result
+	}
+
+	unsafe fn create(base: &Base, desc: TransientDesc) -> PooledImage {
+		let create_info = vk::ImageCreateInfo::default()
+			.image_type(vk::ImageType::TYPE_2D)
+			.format(desc.format)
+			.extent(desc.extent.into())
+			.mip_levels(1)
+			.array_layers(1)
+			.samples(vk::SampleCountFlags::TYPE_1)
+			.tiling(vk::ImageTiling::OPTIMAL)
+			.usage(desc.usage);
+		let image = base.device.create_image(&create_info, None).unwrap();
+		let memory_req = base.device.get_image_memory_requirements(image);
+		let memory_index = find_memorytype_index(
+			&memory_req,
+			&base.device_memory_properties,
+			vk::MemoryPropertyFlags::DEVICE_LOCAL,
+		).unwrap();
+		let allocate_info = vk::MemoryAllocateInfo {
+			allocation_size: memory_req.size,
+			memory_type_index: memory_index,
+			..Default::default()
+		};
+		let memory = base.device.allocate_memory(&allocate_info, None).unwrap();
+		base.device.bind_image_memory(image, memory, 0).unwrap();
+		let view_info = vk::ImageViewCreateInfo::default()
+			.view_type(vk::ImageViewType::TYPE_2D)
+			.format(desc.format)
+			.components(vk::ComponentMapping {
+				r: vk::ComponentSwizzle::R,
+				g: vk::ComponentSwizzle::G,
+				b: vk::ComponentSwizzle::B,
+				a: vk::ComponentSwizzle::A,
+			})
+			.subresource_range(vk::ImageSubresourceRange {
+				aspect_mask: vk::ImageAspectFlags::COLOR,
+				base_mip_level: 0,
+				level_count: 1,
+				base_array_layer: 0,
+				layer_count: 1,
+			})
+			.image(image);
+		let view = base.device.create_image_view(&view_info, None).unwrap();
+		PooledImage {
+			desc,
+			image,
+			memory,
+			view,
+			size: memory_req.size,
+			busy_until: None,
+		}
+	}
+}
+
+impl Drop for TransientPool {
+	fn drop(&mut self) { unsafe {
+		if self.images.is_empty() {
+			return;
+		}
+		self.device.device_wait_idle().unwrap();
+		let mut tracker = self.tracker.lock().unwrap();
+		for pooled in self.images.drain(..) {
+			tracker.forget(pooled.image);
+			self.device.destroy_image_view(pooled.view, None);
+			self.device.destroy_image(pooled.image, None);
+			self.device.free_memory(pooled.memory, None);
+		}
+	}}
+}
+
+#[cfg(test)]
+mod tests {
+	use ash::vk;
+
+	use super::{claim, PooledImage, RenderGraph, ResourceId, TransientDesc};
+	use crate::tracker::ImageUsage;
+
+	const DESC: TransientDesc = TransientDesc {
+		extent: vk::Extent2D { width: 64, height: 64 },
+		format: vk::Format::R8G8B8A8_UNORM,
+		usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
+	};
+
+	fn read(id: ResourceId) -> [(ResourceId, ImageUsage); 1] {
+		[(id, ImageUsage::Sampled)]
+	}
+
+	fn write(id: ResourceId) -> [(ResourceId, ImageUsage); 1] {
+		[(id, ImageUsage::ColorAttachment)]
+	}
+
+	#[test]
+	fn readers_run_after_writers() {
+		let mut graph = RenderGraph::new();
+		let output = graph.import(vk::Image::null());
+		let a = graph.transient(DESC);
+		let b = graph.transient(DESC);
+		graph.output(output);
+		// added before what they read is written
+		graph.add_pass(&[read(a)[0], read(b)[0]], &write(output), |_| {});
+		graph.add_pass(&read(a), &write(b), |_| {});
+		graph.add_pass(&[], &write(a), |_| {});
+		assert_eq!(graph.order(), [2, 1, 0]);
+	}
+
+	#[test]
+	fn writers_keep_insertion_order() {
+		let mut graph = RenderGraph::new();
+		let output = graph.import(vk::Image::null());
+		let cache = graph.import(vk::Image::null());
+		graph.output(output);
+		graph.add_pass(&[], &write(output), |_| {});
+		graph.add_pass(&[], &write(cache), |_| {});
+		graph.add_pass(&read(cache), &write(output), |_| {});
+		graph.add_pass(&[], &write(output), |_| {});
+		assert_eq!(graph.order(), [0, 1, 2, 3]);
+	}
+
+	#[test]
+	fn unused_passes_are_culled() {
+		let mut graph = RenderGraph::new();
+		let output = graph.import(vk::Image::null());
+		let used = graph.transient(DESC);
+		let unused = graph.transient(DESC);
+		graph.output(output);
+		graph.add_pass(&[], &write(unused), |_| {});
+		graph.add_pass(&[], &write(used), |_| {});
+		graph.add_pass(&read(unused), &write(unused), |_| {});
+		graph.add_pass(&read(used), &write(output), |_| {});
+		assert_eq!(graph.order(), [1, 3]);
+		let live = graph.live_passes(&graph.writers());
+		assert_eq!(live, [false, true, false, true]);
+	}
+
+	#[test]
+	fn lifetimes_span_first_to_last_use() {
+		let mut graph = RenderGraph::new();
+		let output = graph.import(vk::Image::null());
+		let a = graph.transient(DESC);
+		let b = graph.transient(DESC);
+		let c = graph.transient(DESC);
+		graph.output(output);
+		graph.add_pass(&[], &write(a), |_| {});
+		graph.add_pass(&read(a), &write(b), |_| {});
+		graph.add_pass(&read(b), &write(c), |_| {});
+		graph.add_pass(&read(c), &write(output), |_| {});
+		let order = graph.order();
+		assert_eq!(
+			graph.lifetimes(&order),
+			[Some((3, 3)), Some((0, 1)), Some((1, 2)), Some((2, 3))],
+		);
+	}
+
+	fn pooled(desc: TransientDesc) -> PooledImage {
+		PooledImage {
+			desc,
+			image: vk::Image::null(),
+			memory: vk::DeviceMemory::null(),
+			view: vk::ImageView::null(),
+			size: 0,
+			busy_until: None,
+		}
+	}
+
+	#[test]
+	fn transients_alias_when_lifetimes_do_not_overlap() {
+		let mut images = vec![pooled(DESC), pooled(DESC)];
+		// a from 0 to 1, b from 1 to 2, c from 2 to 3 like above
+		assert_eq!(claim(&mut images, DESC, 0, 1), Some(0));
+		assert_eq!(claim(&mut images, DESC, 1, 2), Some(1));
+		assert_eq!(claim(&mut images, DESC, 2, 3), Some(0));
+		// b is done by then
+		assert_eq!(claim(&mut images, DESC, 3, 3), Some(1));
+		assert_eq!(claim(&mut images, DESC, 3, 3), None);
+	}
+
+	#[test]
+	fn transients_alias_only_the_same_desc() {
+		let small = TransientDesc {
+			extent: vk::Extent2D { width: 32, height: 32 },
+			..DESC
+		};
+		let mut images = vec![pooled(DESC)];
+		assert_eq!(claim(&mut images, small, 0, 0), None);
+		assert_eq!(claim(&mut images, DESC, 0, 0), Some(0));
+	}
+}
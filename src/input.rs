@@ -0,0 +1,193 @@
+use winit::dpi::PhysicalPosition;
+use winit::event::{
+	ElementState,
+	MouseButton,
+	MouseScrollDelta,
+	VirtualKeyCode,
+	WindowEvent,
+};
+
+// pixels per line for pixel based scroll deltas
+const PIXELS_PER_LINE: f32 = 20.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+	pub min: [f32; 2],
+	pub max: [f32; 2],
+}
+
+impl Rect {
+	pub fn contains(&self, [x, y]: [f32; 2]) -> bool {
+		x >= self.min[0] && x <= self.max[0] && y >= self.min[1] && y <= self.max[1]
+	}
+}
+
+// pointer positions are in the coordinate space of the receiving layer,
+// normalized device coordinates of its output unless the layer is nested
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+	PointerMoved {
+		position: [f32; 2],
+	},
+	// the pointer left the layer or the window
+	PointerLeft,
+	PointerButton {
+		position: [f32; 2],
+		button: MouseButton,
+		pressed: bool,
+	},
+	// in lines
+	Scroll {
+		position: [f32; 2],
+		delta: [f32; 2],
+	},
+	Key {
+		key: Option<VirtualKeyCode>,
+		scancode: u32,
+		pressed: bool,
+	},
+	Char(char),
+}
+
+impl InputEvent {
+	pub fn position(&self) -> Option<[f32; 2]> {
+		match *self {
+			Self::PointerMoved { position } |
+			Self::PointerButton { position, .. } |
+			Self::Scroll { position, .. } => Some(position),
+			_ => None,
+		}
+	}
+
+	pub fn is_pointer(&self) -> bool {
+		matches!(self, Self::PointerLeft) || self.position().is_some()
+	}
+
+	pub fn map_position(&self, f: impl FnOnce([f32; 2]) -> [f32; 2]) -> Self {
+		let mut event = *self;
+		match &mut event {
+			Self::PointerMoved { position } |
+			Self::PointerButton { position, .. } |
+			Self::Scroll { position, .. } => *position = f(*position),
+			_ => {},
+		}
+		event
+	}
+}
+
+// requests of a handler to the dispatching stack
+#[derive(Default)]
+pub struct InputContext {
+	capture: Option<bool>,
+	focus: Option<bool>,
+	update: bool,
+}
+
+impl InputContext {
+	// receive every pointer event until released, hit or not
+	pub fn capture(&mut self) {
+		self.capture = Some(true);
+	}
+
+	pub fn release(&mut self) {
+		self.capture = Some(false);
+	}
+
+	// receive keyboard events first
+	pub fn focus(&mut self) {
+		self.focus = Some(true);
+	}
+
+	pub fn blur(&mut self) {
+		self.focus = Some(false);
+	}
+
+	// the layer needs to be rendered again
+	pub fn update(&mut self) {
+		self.update = true;
+	}
+
+	pub fn capture_request(&self) -> Option<bool> {
+		self.capture
+	}
+
+	pub fn focus_request(&self) -> Option<bool> {
+		self.focus
+	}
+
+	pub fn update_requested(&self) -> bool {
+		self.update
+	}
+}
+
+pub trait InputHandler {
+	// hit area in the layer's coordinate space, none: everywhere
+	fn bounds(&self) -> Option<Rect> {
+		None
+	}
+	// from the parent's coordinate space to the layer's
+	fn to_local(&self, position: [f32; 2]) -> [f32; 2] {
+		position
+	}
+	// true if consumed, the event is not given to layers below
+	fn handle(&mut self, event: &InputEvent, ctx: &mut InputContext) -> bool;
+}
+
+// turns window events into input events in normalized device coordinates
+#[derive(Default)]
+pub struct InputTranslator {
+	cursor: Option<[f32; 2]>,
+}
+
+impl InputTranslator {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn cursor(&self) -> Option<[f32; 2]> {
+		self.cursor
+	}
+
+	pub fn translate(&mut self, event: &WindowEvent, size: [u32; 2]) -> Option<InputEvent> {
+		Some(match *event {
+			WindowEvent::CursorMoved { position, .. } => {
+				let position = to_ndc(position, size);
+				self.cursor = Some(position);
+				InputEvent::PointerMoved { position }
+			}
+			WindowEvent::CursorLeft { .. } => {
+				self.cursor = None;
+				InputEvent::PointerLeft
+			}
+			WindowEvent::MouseInput { state, button, .. } => InputEvent::PointerButton {
+				position: self.cursor?,
+				button,
+				pressed: state == ElementState::Pressed,
+			},
+			WindowEvent::MouseWheel { delta, .. } => InputEvent::Scroll {
+				position: self.cursor?,
+				delta: match delta {
+					MouseScrollDelta::LineDelta(x, y) => [x, y],
+					MouseScrollDelta::PixelDelta(d) => [
+						d.x as f32 / PIXELS_PER_LINE,
+						d.y as f32 / PIXELS_PER_LINE,
+					],
+				},
+			},
+			WindowEvent::KeyboardInput { input, .. } => InputEvent::Key {
+				key: input.virtual_keycode,
+				scancode: input.scancode,
+				pressed: input.state == ElementState::Pressed,
+			},
+			WindowEvent::ReceivedCharacter(c) => InputEvent::Char(c),
+			_ => return None,
+		})
+	}
+}
+
+fn to_ndc(position: PhysicalPosition<f64>, [width, height]: [u32; 2]) -> [f32; 2] {
+	[
+		(position.x / width.max(1) as f64 * 2.0 - 1.0) as f32,
+		(position.y / height.max(1) as f64 * 2.0 - 1.0) as f32,
+	]
+}
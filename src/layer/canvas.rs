@@ -0,0 +1,525 @@
+use ash::vk;
+use lyon_tessellation::{
+	BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator,
+	StrokeVertex, TessellationError, VertexBuffers,
+};
+use lyon_tessellation::math::point;
+use lyon_tessellation::path::{Path, PathEvent};
+use lyon_tessellation::path::iterator::PathIterator;
+use std::f32::consts::{FRAC_PI_2, TAU};
+use std::sync::{Arc, RwLock};
+
+use crate::base::BaseRef;
+use crate::layer::{Layer, RenderTarget};
+use crate::layer::pipeline::BlendMode;
+use crate::layer::triangles::{Indices, Triangles, Vertex};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FillRule {
+	EvenOdd,
+	NonZero,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineJoin {
+	Miter,
+	Round,
+	Bevel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineCap {
+	Butt,
+	Square,
+	Round,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stroke {
+	pub width: f32,
+	pub join: LineJoin,
+	// miters longer than this many half widths are beveled
+	pub miter_limit: f32,
+	pub cap: LineCap,
+	// alternating dash and gap lengths, empty draws a solid line
+	pub dashes: Vec<f32>,
+	// into the dash pattern at the start of every subpath
+	pub dash_offset: f32,
+}
+
+impl Default for Stroke {
+	fn default() -> Self {
+		Self {
+			width: 1.0,
+			join: LineJoin::Miter,
+			miter_limit: 4.0,
+			cap: LineCap::Butt,
+			dashes: Vec::new(),
+			dash_offset: 0.0,
+		}
+	}
+}
+
+impl Stroke {
+	pub fn new(width: f32) -> Self {
+		Self {
+			width,
+			..Default::default()
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Segment {
+	MoveTo([f32; 2]),
+	LineTo([f32; 2]),
+	QuadTo([f32; 2], [f32; 2]),
+	CubicTo([f32; 2], [f32; 2], [f32; 2]),
+	Close,
+}
+
+// paths filled and stroked into the triangles of a `Triangles` layer, like
+// a 2d canvas. coordinates are world coordinates of `triangles.camera` in
+// `triangles.coordinates`, `Coordinates::Pixels` makes them pixels. edges
+// are anti-aliased with multisampling and colors are alpha blended
+pub struct Canvas {
+	pub triangles: Triangles,
+	// maximum distance between curves and their flattened lines
+	pub tolerance: f32,
+	path: Vec<Segment>,
+	// of the subpath being built
+	start: Option<[f32; 2]>,
+	current: Option<[f32; 2]>,
+}
+
+impl Canvas {
+	pub fn new_ref(base: BaseRef) -> Arc<RwLock<Self>> {
+		Arc::new(RwLock::new(Self::new(base)))
+	}
+
+	pub fn new(base: BaseRef) -> Self {
+		Self::with_samples(base, vk::SampleCountFlags::TYPE_4)
+	}
+
+	// TYPE_1 leaves the edges aliased
+	pub fn with_samples(base: BaseRef, samples: vk::SampleCountFlags) -> Self {
+		let builder = Triangles::builder()
+			.blend_mode(BlendMode::Alpha)
+			.samples(samples);
+		let mut triangles = Triangles::with_builder(base, &builder, 0);
+		triangles.indices = Indices::U32(Vec::new());
+		Self {
+			triangles,
+			tolerance: 0.01,
+			path: Vec::new(),
+			start: None,
+			current: None,
+		}
+	}
+
+	// removes everything filled and stroked so far and the current path
+	pub fn clear(&mut self) {
+		self.triangles.vertices.clear();
+		self.triangles.indices = Indices::U32(Vec::new());
+		self.begin_path();
+	}
+
+	pub fn begin_path(&mut self) {
+		self.path.clear();
+		self.start = None;
+		self.current = None;
+	}
+
+	pub fn move_to(&mut self, to: [f32; 2]) -> &mut Self {
+		self.path.push(Segment::MoveTo(to));
+		self.start = Some(to);
+		self.current = Some(to);
+		self
+	}
+
+	// starts a subpath at `to` if there is none
+	pub fn line_to(&mut self, to: [f32; 2]) -> &mut Self {
+		if self.current.is_none() {
+			return self.move_to(to);
+		}
+		self.path.push(Segment::LineTo(to));
+		self.current = Some(to);
+		self
+	}
+
+	pub fn quad_to(&mut self, ctrl: [f32; 2], to: [f32; 2]) -> &mut Self {
+		if self.current.is_none() {
+			self.move_to(ctrl);
+		}
+		self.path.push(Segment::QuadTo(ctrl, to));
+		self.current = Some(to);
+		self
+	}
+
+	pub fn cubic_to(&mut self, ctrl1: [f32; 2], ctrl2: [f32; 2], to: [f32; 2]) -> &mut Self {
+		if self.current.is_none() {
+			self.move_to(ctrl1);
+		}
+		self.path.push(Segment::CubicTo(ctrl1, ctrl2, to));
+		self.current = Some(to);
+		self
+	}
+
+	// from angle `start` to `end` in radians, counterclockwise if `end` is
+	// larger. connected to the current point with a line like html canvas
+	pub fn arc(&mut self, center: [f32; 2], radius: f32, start: f32, end: f32) -> &mut Self {
+		let at = |angle: f32| [center[0] + radius * angle.cos(), center[1] + radius * angle.sin()];
+		if self.current != Some(at(start)) {
+			self.line_to(at(start));
+		}
+		let sweep = (end - start).clamp(-TAU, TAU);
+		// cubics approximate at most a quarter circle well
+		let count = (sweep.abs() / FRAC_PI_2).ceil().max(1.0) as usize;
+		let step = sweep / count as f32;
+		let k = 4.0 / 3.0 * (step / 4.0).tan() * radius;
+		for i in 0..count {
+			let a0 = start + step * i as f32;
+			let a1 = a0 + step;
+			let (p0, p1) = (at(a0), at(a1));
+			self.cubic_to(
+				[p0[0] - k * a0.sin(), p0[1] + k * a0.cos()],
+				[p1[0] + k * a1.sin(), p1[1] - k * a1.cos()],
+				p1,
+			);
+		}
+		self
+	}
+
+	// the next segment starts a new subpath at the start of this one
+	pub fn close(&mut self) -> &mut Self {
+		if self.current.is_some() {
+			self.path.push(Segment::Close);
+			self.current = self.start;
+		}
+		self
+	}
+
+	pub fn rect(&mut self, min: [f32; 2], max: [f32; 2]) -> &mut Self {
+		self.move_to(min)
+			.line_to([max[0], min[1]])
+			.line_to(max)
+			.line_to([min[0], max[1]])
+			.close()
+	}
+
+	pub fn circle(&mut self, center: [f32; 2], radius: f32) -> &mut Self {
+		self.move_to([center[0] + radius, center[1]]);
+		self.arc(center, radius, 0.0, TAU).close()
+	}
+
+	fn build_path(&self) -> Path {
+		let mut builder = Path::builder();
+		let mut open = false;
+		let mut start = [0.0; 2];
+		for &segment in self.path.iter() {
+			// segments after `close` continue from the start of the closed subpath
+			if !open && !matches!(segment, Segment::MoveTo(_) | Segment::Close) {
+				builder.begin(point(start[0], start[1]));
+				open = true;
+			}
+			match segment {
+				Segment::MoveTo(to) => {
+					if open {
+						builder.end(false);
+					}
+					builder.begin(point(to[0], to[1]));
+					open = true;
+					start = to;
+				}
+				Segment::LineTo(to) => {
+					builder.line_to(point(to[0], to[1]));
+				}
+				Segment::QuadTo(ctrl, to) => {
+					builder.quadratic_bezier_to(point(ctrl[0], ctrl[1]), point(to[0], to[1]));
+				}
+				Segment::CubicTo(ctrl1, ctrl2, to) => {
+					builder.cubic_bezier_to(
+						point(ctrl1[0], ctrl1[1]),
+						point(ctrl2[0], ctrl2[1]),
+						point(to[0], to[1]),
+					);
+				}
+				Segment::Close => {
+					if open {
+						builder.end(true);
+						open = false;
+					}
+				}
+			}
+		}
+		if open {
+			builder.end(false);
+		}
+		builder.build()
+	}
+
+	fn output(&mut self) -> (&mut Vec<Vertex>, &mut Vec<u32>) {
+		if let Indices::U16(indices) = &self.triangles.indices {
+			let indices = indices.iter().map(|&index| index as u32).collect();
+			self.triangles.indices = Indices::U32(indices);
+		}
+		match &mut self.triangles.indices {
+			Indices::U32(indices) => (&mut self.triangles.vertices, indices),
+			Indices::U16(_) => unreachable!(),
+		}
+	}
+
+	// the current path, subpaths are closed implicitly. nothing is added if
+	// the path can't be tessellated, e.g. has NaN coordinates
+	pub fn fill(&mut self, color: [f32; 4], rule: FillRule) -> Result<(), TessellationError> {
+		let path = self.build_path();
+		let options = FillOptions::tolerance(self.tolerance).with_fill_rule(match rule {
+			FillRule::EvenOdd => lyon_tessellation::FillRule::EvenOdd,
+			FillRule::NonZero => lyon_tessellation::FillRule::NonZero,
+		});
+		let mut buffers: VertexBuffers<Vertex, u32> = VertexBuffers::new();
+		FillTessellator::new()
+			.tessellate_path(
+				&path,
+				&options,
+				&mut BuffersBuilder::new(&mut buffers, |vertex: FillVertex| {
+					let [x, y] = vertex.position().to_array();
+					Vertex { pos: [x, y, 0.0, 1.0], color }
+				}),
+			)?;
+		self.append(buffers);
+		Ok(())
+	}
+
+	// the current path, see `fill`
+	pub fn stroke(&mut self, color: [f32; 4], stroke: &Stroke) -> Result<(), TessellationError> {
+		let path = self.build_path();
+		let path = if stroke.dashes.iter().any(|&dash| dash > 0.0) {
+			dash(&path, self.tolerance, &stroke.dashes, stroke.dash_offset)
+		} else {
+			path
+		};
+		let options = StrokeOptions::tolerance(self.tolerance)
+			.with_line_width(stroke.width)
+			.with_miter_limit(stroke.miter_limit.max(1.0))
+			.with_line_join(match stroke.join {
+				LineJoin::Miter => lyon_tessellation::LineJoin::Miter,
+				LineJoin::Round => lyon_tessellation::LineJoin::Round,
+				LineJoin::Bevel => lyon_tessellation::LineJoin::Bevel,
+			})
+			.with_line_cap(match stroke.cap {
+				LineCap::Butt => lyon_tessellation::LineCap::Butt,
+				LineCap::Square => lyon_tessellation::LineCap::Square,
+				LineCap::Round => lyon_tessellation::LineCap::Round,
+			});
+		let mut buffers: VertexBuffers<Vertex, u32> = VertexBuffers::new();
+		StrokeTessellator::new()
+			.tessellate_path(
+				&path,
+				&options,
+				&mut BuffersBuilder::new(&mut buffers, |vertex: StrokeVertex| {
+					let [x, y] = vertex.position().to_array();
+					Vertex { pos: [x, y, 0.0, 1.0], color }
+				}),
+			)?;
+		self.append(buffers);
+		Ok(())
+	}
+
+	fn append(&mut self, buffers: VertexBuffers<Vertex, u32>) {
+		let (vertices, indices) = self.output();
+		let first = vertices.len() as u32;
+		vertices.extend(buffers.vertices);
+		indices.extend(buffers.indices.into_iter().map(|index| first + index));
+	}
+}
+
+// the dashes of `path` as open subpaths, curves flattened. odd arrays are
+// repeated like in html canvas, [a, b, c] dashes as [a, b, c, a, b, c]
+fn dash(path: &Path, tolerance: f32, dashes: &[f32], offset: f32) -> Path {
+	let dashes = if dashes.len() % 2 == 1 { dashes.repeat(2) } else { dashes.to_vec() };
+	let pattern: f32 = dashes.iter().map(|dash| dash.max(0.0)).sum();
+	let mut builder = Path::builder();
+	// index into `dashes`, distance left in it and whether it is drawn
+	let mut state = (0, 0.0, false);
+	let reset = |state: &mut (usize, f32, bool)| {
+		let mut offset = offset.rem_euclid(pattern);
+		let mut i = 0;
+		while offset >= dashes[i].max(0.0) {
+			offset -= dashes[i].max(0.0);
+			i = (i + 1) % dashes.len();
+		}
+		*state = (i, dashes[i].max(0.0) - offset, i % 2 == 0);
+	};
+	let mut open = false;
+	for event in path.iter().flattened(tolerance) {
+		let (from, to) = match event {
+			PathEvent::Begin { at } => {
+				reset(&mut state);
+				if state.2 {
+					builder.begin(at);
+					open = true;
+				}
+				continue;
+			}
+			PathEvent::Line { from, to } => (from, to),
+			PathEvent::End { last, first, close } => {
+				if !close {
+					if open {
+						builder.end(false);
+						open = false;
+					}
+					continue;
+				}
+				(last, first)
+			}
+			_ => continue,
+		};
+		let length = (to - from).length();
+		let mut done = 0.0;
+		while length - done > state.1 {
+			done += state.1;
+			let at = from.lerp(to, done / length);
+			if state.2 {
+				builder.line_to(at);
+				builder.end(false);
+				open = false;
+			} else {
+				builder.begin(at);
+				open = true;
+			}
+			let next = (state.0 + 1) % dashes.len();
+			state = (next, dashes[next].max(0.0), !state.2);
+		}
+		state.1 -= length - done;
+		if state.2 {
+			builder.line_to(to);
+		}
+		if let PathEvent::End { .. } = event {
+			if open {
+				builder.end(false);
+				open = false;
+			}
+		}
+	}
+	if open {
+		builder.end(false);
+	}
+	builder.build()
+}
+
+impl Layer for Canvas {
+	fn set_output(&mut self, images: Vec<vk::Image>) {
+		self.triangles.set_output(images);
+	}
+
+	fn resize(&mut self, extent: vk::Extent2D) {
+		self.triangles.resize(extent);
+	}
+
+	fn target(&self, idx: usize) -> Option<RenderTarget> {
+		self.triangles.target(idx)
+	}
+
+	fn prepare(&mut self, idx: usize) -> bool {
+		self.triangles.prepare(idx)
+	}
+
+	fn render(&self, command_buffer: vk::CommandBuffer, idx: usize) {
+		self.triangles.render(command_buffer, idx);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use lyon_tessellation::math::point;
+	use lyon_tessellation::path::{Path, PathEvent};
+
+	use super::dash;
+
+	// the points of every subpath
+	fn subpaths(path: &Path) -> Vec<Vec<[f32; 2]>> {
+		let mut subpaths: Vec<Vec<[f32; 2]>> = Vec::new();
+		for event in path.iter() {
+			match event {
+				PathEvent::Begin { at } => subpaths.push(vec![at.to_array()]),
+				PathEvent::Line { to, .. } => subpaths.last_mut().unwrap().push(to.to_array()),
+				_ => {}
+			}
+		}
+		subpaths
+	}
+
+	fn assert_dashes(path: &Path, expected: &[&[[f32; 2]]]) {
+		let subpaths = subpaths(path);
+		assert_eq!(subpaths.len(), expected.len(), "{:?}", subpaths);
+		for (subpath, expected) in subpaths.iter().zip(expected) {
+			assert_eq!(subpath.len(), expected.len(), "{:?}", subpaths);
+			for (a, b) in subpath.iter().zip(expected.iter()) {
+				assert!((a[0] - b[0]).abs() < 1e-4 && (a[1] - b[1]).abs() < 1e-4, "{:?}", subpaths);
+			}
+		}
+	}
+
+	fn line(length: f32) -> Path {
+		let mut builder = Path::builder();
+		builder.begin(point(0.0, 0.0));
+		builder.line_to(point(length, 0.0));
+		builder.end(false);
+		builder.build()
+	}
+
+	#[test]
+	fn dashes_along_a_line() {
+		assert_dashes(&dash(&line(10.0), 0.01, &[2.0, 1.0], 0.0), &[
+			&[[0.0, 0.0], [2.0, 0.0]],
+			&[[3.0, 0.0], [5.0, 0.0]],
+			&[[6.0, 0.0], [8.0, 0.0]],
+			&[[9.0, 0.0], [10.0, 0.0]],
+		]);
+	}
+
+	#[test]
+	fn offset_starts_into_the_pattern() {
+		assert_dashes(&dash(&line(10.0), 0.01, &[2.0, 1.0], 1.0), &[
+			&[[0.0, 0.0], [1.0, 0.0]],
+			&[[2.0, 0.0], [4.0, 0.0]],
+			&[[5.0, 0.0], [7.0, 0.0]],
+			&[[8.0, 0.0], [10.0, 0.0]],
+		]);
+		// a whole pattern further is the same
+		assert_eq!(
+			subpaths(&dash(&line(10.0), 0.01, &[2.0, 1.0], 4.0)),
+			subpaths(&dash(&line(10.0), 0.01, &[2.0, 1.0], 1.0)),
+		);
+	}
+
+	#[test]
+	fn odd_arrays_repeat() {
+		// dash 1, gap 2, dash 3, gap 1, dash 2, gap 3, so an offset of 6
+		// starts with a gap
+		assert_dashes(&dash(&line(12.0), 0.01, &[1.0, 2.0, 3.0], 6.0), &[
+			&[[1.0, 0.0], [3.0, 0.0]],
+			&[[6.0, 0.0], [7.0, 0.0]],
+			&[[9.0, 0.0], [12.0, 0.0]],
+		]);
+		assert_dashes(&dash(&line(6.0), 0.01, &[2.0], 1.0), &[
+			&[[0.0, 0.0], [1.0, 0.0]],
+			&[[3.0, 0.0], [5.0, 0.0]],
+		]);
+	}
+
+	#[test]
+	fn dashes_turn_corners_and_close() {
+		let mut builder = Path::builder();
+		builder.begin(point(0.0, 0.0));
+		builder.line_to(point(2.0, 0.0));
+		builder.line_to(point(2.0, 2.0));
+		builder.line_to(point(0.0, 2.0));
+		builder.end(true);
+		assert_dashes(&dash(&builder.build(), 0.01, &[3.0, 1.0], 0.0), &[
+			&[[0.0, 0.0], [2.0, 0.0], [2.0, 1.0]],
+			&[[2.0, 2.0], [0.0, 2.0], [0.0, 1.0]],
+		]);
+	}
+}
@@ -4,8 +4,10 @@ use std::sync::{Arc, RwLock};
 
 use crate::base::BaseRef;
 use crate::layer::Layer;
+use crate::tracker::ImageUsage;
 
 pub struct Clear {
+	pub color: [f32; 4],
 	base: BaseRef,
 	images: Vec<vk::Image>,
 }
@@ -17,6 +19,7 @@ impl Clear {
 
 	pub fn new(base: BaseRef) -> Self {
 		Self {
+			color: [0.0; 4],
 			base,
 			images: Vec::new(),
 		}
@@ -28,6 +31,10 @@ impl Layer for Clear {
 		self.images = images;
 	}
 
+	fn usage(&self) -> ImageUsage {
+		ImageUsage::TransferDst
+	}
+
 	fn render(&self, command_buffer: vk::CommandBuffer, idx: usize) { unsafe {
 		let base = self.base.read().unwrap();
 		let device = &base.device;
@@ -37,29 +44,12 @@ impl Layer for Clear {
 			layer_count: 1,
 			..Default::default()
 		};
-		let barrier = vk::ImageMemoryBarrier {
-			dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
-			old_layout: vk::ImageLayout::UNDEFINED,
-			new_layout: vk::ImageLayout::GENERAL,
-			image: self.images[idx],
-			subresource_range,
-			..Default::default()
-		};
-		device.cmd_pipeline_barrier(
-			command_buffer,
-			vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-			vk::PipelineStageFlags::TRANSFER,
-			vk::DependencyFlags::empty(),
-			&[],
-			&[],
-			&[barrier],
-		);
 		device.cmd_clear_color_image(
 			command_buffer,
 			self.images[idx],
-			vk::ImageLayout::GENERAL,
+			vk::ImageLayout::TRANSFER_DST_OPTIMAL,
 			&vk::ClearColorValue {
-				float32: [0.0, 0.0, 0.0, 0.0],
+				float32: self.color,
 			},
 			&[subresource_range],
 		);
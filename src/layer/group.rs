@@ -0,0 +1,323 @@
+use std::default::Default;
+use std::mem;
+use std::sync::{Arc, Mutex, RwLock};
+use ash::vk;
+
+use crate::layer::{Layer, LayerRef};
+use crate::layer::pipeline::{BlendMode, Pipeline, PipelineBuilder};
+use crate::animation::FrameTime;
+use crate::compositor::LayerStack;
+use crate::input::{InputContext, InputEvent, InputHandler, Rect};
+use crate::graph::TransientPool;
+use crate::base::{Base, BaseRef, find_memorytype_index};
+use crate::tracker::ImageUsage;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct PushConstants {
+	transform: [f32; 16],
+	opacity: f32,
+}
+
+// children are composited into `target`, which is then drawn onto the output
+// with the group transform and opacity
+pub struct LayerGroup {
+	base: BaseRef,
+	stack: Mutex<LayerStack>,
+	pool: Mutex<TransientPool>,
+	pub offset: [f32; 2],
+	pub scale: [f32; 2],
+	pub rotation: f32,
+	pub opacity: f32,
+
+	target_extent: vk::Extent2D,
+	target_image: vk::Image,
+	target_memory: vk::DeviceMemory,
+	target_image_view: vk::ImageView,
+	sampler: vk::Sampler,
+	pipeline: Pipeline,
+}
+
+// image the children are composited into, sized like the output
+unsafe fn create_target(base: &Base) -> (vk::Image, vk::DeviceMemory, vk::ImageView) {
+	let device = &base.device;
+	let target_create_info = vk::ImageCreateInfo::default()
+		.image_type(vk::ImageType::TYPE_2D)
+		.format(base.surface_format.format)
+		.extent(base.render_resolution.into())
+		.mip_levels(1)
+		.array_layers(1)
+		.samples(vk::SampleCountFlags::TYPE_1)
+		.tiling(vk::ImageTiling::OPTIMAL)
+		.usage(vk::ImageUsageFlags::COLOR_ATTACHMENT |
+			vk::ImageUsageFlags::SAMPLED |
+			vk::ImageUsageFlags::TRANSFER_DST |
+			vk::ImageUsageFlags::TRANSFER_SRC);
+	let target_image = device.create_image(&target_create_info, None).unwrap();
+	let target_memory_req = device.get_image_memory_requirements(target_image);
+	let target_memory_index = find_memorytype_index(
+		&target_memory_req,
+		&base.device_memory_properties,
+		vk::MemoryPropertyFlags::DEVICE_LOCAL,
+	).unwrap();
+	let target_allocate_info = vk::MemoryAllocateInfo {
+		allocation_size: target_memory_req.size,
+		memory_type_index: target_memory_index,
+		..Default::default()
+	};
+	let target_memory = device
+		.allocate_memory(&target_allocate_info, None)
+		.unwrap();
+	device
+		.bind_image_memory(target_image, target_memory, 0)
+		.unwrap();
+	let target_image_view_info = vk::ImageViewCreateInfo {
+		view_type: vk::ImageViewType::TYPE_2D,
+		format: base.surface_format.format,
+		components: vk::ComponentMapping {
+			r: vk::ComponentSwizzle::R,
+			g: vk::ComponentSwizzle::G,
+			b: vk::ComponentSwizzle::B,
+			a: vk::ComponentSwizzle::A,
+		},
+		subresource_range: vk::ImageSubresourceRange {
+			aspect_mask: vk::ImageAspectFlags::COLOR,
+			level_count: 1,
+			layer_count: 1,
+			..Default::default()
+		},
+		image: target_image,
+		..Default::default()
+	};
+	let target_image_view = device
+		.create_image_view(&target_image_view_info, None)
+		.unwrap();
+	(target_image, target_memory, target_image_view)
+}
+
+impl LayerGroup {
+	pub fn new_ref(base: BaseRef) -> Arc<RwLock<Self>> {
+		Arc::new(RwLock::new(Self::new(base)))
+	}
+
+	pub fn new(base: BaseRef) -> Self { unsafe {
+		let base_clone = base.clone();
+		let base = base.read().unwrap();
+		let device = &base.device;
+
+		let (target_image, target_memory, target_image_view) = create_target(&base);
+
+		let sampler_info = vk::SamplerCreateInfo {
+			mag_filter: vk::Filter::LINEAR,
+			min_filter: vk::Filter::LINEAR,
+			mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+			address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+			address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+			address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+			max_anisotropy: 1.0,
+			border_color: vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+			compare_op: vk::CompareOp::NEVER,
+			..Default::default()
+		};
+		let sampler = device.create_sampler(&sampler_info, None).unwrap();
+
+		// the quad is generated from gl_VertexIndex, the target holds
+		// premultiplied color
+		let pipeline = PipelineBuilder::new(
+			include_bytes!("../../assets/spvs/group_vert.spv"),
+			include_bytes!("../../assets/spvs/group_frag.spv"),
+		)
+			.blend_mode(BlendMode::Premultiplied)
+			.texture(0)
+			.push_constants(
+				vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+				mem::size_of::<PushConstants>() as u32,
+			)
+			.build(&base);
+		pipeline.write_image(0, target_image_view, sampler);
+
+		Self {
+			base: base_clone,
+			stack: Mutex::new(LayerStack::new(&base, vec![target_image])),
+			pool: Mutex::new(TransientPool::new(&base)),
+			offset: [0.0, 0.0],
+			scale: [1.0, 1.0],
+			rotation: 0.0,
+			opacity: 1.0,
+
+			target_extent: base.render_resolution,
+			target_image,
+			target_memory,
+			target_image_view,
+			sampler,
+			pipeline,
+		}
+	}}
+
+	pub fn new_layer(&mut self, layer: LayerRef) {
+		let base = self.base.read().unwrap();
+		self.stack.lock().unwrap().new_layer(&base, layer);
+	}
+
+	pub fn new_cached_layer(&mut self, layer: LayerRef) {
+		let base = self.base.read().unwrap();
+		self.stack.lock().unwrap().new_cached_layer(&base, layer);
+	}
+
+	pub fn update_all(&mut self) {
+		self.stack.lock().unwrap().update_all();
+	}
+
+	pub fn mark_update(&mut self, idx: usize) {
+		self.stack.lock().unwrap().mark_update(idx);
+	}
+
+	pub fn set_inputs(&mut self, idx: usize, inputs: Vec<usize>) {
+		self.stack.lock().unwrap().set_inputs(idx, inputs);
+	}
+
+	pub fn focus(&self) -> Option<usize> {
+		self.stack.lock().unwrap().focus()
+	}
+
+	pub fn set_focus(&mut self, focus: Option<usize>) {
+		self.stack.lock().unwrap().set_focus(focus);
+	}
+
+	fn push_constants(&self) -> PushConstants {
+		let (s, c) = self.rotation.sin_cos();
+		let [sx, sy] = self.scale;
+		let [ox, oy] = self.offset;
+		PushConstants {
+			transform: [
+				sx * c, sx * s, 0.0, 0.0,
+				-sy * s, sy * c, 0.0, 0.0,
+				0.0, 0.0, 1.0, 0.0,
+				ox, oy, 0.0, 1.0,
+			],
+			opacity: self.opacity,
+		}
+	}
+}
+
+impl Drop for LayerGroup {
+	fn drop(&mut self) { unsafe {
+		// children hold the target image, release them first
+		self.stack.get_mut().unwrap().clear();
+		let base = self.base.read().unwrap();
+		let device = &base.device;
+		device.device_wait_idle().unwrap();
+		base.image_tracker.lock().unwrap().forget(self.target_image);
+		device.destroy_image_view(self.target_image_view, None);
+		device.destroy_image(self.target_image, None);
+		device.free_memory(self.target_memory, None);
+		device.destroy_sampler(self.sampler, None);
+	}}
+}
+
+impl Layer for LayerGroup {
+	fn set_output(&mut self, images: Vec<vk::Image>) {
+		self.pipeline.set_output(images);
+	}
+
+	// the target is sized like the output
+	fn resize(&mut self, extent: vk::Extent2D) { unsafe {
+		self.pipeline.resize(extent);
+		if self.target_extent == extent {
+			return;
+		}
+		let base = self.base.read().unwrap();
+		base.image_tracker.lock().unwrap().forget(self.target_image);
+		base.device.destroy_image_view(self.target_image_view, None);
+		base.device.destroy_image(self.target_image, None);
+		base.device.free_memory(self.target_memory, None);
+		let (target_image, target_memory, target_image_view) = create_target(&base);
+		self.pipeline.write_image(0, target_image_view, self.sampler);
+		self.target_extent = extent;
+		self.target_image = target_image;
+		self.target_memory = target_memory;
+		self.target_image_view = target_image_view;
+		self.stack.get_mut().unwrap().resize(&base, vec![target_image]);
+		*self.pool.get_mut().unwrap() = TransientPool::new(&base);
+	}}
+
+	// rendered inline, the stack re-records its own secondaries
+	fn prepare(&mut self, _idx: usize) -> bool {
+		self.stack.get_mut().unwrap().prepare(0);
+		false
+	}
+
+	fn render(&self, draw_command_buffer: vk::CommandBuffer, idx: usize) { unsafe {
+		let base = self.base.read().unwrap();
+		let device = &base.device;
+
+		self.stack.lock().unwrap().record(
+			&base,
+			&mut self.pool.lock().unwrap(),
+			draw_command_buffer,
+			0,
+		);
+
+		base.image_tracker.lock().unwrap().require(
+			device,
+			draw_command_buffer,
+			self.target_image,
+			ImageUsage::Sampled,
+		);
+
+		// the stack is recorded first, so the render pass begins here
+		let target = self.pipeline.target(idx);
+		if let Some(target) = &target {
+			target.begin(device, draw_command_buffer, vk::SubpassContents::INLINE);
+		}
+		self.pipeline.begin(draw_command_buffer, idx);
+		let push_constants = self.push_constants();
+		device.cmd_push_constants(
+			draw_command_buffer,
+			self.pipeline.layout(),
+			vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+			0,
+			std::slice::from_raw_parts(
+				&push_constants as *const PushConstants as *const u8,
+				mem::size_of::<PushConstants>(),
+			),
+		);
+		device.cmd_draw(draw_command_buffer, 6, 1, 0, 0);
+		self.pipeline.end(draw_command_buffer, idx);
+		if target.is_some() {
+			device.cmd_end_render_pass(draw_command_buffer);
+		}
+	}}
+
+	fn input(&mut self) -> Option<&mut dyn InputHandler> {
+		Some(self)
+	}
+
+	fn tick(&mut self, time: &FrameTime) -> bool {
+		self.stack.get_mut().unwrap().tick(time)
+	}
+}
+
+// children are hit-tested in the group's own space
+impl InputHandler for LayerGroup {
+	fn bounds(&self) -> Option<Rect> {
+		Some(Rect {
+			min: [-1.0, -1.0],
+			max: [1.0, 1.0],
+		})
+	}
+
+	// inverse of the transform in `push_constants`
+	fn to_local(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+		let (s, c) = self.rotation.sin_cos();
+		let (dx, dy) = (x - self.offset[0], y - self.offset[1]);
+		[
+			(c * dx + s * dy) / self.scale[0],
+			(c * dy - s * dx) / self.scale[1],
+		]
+	}
+
+	fn handle(&mut self, event: &InputEvent, ctx: &mut InputContext) -> bool {
+		self.stack.get_mut().unwrap().dispatch(event, ctx)
+	}
+}
@@ -0,0 +1,301 @@
+use ash::vk;
+use std::f32::consts::TAU;
+use std::mem;
+use std::sync::{Arc, RwLock};
+
+use crate::animation::FrameTime;
+use crate::base::BaseRef;
+use crate::camera::{Camera2D, Coordinates};
+use crate::layer::{Layer, RenderTarget};
+use crate::layer::pipeline::{
+	BlendMode, HostBuffer, LoadOp, Pipeline, PipelineBuilder, Texture, VertexLayout,
+};
+use crate::offset_of;
+
+// of the base mesh, around the origin of its instances
+#[derive(Clone, Debug, Copy)]
+pub struct MarkerVertex {
+	pub pos: [f32; 2],
+	pub color: [f32; 4],
+	pub uv: [f32; 2],
+}
+
+impl VertexLayout for MarkerVertex {
+	fn attributes() -> Vec<(vk::Format, u32)> {
+		vec![
+			(vk::Format::R32G32_SFLOAT, offset_of!(MarkerVertex, pos) as u32),
+			(vk::Format::R32G32B32A32_SFLOAT, offset_of!(MarkerVertex, color) as u32),
+			(vk::Format::R32G32_SFLOAT, offset_of!(MarkerVertex, uv) as u32),
+		]
+	}
+}
+
+// the mesh is scaled, rotated counterclockwise in radians and moved to
+// `offset`, in world coordinates of `Instanced::camera` and
+// `Instanced::coordinates`
+#[derive(Clone, Debug, Copy)]
+pub struct Instance {
+	pub offset: [f32; 2],
+	pub scale: [f32; 2],
+	pub rotation: f32,
+	// multiplied with the mesh colors and the texture
+	pub color: [f32; 4],
+	// added to the mesh uvs, e.g. to pick a cell of an atlas
+	pub uv_offset: [f32; 2],
+}
+
+impl Default for Instance {
+	fn default() -> Self {
+		Self {
+			offset: [0.0; 2],
+			scale: [1.0; 2],
+			rotation: 0.0,
+			color: [1.0; 4],
+			uv_offset: [0.0; 2],
+		}
+	}
+}
+
+impl VertexLayout for Instance {
+	fn attributes() -> Vec<(vk::Format, u32)> {
+		vec![
+			(vk::Format::R32G32_SFLOAT, offset_of!(Instance, offset) as u32),
+			(vk::Format::R32G32_SFLOAT, offset_of!(Instance, scale) as u32),
+			(vk::Format::R32_SFLOAT, offset_of!(Instance, rotation) as u32),
+			(vk::Format::R32G32B32A32_SFLOAT, offset_of!(Instance, color) as u32),
+			(vk::Format::R32G32_SFLOAT, offset_of!(Instance, uv_offset) as u32),
+		]
+	}
+}
+
+// white triangle list of a unit circle
+pub fn circle(segments: u32) -> Vec<MarkerVertex> {
+	let segments = segments.max(3);
+	let at = |i: u32| {
+		let angle = i as f32 / segments as f32 * TAU;
+		let pos = [angle.cos(), angle.sin()];
+		MarkerVertex { pos, color: [1.0; 4], uv: [pos[0] / 2.0 + 0.5, pos[1] / 2.0 + 0.5] }
+	};
+	let center = MarkerVertex { pos: [0.0; 2], color: [1.0; 4], uv: [0.5; 2] };
+	(0..segments).flat_map(|i| [center, at(i), at(i + 1)]).collect()
+}
+
+// white triangle list of an arrow from the origin along +x, one unit long
+pub fn arrow() -> Vec<MarkerVertex> {
+	[
+		[0.0, -0.1], [0.6, -0.1], [0.6, 0.1],
+		[0.0, -0.1], [0.6, 0.1], [0.0, 0.1],
+		[0.6, -0.3], [1.0, 0.0], [0.6, 0.3],
+	]
+		.into_iter()
+		.map(|pos| MarkerVertex { pos, color: [1.0; 4], uv: [pos[0], pos[1] + 0.5] })
+		.collect()
+}
+
+// see src/shader/instanced.vert
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct PushConstants {
+	transform: [f32; 16],
+}
+
+// one base mesh drawn once per instance in a single draw. the mesh is only
+// uploaded again after `set_mesh`, `instances` every frame
+pub struct Instanced {
+	pub instances: Vec<Instance>,
+	pub camera: Camera2D,
+	pub coordinates: Coordinates,
+	mesh: Vec<MarkerVertex>,
+	mesh_changed: bool,
+
+	base: BaseRef,
+	pipeline: Pipeline,
+	mesh_buffer: HostBuffer<MarkerVertex>,
+	instance_buffer: HostBuffer<Instance>,
+	texture: Texture,
+	transform: [f32; 16],
+	// uploaded by the last `prepare`
+	vertex_count: u32,
+	instance_count: u32,
+}
+
+impl Instanced {
+	pub fn new_ref(base: BaseRef, mesh: Vec<MarkerVertex>) -> Arc<RwLock<Self>> {
+		Arc::new(RwLock::new(Self::new(base, mesh)))
+	}
+
+	// alpha blended over the layers below
+	pub fn new(base: BaseRef, mesh: Vec<MarkerVertex>) -> Self {
+		Self::with_modes(base, mesh, BlendMode::Alpha, LoadOp::Load)
+	}
+
+	pub fn with_modes(
+		base: BaseRef,
+		mesh: Vec<MarkerVertex>,
+		blend: BlendMode,
+		load: LoadOp,
+	) -> Self {
+		let (pipeline, mesh_buffer, instance_buffer, texture) = {
+			let base = base.read().unwrap();
+			let pipeline = PipelineBuilder::new(
+				include_bytes!("../../assets/spvs/instanced_vert.spv"),
+				include_bytes!("../../assets/spvs/instanced_frag.spv"),
+			)
+				.vertex::<MarkerVertex>()
+				.instance::<Instance>()
+				.texture(0)
+				.push_constants(
+					vk::ShaderStageFlags::VERTEX,
+					mem::size_of::<PushConstants>() as u32,
+				)
+				.blend_mode(blend)
+				.load(load)
+				.build(&base);
+			let mesh_buffer = HostBuffer::new(&base, vk::BufferUsageFlags::VERTEX_BUFFER, mesh.len());
+			let instance_buffer = HostBuffer::new(&base, vk::BufferUsageFlags::VERTEX_BUFFER, 0);
+			let texture = Texture::new(
+				&base,
+				vk::Format::R8G8B8A8_UNORM,
+				vk::Extent2D { width: 1, height: 1 },
+				&[255; 4],
+			);
+			pipeline.write_texture(0, &texture);
+			(pipeline, mesh_buffer, instance_buffer, texture)
+		};
+		Self {
+			instances: Vec::new(),
+			camera: Camera2D::default(),
+			coordinates: Coordinates::default(),
+			mesh,
+			mesh_changed: true,
+			base,
+			pipeline,
+			mesh_buffer,
+			instance_buffer,
+			texture,
+			transform: Camera2D::default().matrix([1.0, 1.0]),
+			vertex_count: 0,
+			instance_count: 0,
+		}
+	}
+
+	pub fn mesh(&self) -> &[MarkerVertex] {
+		&self.mesh
+	}
+
+	pub fn set_mesh(&mut self, mesh: Vec<MarkerVertex>) {
+		self.mesh = mesh;
+		self.mesh_changed = true;
+	}
+
+	// replaces the white default texture, sampled with the uvs
+	pub fn set_texture(&mut self, image: image::RgbaImage) {
+		let base = self.base.read().unwrap();
+		self.pipeline.replace_texture(&base, 0, &mut self.texture, image);
+	}
+
+	// of the output in pixels
+	pub fn extent(&self) -> [f32; 2] {
+		let extent = self.pipeline.extent();
+		[extent.width as f32, extent.height as f32]
+	}
+}
+
+impl Layer for Instanced {
+	fn set_output(&mut self, images: Vec<vk::Image>) {
+		self.pipeline.set_output(images);
+	}
+
+	fn resize(&mut self, extent: vk::Extent2D) {
+		self.pipeline.resize(extent);
+	}
+
+	fn target(&self, idx: usize) -> Option<RenderTarget> {
+		self.pipeline.target(idx)
+	}
+
+	fn tick(&mut self, _time: &FrameTime) -> bool {
+		self.pipeline.take_rewritten()
+	}
+
+	fn prepare(&mut self, _idx: usize) -> bool {
+		let transform = self.camera.matrix_in(self.coordinates, self.extent());
+		let buffers = [self.mesh_buffer.buffer(), self.instance_buffer.buffer()];
+		let vertex_count = self.vertex_count;
+		if self.mesh_changed {
+			self.vertex_count = self.mesh_buffer.upload(&self.mesh);
+			self.mesh_changed = false;
+		}
+		let instance_count = self.instance_buffer.upload(&self.instances);
+		let changed = transform != self.transform
+			|| vertex_count != self.vertex_count
+			|| instance_count != self.instance_count
+			|| buffers != [self.mesh_buffer.buffer(), self.instance_buffer.buffer()];
+		self.transform = transform;
+		self.instance_count = instance_count;
+		changed
+	}
+
+	fn render(&self, draw_command_buffer: vk::CommandBuffer, idx: usize) { unsafe {
+		let base = self.base.read().unwrap();
+		let device = &base.device;
+		self.pipeline.begin(draw_command_buffer, idx);
+		device.cmd_bind_vertex_buffers(
+			draw_command_buffer,
+			0,
+			&[self.mesh_buffer.buffer(), self.instance_buffer.buffer()],
+			&[0, 0],
+		);
+		let push_constants = PushConstants {
+			transform: self.transform,
+		};
+		device.cmd_push_constants(
+			draw_command_buffer,
+			self.pipeline.layout(),
+			vk::ShaderStageFlags::VERTEX,
+			0,
+			std::slice::from_raw_parts(
+				&push_constants as *const PushConstants as *const u8,
+				mem::size_of::<PushConstants>(),
+			),
+		);
+		if self.vertex_count > 0 && self.instance_count > 0 {
+			device.cmd_draw(draw_command_buffer, self.vertex_count, self.instance_count, 0, 0);
+		}
+		self.pipeline.end(draw_command_buffer, idx);
+	}}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{arrow, circle};
+
+	#[test]
+	fn circle_fans_around_the_center() {
+		let mesh = circle(16);
+		assert_eq!(mesh.len(), 16 * 3);
+		for triangle in mesh.chunks_exact(3) {
+			assert_eq!(triangle[0].pos, [0.0; 2]);
+			for vertex in &triangle[1..] {
+				let [x, y] = vertex.pos;
+				assert!((x * x + y * y - 1.0).abs() < 1e-5);
+			}
+			// counterclockwise
+			let [a, b] = [triangle[1].pos, triangle[2].pos];
+			assert!(a[0] * b[1] - a[1] * b[0] > 0.0);
+		}
+		// the uvs span the unit square
+		assert!(mesh.iter().all(|vertex| vertex.uv.iter().all(|&uv| (0.0..=1.0).contains(&uv))));
+		// fewer segments can't make a circle
+		assert_eq!(circle(1).len(), 3 * 3);
+	}
+
+	#[test]
+	fn arrow_is_one_unit_long() {
+		let mesh = arrow();
+		assert_eq!(mesh.len() % 3, 0);
+		let max = mesh.iter().map(|vertex| vertex.pos[0]).fold(f32::MIN, f32::max);
+		let min = mesh.iter().map(|vertex| vertex.pos[0]).fold(f32::MAX, f32::min);
+		assert_eq!([min, max], [0.0, 1.0]);
+	}
+}
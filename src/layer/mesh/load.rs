@@ -0,0 +1,279 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::camera::{multiply, normal_matrix, IDENTITY};
+use crate::layer::mesh::MeshVertex;
+
+// a triangle list
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+	pub vertices: Vec<MeshVertex>,
+	pub indices: Vec<u32>,
+}
+
+#[derive(Debug)]
+pub enum MeshError {
+	Obj(PathBuf, tobj::LoadError),
+	Gltf(PathBuf, gltf::Error),
+	// neither .obj, .gltf nor .glb
+	Unsupported(PathBuf),
+}
+
+impl fmt::Display for MeshError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			MeshError::Obj(path, e) => write!(f, "{}: {}", path.display(), e),
+			MeshError::Gltf(path, e) => write!(f, "{}: {}", path.display(), e),
+			MeshError::Unsupported(path) => write!(f, "{}: unsupported mesh format", path.display()),
+		}
+	}
+}
+
+impl std::error::Error for MeshError {}
+
+// by extension, all meshes of the file merged into one
+pub fn load(path: impl AsRef<Path>) -> Result<MeshData, MeshError> {
+	let path = path.as_ref();
+	let extension = path.extension()
+		.and_then(|extension| extension.to_str())
+		.map(|extension| extension.to_ascii_lowercase());
+	match extension.as_deref() {
+		Some("obj") => load_obj(path),
+		Some("gltf") | Some("glb") => load_gltf(path),
+		_ => Err(MeshError::Unsupported(path.to_path_buf())),
+	}
+}
+
+// materials are ignored, faces are triangulated
+pub fn load_obj(path: impl AsRef<Path>) -> Result<MeshData, MeshError> {
+	let path = path.as_ref();
+	let options = tobj::LoadOptions {
+		triangulate: true,
+		single_index: true,
+		..Default::default()
+	};
+	let (models, _materials) = tobj::load_obj(path, &options)
+		.map_err(|e| MeshError::Obj(path.to_path_buf(), e))?;
+	let mut data = MeshData::default();
+	for model in models {
+		let mesh = model.mesh;
+		let first = data.vertices.len() as u32;
+		let has_normals = !mesh.normals.is_empty();
+		for i in 0..mesh.positions.len() / 3 {
+			let normal = if has_normals {
+				[mesh.normals[i * 3], mesh.normals[i * 3 + 1], mesh.normals[i * 3 + 2]]
+			} else {
+				[0.0; 3]
+			};
+			// obj textures start at the bottom
+			let uv = match mesh.texcoords.get(i * 2..i * 2 + 2) {
+				Some(uv) => [uv[0], 1.0 - uv[1]],
+				None => [0.0; 2],
+			};
+			data.vertices.push(MeshVertex {
+				pos: [mesh.positions[i * 3], mesh.positions[i * 3 + 1], mesh.positions[i * 3 + 2]],
+				normal,
+				uv,
+			});
+		}
+		let indices: Vec<u32> = mesh.indices.iter().map(|&index| first + index).collect();
+		if !has_normals {
+			smooth_normals(&mut data.vertices, &indices);
+		}
+		data.indices.extend(indices);
+	}
+	Ok(data)
+}
+
+// the triangle primitives of the default scene with the node transforms
+// applied, binary and embedded buffers as well as external .bin files
+pub fn load_gltf(path: impl AsRef<Path>) -> Result<MeshData, MeshError> {
+	let path = path.as_ref();
+	let error = |e| MeshError::Gltf(path.to_path_buf(), e);
+	let gltf = gltf::Gltf::open(path).map_err(error)?;
+	let buffers = gltf::import_buffers(&gltf.document, path.parent(), gltf.blob.clone())
+		.map_err(error)?;
+	let mut data = MeshData::default();
+	let scene = match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+		Some(scene) => scene,
+		None => return Ok(data),
+	};
+	for node in scene.nodes() {
+		load_node(&node, &IDENTITY, &buffers, &mut data);
+	}
+	Ok(data)
+}
+
+fn load_node(
+	node: &gltf::Node,
+	parent: &[f32; 16],
+	buffers: &[gltf::buffer::Data],
+	data: &mut MeshData,
+) {
+	let local: Vec<f32> = node.transform().matrix().iter().flatten().copied().collect();
+	let transform = multiply(parent, &local.try_into().unwrap());
+	if let Some(mesh) = node.mesh() {
+		for primitive in mesh.primitives() {
+			if primitive.mode() != gltf::mesh::Mode::Triangles {
+				continue;
+			}
+			let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+			let positions = match reader.read_positions() {
+				Some(positions) => positions,
+				None => continue,
+			};
+			let first = data.vertices.len() as u32;
+			let mut vertices: Vec<MeshVertex> = positions
+				.map(|pos| MeshVertex {
+					pos: transform_point(&transform, pos, 1.0),
+					..Default::default()
+				})
+				.collect();
+			let has_normals = match reader.read_normals() {
+				Some(normals) => {
+					let normal_transform = normal_matrix(&transform);
+					for (vertex, normal) in vertices.iter_mut().zip(normals) {
+						vertex.normal = transform_point(&normal_transform, normal, 0.0);
+					}
+					true
+				}
+				None => false,
+			};
+			if let Some(uvs) = reader.read_tex_coords(0) {
+				for (vertex, uv) in vertices.iter_mut().zip(uvs.into_f32()) {
+					vertex.uv = uv;
+				}
+			}
+			let count = vertices.len() as u32;
+			data.vertices.extend(vertices);
+			let indices: Vec<u32> = match reader.read_indices() {
+				Some(indices) => indices.into_u32().map(|index| first + index).collect(),
+				None => (first..first + count).collect(),
+			};
+			if !has_normals {
+				smooth_normals(&mut data.vertices, &indices);
+			}
+			data.indices.extend(indices);
+		}
+	}
+	for child in node.children() {
+		load_node(&child, &transform, buffers, data);
+	}
+}
+
+// w 1.0 for points, 0.0 for directions
+fn transform_point(m: &[f32; 16], [x, y, z]: [f32; 3], w: f32) -> [f32; 3] {
+	[
+		m[0] * x + m[4] * y + m[8] * z + m[12] * w,
+		m[1] * x + m[5] * y + m[9] * z + m[13] * w,
+		m[2] * x + m[6] * y + m[10] * z + m[14] * w,
+	]
+}
+
+// area weighted face normals summed per vertex, for files without normals
+fn smooth_normals(vertices: &mut [MeshVertex], indices: &[u32]) {
+	for triangle in indices.chunks_exact(3) {
+		let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].pos);
+		let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+		let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+		let normal = [
+			u[1] * v[2] - u[2] * v[1],
+			u[2] * v[0] - u[0] * v[2],
+			u[0] * v[1] - u[1] * v[0],
+		];
+		for &index in triangle {
+			let vertex = &mut vertices[index as usize];
+			for i in 0..3 {
+				vertex.normal[i] += normal[i];
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{load, load_gltf, load_obj};
+
+	fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+		a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+	}
+
+	#[test]
+	fn obj_cube() {
+		let data = load_obj(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/models/cube.obj")).unwrap();
+		assert_eq!(data.vertices.len(), 8);
+		// six quads split in two
+		assert_eq!(data.indices.len(), 36);
+		assert!(data.indices.iter().all(|&index| index < 8));
+		// the file has no normals, the smoothed ones point away from the center
+		for vertex in data.vertices.iter() {
+			assert!(dot(vertex.normal, vertex.pos) > 0.0, "{:?}", vertex);
+		}
+	}
+
+	#[test]
+	fn unsupported_extension() {
+		assert!(load("cube.stl").is_err());
+	}
+
+	// a .glb of one triangle on the plane x + y + z = 1, in a node
+	// stretched along x
+	fn stretched_triangle() -> Vec<u8> {
+		let json = r#"{
+			"asset": {"version": "2.0"},
+			"scene": 0,
+			"scenes": [{"nodes": [0]}],
+			"nodes": [{"mesh": 0, "scale": [2.0, 1.0, 1.0]}],
+			"meshes": [{"primitives": [{"attributes": {"POSITION": 0, "NORMAL": 1}}]}],
+			"buffers": [{"byteLength": 72}],
+			"bufferViews": [{"buffer": 0, "byteLength": 72}],
+			"accessors": [
+				{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+					"min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 1.0]},
+				{"bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3"}
+			]
+		}"#;
+		let mut json = json.as_bytes().to_vec();
+		while json.len() % 4 != 0 {
+			json.push(b' ');
+		}
+		let floats: [f32; 18] = [
+			1.0, 0.0, 0.0,
+			0.0, 1.0, 0.0,
+			0.0, 0.0, 1.0,
+			1.0, 1.0, 1.0,
+			1.0, 1.0, 1.0,
+			1.0, 1.0, 1.0,
+		];
+		let bin: Vec<u8> = floats.iter().flat_map(|float| float.to_le_bytes()).collect();
+		let length = 12 + 8 + json.len() + 8 + bin.len();
+		let mut glb = Vec::new();
+		glb.extend(b"glTF");
+		glb.extend(2u32.to_le_bytes());
+		glb.extend((length as u32).to_le_bytes());
+		glb.extend((json.len() as u32).to_le_bytes());
+		glb.extend(b"JSON");
+		glb.extend(json);
+		glb.extend((bin.len() as u32).to_le_bytes());
+		glb.extend(b"BIN\0");
+		glb.extend(bin);
+		glb
+	}
+
+	#[test]
+	fn gltf_node_transform() {
+		let path = std::env::temp_dir().join("vkwh_stretched_triangle.glb");
+		std::fs::write(&path, stretched_triangle()).unwrap();
+		let data = load_gltf(&path).unwrap();
+		std::fs::remove_file(&path).unwrap();
+		let positions: Vec<_> = data.vertices.iter().map(|vertex| vertex.pos).collect();
+		assert_eq!(positions, [[2.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+		// no indices draws the vertices in order
+		assert_eq!(data.indices, [0, 1, 2]);
+		// the stretched plane is x / 2 + y + z = 1, the model matrix would
+		// give [2, 1, 1]
+		for vertex in data.vertices.iter() {
+			assert_eq!(vertex.normal, [0.5, 1.0, 1.0]);
+		}
+	}
+}
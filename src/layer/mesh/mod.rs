@@ -0,0 +1,236 @@
+pub mod load;
+
+use ash::vk;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use crate::animation::FrameTime;
+use crate::base::BaseRef;
+use crate::camera::{normal_matrix, Camera3D, IDENTITY};
+use crate::layer::{Layer, RenderTarget};
+use crate::layer::pipeline::{HostBuffer, Pipeline, PipelineBuilder, Texture, VertexLayout};
+use crate::offset_of;
+pub use load::{MeshData, MeshError};
+
+#[derive(Clone, Debug, Copy, Default)]
+pub struct MeshVertex {
+	pub pos: [f32; 3],
+	pub normal: [f32; 3],
+	pub uv: [f32; 2],
+}
+
+impl VertexLayout for MeshVertex {
+	fn attributes() -> Vec<(vk::Format, u32)> {
+		vec![
+			(vk::Format::R32G32B32_SFLOAT, offset_of!(MeshVertex, pos) as u32),
+			(vk::Format::R32G32B32_SFLOAT, offset_of!(MeshVertex, normal) as u32),
+			(vk::Format::R32G32_SFLOAT, offset_of!(MeshVertex, uv) as u32),
+		]
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionalLight {
+	// the light travels along it, in world space
+	pub direction: [f32; 3],
+	pub color: [f32; 3],
+	// added to every surface regardless of its normal
+	pub ambient: [f32; 3],
+}
+
+impl Default for DirectionalLight {
+	fn default() -> Self {
+		Self {
+			direction: [-0.4, -1.0, -0.6],
+			color: [0.8, 0.8, 0.8],
+			ambient: [0.2, 0.2, 0.2],
+		}
+	}
+}
+
+// see src/shader/mesh.vert, std140
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Uniforms {
+	model: [f32; 16],
+	view: [f32; 16],
+	projection: [f32; 16],
+	normal: [f32; 16],
+	light_direction: [f32; 4],
+	light_color: [f32; 4],
+	ambient: [f32; 4],
+	color: [f32; 4],
+}
+
+// lit, depth tested triangle lists. unlike `Triangles` the vertices are in
+// world space and seen through a perspective camera
+pub struct Mesh {
+	pub vertices: Vec<MeshVertex>,
+	// triangle list into `vertices`, empty draws the vertices in order
+	pub indices: Vec<u32>,
+	pub model: [f32; 16],
+	pub camera: Camera3D,
+	pub light: DirectionalLight,
+	// multiplied with the texture
+	pub color: [f32; 4],
+
+	base: BaseRef,
+	pipeline: Pipeline,
+	vertex_buffer: HostBuffer<MeshVertex>,
+	index_buffer: HostBuffer<u32>,
+	uniform_buffer: HostBuffer<Uniforms>,
+	texture: Texture,
+	vertex_count: u32,
+	index_count: u32,
+}
+
+impl Mesh {
+	pub fn new_ref(base: BaseRef) -> Arc<RwLock<Self>> {
+		Arc::new(RwLock::new(Self::new(base)))
+	}
+
+	pub fn new(base: BaseRef) -> Self {
+		Self::with_options(base, vk::AttachmentLoadOp::CLEAR, vk::SampleCountFlags::TYPE_1)
+	}
+
+	// LOAD draws over the output instead of replacing it, the depth buffer
+	// is cleared either way. see `PipelineBuilder::samples`
+	pub fn with_options(
+		base: BaseRef,
+		load_op: vk::AttachmentLoadOp,
+		samples: vk::SampleCountFlags,
+	) -> Self {
+		let (pipeline, vertex_buffer, index_buffer, uniform_buffer, texture) = {
+			let base = base.read().unwrap();
+			let pipeline = PipelineBuilder::new(
+				include_bytes!("../../../assets/spvs/mesh_vert.spv"),
+				include_bytes!("../../../assets/spvs/mesh_frag.spv"),
+			)
+				.vertex::<MeshVertex>()
+				.uniform(0, vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+				.texture(1)
+				.depth(true)
+				.load_op(load_op)
+				.samples(samples)
+				.build(&base);
+			let vertex_buffer = HostBuffer::new(&base, vk::BufferUsageFlags::VERTEX_BUFFER, 0);
+			let index_buffer = HostBuffer::new(&base, vk::BufferUsageFlags::INDEX_BUFFER, 0);
+			// never grows, so the descriptor stays valid
+			let uniform_buffer = HostBuffer::new(&base, vk::BufferUsageFlags::UNIFORM_BUFFER, 1);
+			pipeline.write_buffer(0, uniform_buffer.buffer());
+			let texture = Texture::new(
+				&base,
+				vk::Format::R8G8B8A8_UNORM,
+				vk::Extent2D { width: 1, height: 1 },
+				&[255; 4],
+			);
+			pipeline.write_texture(1, &texture);
+			(pipeline, vertex_buffer, index_buffer, uniform_buffer, texture)
+		};
+		Self {
+			vertices: Vec::new(),
+			indices: Vec::new(),
+			model: IDENTITY,
+			camera: Camera3D::default(),
+			light: DirectionalLight::default(),
+			color: [1.0; 4],
+			base,
+			pipeline,
+			vertex_buffer,
+			index_buffer,
+			uniform_buffer,
+			texture,
+			vertex_count: 0,
+			index_count: 0,
+		}
+	}
+
+	// an .obj or .gltf/.glb file, see `load::load`
+	pub fn from_file(base: BaseRef, path: impl AsRef<Path>) -> Result<Self, MeshError> {
+		let data = load::load(path)?;
+		let mut mesh = Self::new(base);
+		mesh.set_data(data);
+		Ok(mesh)
+	}
+
+	pub fn set_data(&mut self, data: MeshData) {
+		self.vertices = data.vertices;
+		self.indices = data.indices;
+	}
+
+	// replaces the white default texture, sampled with the uvs
+	pub fn set_texture(&mut self, image: image::RgbaImage) {
+		let base = self.base.read().unwrap();
+		self.pipeline.replace_texture(&base, 1, &mut self.texture, image);
+	}
+
+	// of the output in pixels
+	pub fn extent(&self) -> [f32; 2] {
+		let extent = self.pipeline.extent();
+		[extent.width as f32, extent.height as f32]
+	}
+}
+
+impl Layer for Mesh {
+	fn set_output(&mut self, images: Vec<vk::Image>) {
+		self.pipeline.set_output(images);
+	}
+
+	fn resize(&mut self, extent: vk::Extent2D) {
+		self.pipeline.resize(extent);
+	}
+
+	fn target(&self, idx: usize) -> Option<RenderTarget> {
+		self.pipeline.target(idx)
+	}
+
+	fn tick(&mut self, _time: &FrameTime) -> bool {
+		self.pipeline.take_rewritten()
+	}
+
+	fn prepare(&mut self, _idx: usize) -> bool {
+		let counts = (self.vertex_count, self.index_count);
+		let buffers = [self.vertex_buffer.buffer(), self.index_buffer.buffer()];
+		let [dx, dy, dz] = self.light.direction;
+		let [r, g, b] = self.light.color;
+		let [ar, ag, ab] = self.light.ambient;
+		self.uniform_buffer.upload(&[Uniforms {
+			model: self.model,
+			view: self.camera.view(),
+			projection: self.camera.projection(self.extent()),
+			normal: normal_matrix(&self.model),
+			light_direction: [dx, dy, dz, 0.0],
+			light_color: [r, g, b, 1.0],
+			ambient: [ar, ag, ab, 1.0],
+			color: self.color,
+		}]);
+		self.vertex_count = self.vertex_buffer.upload(&self.vertices);
+		self.index_count = self.index_buffer.upload(&self.indices);
+		counts != (self.vertex_count, self.index_count)
+			|| buffers != [self.vertex_buffer.buffer(), self.index_buffer.buffer()]
+	}
+
+	fn render(&self, draw_command_buffer: vk::CommandBuffer, idx: usize) { unsafe {
+		let base = self.base.read().unwrap();
+		let device = &base.device;
+		self.pipeline.begin(draw_command_buffer, idx);
+		device.cmd_bind_vertex_buffers(
+			draw_command_buffer,
+			0,
+			&[self.vertex_buffer.buffer()],
+			&[0],
+		);
+		if self.index_count > 0 {
+			device.cmd_bind_index_buffer(
+				draw_command_buffer,
+				self.index_buffer.buffer(),
+				0,
+				vk::IndexType::UINT32,
+			);
+			device.cmd_draw_indexed(draw_command_buffer, self.index_count, 1, 0, 0, 0);
+		} else if self.vertex_count > 0 {
+			device.cmd_draw(draw_command_buffer, self.vertex_count, 1, 0, 0);
+		}
+		self.pipeline.end(draw_command_buffer, idx);
+	}}
+}
@@ -2,12 +2,105 @@ pub mod triangles;
 pub mod image_viewer;
 pub mod clear;
 pub mod monotext;
+pub mod group;
+pub mod perf_hud;
+pub mod pipeline;
+pub mod msaa;
+pub mod shader;
+pub mod mesh;
+pub mod canvas;
+pub mod shapes;
+pub mod instanced;
 
 use ash::vk;
 use std::sync::{Arc, RwLock};
 
+use crate::animation::FrameTime;
+use crate::input::InputHandler;
+use crate::tracker::ImageUsage;
+
+pub struct RenderTarget {
+	pub render_pass: vk::RenderPass,
+	pub framebuffer: vk::Framebuffer,
+	pub render_area: vk::Rect2D,
+	pub clear_values: Vec<vk::ClearValue>,
+}
+
+impl RenderTarget {
+	pub unsafe fn begin(
+		&self,
+		device: &ash::Device,
+		command_buffer: vk::CommandBuffer,
+		contents: vk::SubpassContents,
+	) {
+		let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+			.render_pass(self.render_pass)
+			.framebuffer(self.framebuffer)
+			.render_area(self.render_area)
+			.clear_values(&self.clear_values);
+		device.cmd_begin_render_pass(command_buffer, &render_pass_begin_info, contents);
+	}
+}
+
 pub type LayerRef = Arc<RwLock<dyn Layer>>;
-pub trait Layer {
+// layers are recorded from worker threads
+pub trait Layer: Send + Sync {
 	fn set_output(&mut self, image: Vec<vk::Image>);
+	// the output extent changed, called before `set_output` with the images
+	// of the new extent. the device is idle
+	fn resize(&mut self, _extent: vk::Extent2D) {}
+	// cpu work and uploads for output `idx`, called every frame the layer is
+	// composited. returns true if the commands `render` records changed
+	// (draw counts, push constants, buffer handles), so a recorded secondary
+	// is recorded again. cached layers are only prepared when damaged, their
+	// mutators go through `LayerStack::mark_update` or `tick`
+	fn prepare(&mut self, _idx: usize) -> bool {
+		false
+	}
+	// only records commands, possibly from a worker thread
 	fn render(&self, command_buffer: vk::CommandBuffer, idx: usize);
+	// if some, `render` only records the subpass contents and the caller
+	// begins the render pass, which allows recording into a secondary
+	// command buffer
+	fn target(&self, _idx: usize) -> Option<RenderTarget> {
+		None
+	}
+	// cache images of other layers this layer samples, see
+	// `LayerCompositor::set_inputs`
+	fn set_input(&mut self, _images: Vec<vk::Image>) {}
+	// how `render` accesses the output image, the caller transitions it
+	fn usage(&self) -> ImageUsage {
+		ImageUsage::ColorAttachment
+	}
+	// layers receiving input return themselves
+	fn input(&mut self) -> Option<&mut dyn InputHandler> {
+		None
+	}
+	// shown in the performance overlay
+	fn name(&self) -> &str {
+		let name = std::any::type_name::<Self>();
+		name.rsplit("::").next().unwrap_or(name)
+	}
+	// called once per frame before rendering, true if the layer changed and
+	// must be rendered again, which also keeps the compositor redrawing
+	fn tick(&mut self, _time: &FrameTime) -> bool {
+		false
+	}
+}
+
+// record a layer directly into a primary command buffer
+pub fn record_inline(
+	device: &ash::Device,
+	command_buffer: vk::CommandBuffer,
+	layer: &dyn Layer,
+	idx: usize,
+) {
+	match layer.target(idx) {
+		Some(target) => unsafe {
+			target.begin(device, command_buffer, vk::SubpassContents::INLINE);
+			layer.render(command_buffer, idx);
+			device.cmd_end_render_pass(command_buffer);
+		},
+		None => layer.render(command_buffer, idx),
+	}
 }
@@ -46,6 +46,18 @@ impl LabelStack {
 	}
 
 	pub fn to_vertices(&self, viewport: &vk::Viewport) -> Vec<Vertex> {
+		self.to_vertices_at(viewport, [-1.0, -1.0], 1.0)
+	}
+
+	// the first line starts at `origin` in normalized device coordinates,
+	// glyphs are `pixel_scale` times larger
+	pub fn to_vertices_at(
+		&self,
+		viewport: &vk::Viewport,
+		origin: [f32; 2],
+		pixel_scale: f32,
+	) -> Vec<Vertex> {
+		let scaler = self.scaler * pixel_scale;
 		let size_x = 1024 / self.font_size[0];
 		// let size_y = 1024 / self.font_size[1];
 		let mut result = vec![];
@@ -73,10 +85,10 @@ impl LabelStack {
 						((uy + upos[1]) * self.font_size[1]) as f32 / 1024f32,
 					];
 					let pos = [
-						-1.0 + ((idx + upos[0]) * self.font_size[0]) as f32
-							/ viewport.width * self.scaler,
-						-1.0 + ((idy + upos[1]) * self.font_size[1]) as f32
-							/ viewport.height * self.scaler,
+						origin[0] + ((idx + upos[0]) * self.font_size[0]) as f32
+							/ viewport.width * scaler,
+						origin[1] + ((idy + upos[1]) * self.font_size[1]) as f32
+							/ viewport.height * scaler,
 					];
 					result.push(Vertex {
 						color,
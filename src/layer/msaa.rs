@@ -0,0 +1,482 @@
+use ash::vk;
+use std::default::Default;
+
+use crate::base::Base;
+use crate::layer::pipeline::{
+	Attachment, BlendMode, Pipeline, PipelineBuilder, depth_attachment, external_dependency,
+};
+
+// highest sample count up to `requested` the output format supports, and
+// the depth attachment if `depth`
+pub fn supported_samples(
+	base: &Base,
+	requested: vk::SampleCountFlags,
+	depth: bool,
+) -> vk::SampleCountFlags { unsafe {
+	let limits = base.instance
+		.get_physical_device_properties(base.pdevice)
+		.limits;
+	let mut supported = limits.framebuffer_color_sample_counts;
+	if depth {
+		supported &= limits.framebuffer_depth_sample_counts;
+	}
+	[
+		vk::SampleCountFlags::TYPE_64,
+		vk::SampleCountFlags::TYPE_32,
+		vk::SampleCountFlags::TYPE_16,
+		vk::SampleCountFlags::TYPE_8,
+		vk::SampleCountFlags::TYPE_4,
+		vk::SampleCountFlags::TYPE_2,
+	]
+		.into_iter()
+		.find(|&count| count.as_raw() <= requested.as_raw() && supported.contains(count))
+		.unwrap_or(vk::SampleCountFlags::TYPE_1)
+}}
+
+// attachments: multisampled color, its resolve image, the output and the
+// optional multisampled depth. the first subpass draws into the multisampled
+// image, the second blends the resolved image over the output. a CLEAR
+// `load_op` needs no blending, the only subpass resolves into the output
+// then, the attachments are multisampled color, output and depth
+pub fn render_pass(
+	device: &ash::Device,
+	format: vk::Format,
+	samples: vk::SampleCountFlags,
+	load_op: vk::AttachmentLoadOp,
+	depth_format: Option<vk::Format>,
+) -> vk::RenderPass { unsafe {
+	let multisampled = vk::AttachmentDescription {
+		format,
+		samples,
+		load_op: vk::AttachmentLoadOp::CLEAR,
+		store_op: vk::AttachmentStoreOp::DONT_CARE,
+		initial_layout: vk::ImageLayout::UNDEFINED,
+		final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+		..Default::default()
+	};
+	let output = vk::AttachmentDescription {
+		format,
+		samples: vk::SampleCountFlags::TYPE_1,
+		load_op,
+		store_op: vk::AttachmentStoreOp::STORE,
+		initial_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+		final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+		..Default::default()
+	};
+	if load_op == vk::AttachmentLoadOp::CLEAR {
+		return resolve_render_pass(device, multisampled, output, samples, depth_format);
+	}
+	let mut renderpass_attachments = vec![
+		multisampled,
+		vk::AttachmentDescription {
+			format,
+			samples: vk::SampleCountFlags::TYPE_1,
+			load_op: vk::AttachmentLoadOp::DONT_CARE,
+			store_op: vk::AttachmentStoreOp::DONT_CARE,
+			initial_layout: vk::ImageLayout::UNDEFINED,
+			final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+			..Default::default()
+		},
+		output,
+	];
+	if let Some(depth_format) = depth_format {
+		renderpass_attachments.push(depth_attachment(depth_format, samples));
+	}
+	let draw_depth_ref = vk::AttachmentReference {
+		attachment: 3,
+		layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+	};
+	let draw_color_refs = [vk::AttachmentReference {
+		attachment: 0,
+		layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+	}];
+	let draw_resolve_refs = [vk::AttachmentReference {
+		attachment: 1,
+		layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+	}];
+	let blend_input_refs = [vk::AttachmentReference {
+		attachment: 1,
+		layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+	}];
+	let blend_color_refs = [vk::AttachmentReference {
+		attachment: 2,
+		layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+	}];
+	let mut draw_subpass = vk::SubpassDescription::default()
+		.color_attachments(&draw_color_refs)
+		.resolve_attachments(&draw_resolve_refs)
+		.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+	if depth_format.is_some() {
+		draw_subpass = draw_subpass.depth_stencil_attachment(&draw_depth_ref);
+	}
+	let subpasses = [
+		draw_subpass,
+		vk::SubpassDescription::default()
+			.input_attachments(&blend_input_refs)
+			.color_attachments(&blend_color_refs)
+			.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS),
+	];
+	let dependencies = [
+		external_dependency(),
+		vk::SubpassDependency {
+			src_subpass: 0,
+			dst_subpass: 1,
+			src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+			src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+			dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+			dst_access_mask: vk::AccessFlags::INPUT_ATTACHMENT_READ,
+			dependency_flags: vk::DependencyFlags::BY_REGION,
+		},
+	];
+	let renderpass_create_info = vk::RenderPassCreateInfo::default()
+		.attachments(&renderpass_attachments)
+		.subpasses(&subpasses)
+		.dependencies(&dependencies);
+	device
+		.create_render_pass(&renderpass_create_info, None)
+		.unwrap()
+}}
+
+// a single subpass drawing into the multisampled image and resolving it
+// into the output, which it overwrites
+unsafe fn resolve_render_pass(
+	device: &ash::Device,
+	multisampled: vk::AttachmentDescription,
+	output: vk::AttachmentDescription,
+	samples: vk::SampleCountFlags,
+	depth_format: Option<vk::Format>,
+) -> vk::RenderPass {
+	let mut renderpass_attachments = vec![
+		multisampled,
+		vk::AttachmentDescription {
+			load_op: vk::AttachmentLoadOp::DONT_CARE,
+			..output
+		},
+	];
+	if let Some(depth_format) = depth_format {
+		renderpass_attachments.push(depth_attachment(depth_format, samples));
+	}
+	let color_refs = [vk::AttachmentReference {
+		attachment: 0,
+		layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+	}];
+	let resolve_refs = [vk::AttachmentReference {
+		attachment: 1,
+		layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+	}];
+	let depth_ref = vk::AttachmentReference {
+		attachment: 2,
+		layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+	};
+	let mut subpass = vk::SubpassDescription::default()
+		.color_attachments(&color_refs)
+		.resolve_attachments(&resolve_refs)
+		.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+	if depth_format.is_some() {
+		subpass = subpass.depth_stencil_attachment(&depth_ref);
+	}
+	let dependencies = [external_dependency()];
+	let renderpass_create_info = vk::RenderPassCreateInfo::default()
+		.attachments(&renderpass_attachments)
+		.subpasses(std::slice::from_ref(&subpass))
+		.dependencies(&dependencies);
+	device
+		.create_render_pass(&renderpass_create_info, None)
+		.unwrap()
+}
+
+// the color the multisampled image of a layer drawn with `mode` starts
+// with and how it is then blended over the output. alpha and premultiplied
+// draws leave a premultiplied image, additive ones a sum and multiplied ones
+// a product, so drawing a single sample onto the output gives the same
+fn composite(mode: BlendMode) -> ([f32; 4], vk::PipelineColorBlendAttachmentState) {
+	match mode {
+		BlendMode::Off | BlendMode::Alpha | BlendMode::Premultiplied => {
+			([0.0; 4], BlendMode::Premultiplied.state())
+		}
+		BlendMode::Additive => ([0.0; 4], vk::PipelineColorBlendAttachmentState {
+			blend_enable: 1,
+			src_color_blend_factor: vk::BlendFactor::ONE,
+			dst_color_blend_factor: vk::BlendFactor::ONE,
+			color_blend_op: vk::BlendOp::ADD,
+			src_alpha_blend_factor: vk::BlendFactor::ZERO,
+			dst_alpha_blend_factor: vk::BlendFactor::ONE,
+			alpha_blend_op: vk::BlendOp::ADD,
+			color_write_mask: vk::ColorComponentFlags::RGBA,
+		}),
+		BlendMode::Multiply => ([1.0; 4], BlendMode::Multiply.state()),
+	}
+}
+
+struct Target {
+	multisampled: Attachment,
+	// the resolve image and the set reading it, when blended over the output
+	resolve: Option<(Attachment, vk::DescriptorSet)>,
+}
+
+// the pipeline blending the resolved image over the output, in subpass 1 of
+// `renderpass`
+fn composite_pipeline(
+	base: &Base,
+	renderpass: vk::RenderPass,
+	blend: vk::PipelineColorBlendAttachmentState,
+) -> Pipeline {
+	PipelineBuilder::new(
+		include_bytes!("../../assets/spvs/fullscreen_vert.spv"),
+		include_bytes!("../../assets/spvs/msaa_blend_frag.spv"),
+	)
+		.blend(blend)
+		.input_attachment(0)
+		.subpass(renderpass, 1)
+		.build(base)
+}
+
+// the multisampled images of every output image, see `render_pass`. layers
+// clearing the output resolve into it and look like drawn without
+// multisampling, only smoother. the others draw into an image of their own
+// that is blended over the output by their blend mode, see `composite`.
+// geometry drawn without blending is blended over premultiplied, so it no
+// longer replaces the output where its alpha is below 1. as a secondary
+// command buffer can't span both subpasses, such layers begin their render
+// pass in `Pipeline::begin` and are recorded inline instead of in parallel
+pub struct Multisample {
+	device: ash::Device,
+	memory_properties: vk::PhysicalDeviceMemoryProperties,
+	format: vk::Format,
+	samples: vk::SampleCountFlags,
+	// of the multisampled image
+	clear_color: [f32; 4],
+	// boxed, a pipeline holds its multisample
+	composite: Option<Box<Pipeline>>,
+	// the descriptor sets of the resolved images
+	descriptor_pool: vk::DescriptorPool,
+	targets: Vec<Target>,
+}
+
+impl Multisample {
+	// `load_op` as given to `render_pass`, `mode` the one the layer draws with
+	pub fn new(
+		base: &Base,
+		renderpass: vk::RenderPass,
+		samples: vk::SampleCountFlags,
+		load_op: vk::AttachmentLoadOp,
+		mode: BlendMode,
+	) -> Self {
+		let (clear_color, blend) = composite(mode);
+		let composite = (load_op != vk::AttachmentLoadOp::CLEAR)
+			.then(|| Box::new(composite_pipeline(base, renderpass, blend)));
+		Self {
+			device: base.device.clone(),
+			memory_properties: base.device_memory_properties,
+			format: base.surface_format.format,
+			samples,
+			clear_color,
+			composite,
+			descriptor_pool: vk::DescriptorPool::null(),
+			targets: Vec::new(),
+		}
+	}
+
+	pub fn samples(&self) -> vk::SampleCountFlags {
+		self.samples
+	}
+
+	// if `composites`
+	pub fn clear_color(&self) -> [f32; 4] {
+		self.clear_color
+	}
+
+	// false: the render pass has a single subpass resolving into the output
+	pub fn composites(&self) -> bool {
+		self.composite.is_some()
+	}
+
+	unsafe fn destroy_targets(&mut self) {
+		let device = &self.device;
+		for target in self.targets.drain(..) {
+			target.multisampled.destroy(device);
+			if let Some((resolve, _)) = target.resolve {
+				resolve.destroy(device);
+			}
+		}
+		if self.descriptor_pool != vk::DescriptorPool::null() {
+			device.destroy_descriptor_pool(self.descriptor_pool, None);
+			self.descriptor_pool = vk::DescriptorPool::null();
+		}
+	}
+
+	// from the pool of the current outputs
+	unsafe fn descriptor_set(&self, view: vk::ImageView) -> vk::DescriptorSet {
+		let composite = self.composite.as_ref().unwrap();
+		let set_layouts = [composite.descriptor_set_layout()];
+		let desc_alloc_info = vk::DescriptorSetAllocateInfo::default()
+			.descriptor_pool(self.descriptor_pool)
+			.set_layouts(&set_layouts);
+		let descriptor_set = self.device
+			.allocate_descriptor_sets(&desc_alloc_info)
+			.unwrap()[0];
+		let input_descriptor = vk::DescriptorImageInfo {
+			image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+			image_view: view,
+			sampler: vk::Sampler::null(),
+		};
+		let write_desc_sets = [
+			vk::WriteDescriptorSet {
+				dst_set: descriptor_set,
+				descriptor_count: 1,
+				descriptor_type: vk::DescriptorType::INPUT_ATTACHMENT,
+				p_image_info: &input_descriptor,
+				..Default::default()
+			},
+		];
+		self.device.update_descriptor_sets(&write_desc_sets, &[]);
+		descriptor_set
+	}
+
+	// recreates the images for `count` outputs of `extent`, returns the
+	// views of each that come before the output in the framebuffer, the
+	// device is idle
+	pub fn set_output(&mut self, extent: vk::Extent2D, count: usize) -> Vec<Vec<vk::ImageView>> { unsafe {
+		self.destroy_targets();
+		if let Some(composite) = self.composite.as_mut() {
+			composite.resize(extent);
+			let descriptor_sizes = [
+				vk::DescriptorPoolSize {
+					ty: vk::DescriptorType::INPUT_ATTACHMENT,
+					descriptor_count: count.max(1) as u32,
+				},
+			];
+			let descriptor_pool_info = vk::DescriptorPoolCreateInfo::default()
+				.pool_sizes(&descriptor_sizes)
+				.max_sets(count.max(1) as u32);
+			self.descriptor_pool = self.device
+				.create_descriptor_pool(&descriptor_pool_info, None)
+				.unwrap();
+		}
+		for _ in 0..count {
+			let multisampled = Attachment::new(
+				&self.device,
+				&self.memory_properties,
+				self.format,
+				extent,
+				self.samples,
+				vk::ImageUsageFlags::COLOR_ATTACHMENT,
+			);
+			let resolve = self.composite.is_some().then(|| {
+				let resolve = Attachment::new(
+					&self.device,
+					&self.memory_properties,
+					self.format,
+					extent,
+					vk::SampleCountFlags::TYPE_1,
+					vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::INPUT_ATTACHMENT,
+				);
+				let descriptor_set = self.descriptor_set(resolve.view);
+				(resolve, descriptor_set)
+			});
+			self.targets.push(Target {
+				multisampled,
+				resolve,
+			});
+		}
+		self.targets
+			.iter()
+			.map(|target| match &target.resolve {
+				Some((resolve, _)) => vec![target.multisampled.view, resolve.view],
+				None => vec![target.multisampled.view],
+			})
+			.collect()
+	}}
+
+	// ends the drawing subpass and blends the result over output `idx`, only
+	// if `composites`
+	pub fn finish(&self, command_buffer: vk::CommandBuffer, idx: usize) { unsafe {
+		let composite = self.composite.as_ref().expect("resolved in the drawing subpass");
+		let (_, descriptor_set) = self.targets[idx].resolve.as_ref().unwrap();
+		self.device.cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+		composite.bind_with(command_buffer, *descriptor_set);
+		self.device.cmd_draw(command_buffer, 3, 1, 0, 0);
+		self.device.cmd_end_render_pass(command_buffer);
+	}}
+}
+
+impl Drop for Multisample {
+	fn drop(&mut self) { unsafe {
+		self.device.device_wait_idle().unwrap();
+		self.destroy_targets();
+	}}
+}
+
+#[cfg(test)]
+mod tests {
+	use ash::vk;
+
+	use super::composite;
+	use crate::layer::pipeline::BlendMode;
+
+	// what the blend unit writes for the factors the blend modes use
+	fn blend(state: vk::PipelineColorBlendAttachmentState, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+		if state.blend_enable == 0 {
+			return src;
+		}
+		let factor = |factor: vk::BlendFactor, i: usize| match factor {
+			vk::BlendFactor::ZERO => 0.0,
+			vk::BlendFactor::ONE => 1.0,
+			vk::BlendFactor::SRC_ALPHA => src[3],
+			vk::BlendFactor::ONE_MINUS_SRC_ALPHA => 1.0 - src[3],
+			vk::BlendFactor::DST_COLOR => dst[i],
+			factor => panic!("{:?}", factor),
+		};
+		[0, 1, 2, 3].map(|i| {
+			let (src_factor, dst_factor) = if i < 3 {
+				(state.src_color_blend_factor, state.dst_color_blend_factor)
+			} else {
+				(state.src_alpha_blend_factor, state.dst_alpha_blend_factor)
+			};
+			src[i] * factor(src_factor, i) + dst[i] * factor(dst_factor, i)
+		})
+	}
+
+	fn mix(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+		[0, 1, 2, 3].map(|i| (a[i] + b[i]) / 2.0)
+	}
+
+	fn assert_near(a: [f32; 4], b: [f32; 4]) {
+		assert!(a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-5), "{:?} != {:?}", a, b);
+	}
+
+	const DST: [f32; 4] = [0.2, 0.6, 0.8, 1.0];
+
+	// premultiplied, so valid for every mode
+	const SRC: [f32; 4] = [0.4, 0.2, 0.1, 0.5];
+
+	fn cases() -> Vec<(BlendMode, [f32; 4])> {
+		vec![
+			(BlendMode::Alpha, SRC),
+			(BlendMode::Premultiplied, SRC),
+			(BlendMode::Additive, SRC),
+			(BlendMode::Multiply, SRC),
+			// only opaque geometry replaces the output when composited
+			(BlendMode::Off, [0.4, 0.2, 0.1, 1.0]),
+		]
+	}
+
+	#[test]
+	fn covered_pixels_match_drawing_directly() {
+		for (mode, src) in cases() {
+			let (clear, state) = composite(mode);
+			let resolved = blend(mode.state(), src, clear);
+			assert_near(blend(state, resolved, DST), blend(mode.state(), src, DST));
+		}
+	}
+
+	#[test]
+	fn edges_mix_with_the_output() {
+		// half the samples covered resolve to the mean with the clear color
+		for (mode, src) in cases() {
+			let (clear, state) = composite(mode);
+			let resolved = mix(blend(mode.state(), src, clear), clear);
+			assert_near(blend(state, resolved, DST), mix(blend(mode.state(), src, DST), DST));
+		}
+	}
+}
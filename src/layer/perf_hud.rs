@@ -0,0 +1,132 @@
+use ash::vk;
+
+use crate::base::BaseRef;
+use crate::layer::{Layer, record_inline};
+use crate::layer::monotext::Monotext;
+use crate::layer::monotext::label_stack::line::Line;
+use crate::layer::pipeline::{BlendMode, LoadOp};
+use crate::layer::triangles::{Triangles, Vertex};
+use crate::profiler::{FrameStats, HISTORY};
+
+// graph area in normalized device coordinates, below the text
+const GRAPH_WIDTH: f32 = 0.6;
+const GRAPH_HEIGHT: f32 = 0.25;
+// frame time at the top of the graph
+const GRAPH_MAX_MS: f32 = 50.0;
+
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const BACKGROUND: [f32; 4] = [0.05, 0.05, 0.05, 1.0];
+
+// frame statistics drawn over the composited frame, fed by
+// `LayerCompositor` every frame while shown
+pub struct PerfHud {
+	base: BaseRef,
+	text: Monotext,
+	graph: Triangles,
+	lines: usize,
+}
+
+impl PerfHud {
+	pub fn new(base: BaseRef, font: image::GrayImage) -> Self {
+		let text = Monotext::new(base.clone(), font);
+		let graph = Triangles::with_modes(
+			base.clone(),
+			BlendMode::Off,
+			LoadOp::Load,
+			(HISTORY + 1) * 6,
+		);
+		Self {
+			base,
+			text,
+			graph,
+			lines: 0,
+		}
+	}
+
+	pub fn update(&mut self, stats: &FrameStats) {
+		let mut lines = vec![
+			format!("fps {:6.1}", stats.fps),
+			format!("cpu {:6.2} ms", stats.cpu_ms),
+			match stats.gpu_ms {
+				Some(ms) => format!("gpu {:6.2} ms", ms),
+				None => "gpu    n/a".to_string(),
+			},
+			format!("img {:6.1} MiB", stats.memory as f32 / (1024.0 * 1024.0)),
+		];
+		for (idx, (name, ms)) in stats.layers.iter().enumerate() {
+			lines.push(format!("{:2} {:12} {:6.2} ms", idx, name, ms));
+		}
+		let labels = &mut self.text.label_stack;
+		for (idx, line) in lines.iter().enumerate() {
+			labels.add_text(&idx.to_string(), Line::new_colored(
+				line.bytes().collect(),
+				TEXT_COLOR,
+			));
+		}
+		// stale per-layer lines after layers were removed, last first so the
+		// remaining lines keep their index
+		for idx in (lines.len()..self.lines).rev() {
+			labels.remove_text(&idx.to_string());
+		}
+		self.lines = lines.len();
+
+		let height = self.base.read().unwrap().render_resolution.height as f32;
+		let top = -1.0 + lines.len() as f32 * 32.0 * labels.scaler / height + 0.02;
+		let bottom = top + GRAPH_HEIGHT;
+		let mut vertices = Vec::with_capacity((HISTORY + 1) * 6);
+		push_rect(&mut vertices, [-1.0, top], [-1.0 + GRAPH_WIDTH, bottom], BACKGROUND);
+		let bar = GRAPH_WIDTH / HISTORY as f32;
+		let offset = HISTORY - stats.history.len();
+		for (idx, &ms) in stats.history.iter().enumerate() {
+			let color = if ms <= 1000.0 / 60.0 {
+				[0.2, 0.9, 0.2, 1.0]
+			} else if ms <= 1000.0 / 30.0 {
+				[0.9, 0.9, 0.2, 1.0]
+			} else {
+				[0.9, 0.2, 0.2, 1.0]
+			};
+			let x = -1.0 + (offset + idx) as f32 * bar;
+			let y = bottom - (ms / GRAPH_MAX_MS).min(1.0) * GRAPH_HEIGHT;
+			push_rect(&mut vertices, [x, y], [x + bar, bottom], color);
+		}
+		self.graph.vertices = vertices;
+	}
+}
+
+fn push_rect(vertices: &mut Vec<Vertex>, min: [f32; 2], max: [f32; 2], color: [f32; 4]) {
+	let corners = [
+		[min[0], min[1]], [min[0], max[1]], [max[0], max[1]],
+		[min[0], min[1]], [max[0], min[1]], [max[0], max[1]],
+	];
+	for [x, y] in corners {
+		vertices.push(Vertex {
+			pos: [x, y, 0.0, 1.0],
+			color,
+		});
+	}
+}
+
+impl Layer for PerfHud {
+	fn set_output(&mut self, images: Vec<vk::Image>) {
+		self.graph.set_output(images.clone());
+		self.text.set_output(images);
+	}
+
+	fn resize(&mut self, extent: vk::Extent2D) {
+		self.graph.resize(extent);
+		self.text.resize(extent);
+	}
+
+	fn prepare(&mut self, idx: usize) -> bool {
+		let graph = self.graph.prepare(idx);
+		let text = self.text.prepare(idx);
+		graph || text
+	}
+
+	// two render passes, recorded inline
+	fn render(&self, command_buffer: vk::CommandBuffer, idx: usize) {
+		let base = self.base.read().unwrap();
+		record_inline(&base.device, command_buffer, &self.graph, idx);
+		record_inline(&base.device, command_buffer, &self.text, idx);
+	}
+}
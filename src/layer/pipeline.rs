@@ -0,0 +1,1515 @@
+use ash::util::*;
+use ash::vk;
+use std::default::Default;
+use std::ffi::CStr;
+use std::io::Cursor;
+use std::marker::PhantomData;
+use std::mem;
+use serde::Deserialize;
+use std::sync::{Arc, RwLock};
+
+use crate::base::{Base, BaseRef, record_submit_commandbuffer, find_memorytype_index};
+use crate::layer::{Layer, RenderTarget};
+use crate::layer::msaa::{self, Multisample};
+
+// vertex types read by the shaders of a pipeline, attributes are bound to
+// locations 0, 1, ... in order
+pub trait VertexLayout: Copy + Send + Sync + 'static {
+	// (format, offset) of every attribute
+	fn attributes() -> Vec<(vk::Format, u32)>;
+}
+
+pub fn no_blending() -> vk::PipelineColorBlendAttachmentState {
+	vk::PipelineColorBlendAttachmentState {
+		blend_enable: 0,
+		src_color_blend_factor: vk::BlendFactor::SRC_COLOR,
+		dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_DST_COLOR,
+		color_blend_op: vk::BlendOp::ADD,
+		src_alpha_blend_factor: vk::BlendFactor::ZERO,
+		dst_alpha_blend_factor: vk::BlendFactor::ZERO,
+		alpha_blend_op: vk::BlendOp::ADD,
+		color_write_mask: vk::ColorComponentFlags::RGBA,
+	}
+}
+
+pub fn alpha_blending() -> vk::PipelineColorBlendAttachmentState {
+	vk::PipelineColorBlendAttachmentState {
+		blend_enable: 1,
+		src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+		dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+		color_blend_op: vk::BlendOp::ADD,
+		src_alpha_blend_factor: vk::BlendFactor::ONE,
+		dst_alpha_blend_factor: vk::BlendFactor::ZERO,
+		alpha_blend_op: vk::BlendOp::ADD,
+		color_write_mask: vk::ColorComponentFlags::RGBA,
+	}
+}
+
+// how a layer's colors combine with the output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum BlendMode {
+	// replaces the output, alpha included
+	#[default]
+	Off,
+	// colors with straight alpha drawn over the output
+	Alpha,
+	// colors already multiplied with their alpha drawn over the output
+	Premultiplied,
+	// colors scaled by their alpha added to the output
+	Additive,
+	// the output multiplied with the colors, alpha is ignored
+	Multiply,
+}
+
+impl BlendMode {
+	pub fn state(self) -> vk::PipelineColorBlendAttachmentState {
+		let (src_color, dst_color, src_alpha, dst_alpha) = match self {
+			BlendMode::Off => return no_blending(),
+			BlendMode::Alpha => (
+				vk::BlendFactor::SRC_ALPHA,
+				vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+				vk::BlendFactor::ONE,
+				vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+			),
+			BlendMode::Premultiplied => (
+				vk::BlendFactor::ONE,
+				vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+				vk::BlendFactor::ONE,
+				vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+			),
+			BlendMode::Additive => (
+				vk::BlendFactor::SRC_ALPHA,
+				vk::BlendFactor::ONE,
+				vk::BlendFactor::ZERO,
+				vk::BlendFactor::ONE,
+			),
+			BlendMode::Multiply => (
+				vk::BlendFactor::DST_COLOR,
+				vk::BlendFactor::ZERO,
+				vk::BlendFactor::ZERO,
+				vk::BlendFactor::ONE,
+			),
+		};
+		vk::PipelineColorBlendAttachmentState {
+			blend_enable: 1,
+			src_color_blend_factor: src_color,
+			dst_color_blend_factor: dst_color,
+			color_blend_op: vk::BlendOp::ADD,
+			src_alpha_blend_factor: src_alpha,
+			dst_alpha_blend_factor: dst_alpha,
+			alpha_blend_op: vk::BlendOp::ADD,
+			color_write_mask: vk::ColorComponentFlags::RGBA,
+		}
+	}
+}
+
+// what a layer starts from when drawing into the output
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum LoadOp {
+	// draws over the layers below
+	Load,
+	// replaces them with a color
+	Clear([f32; 4]),
+}
+
+// a single subpass drawing into the output image, or a subpass of another
+// render pass, see `subpass`
+pub struct PipelineBuilder {
+	vertex_code: Vec<u32>,
+	fragment_code: Vec<u32>,
+	stride: u32,
+	attributes: Vec<vk::VertexInputAttributeDescription>,
+	// stride and attributes of binding 1, advanced per instance
+	instance: Option<(u32, Vec<(vk::Format, u32)>)>,
+	// one pipeline per topology, the first is bound by `Pipeline::bind`
+	topologies: Vec<vk::PrimitiveTopology>,
+	load_op: vk::AttachmentLoadOp,
+	clear_color: [f32; 4],
+	blend: vk::PipelineColorBlendAttachmentState,
+	// none for a raw `blend` state, which multisampled layers composite
+	// like `BlendMode::Premultiplied`
+	blend_mode: Option<BlendMode>,
+	// bindings of the combined image samplers in set 0
+	textures: Vec<u32>,
+	// bindings of the input attachments in set 0
+	inputs: Vec<u32>,
+	// bindings and stages of the uniform buffers in set 0
+	uniforms: Vec<(u32, vk::ShaderStageFlags)>,
+	push_constants: Option<(vk::ShaderStageFlags, u32)>,
+	samples: vk::SampleCountFlags,
+	depth: bool,
+	// render pass owned by the caller and the subpass drawn in
+	subpass: Option<(vk::RenderPass, u32)>,
+}
+
+impl PipelineBuilder {
+	// triangle list drawn over the output without blending
+	pub fn new(vertex_spv: &[u8], fragment_spv: &[u8]) -> Self {
+		let vertex_code = read_spv(&mut Cursor::new(vertex_spv))
+			.expect("Failed to read vertex shader spv file");
+		let fragment_code = read_spv(&mut Cursor::new(fragment_spv))
+			.expect("Failed to read fragment shader spv file");
+		Self::from_code(vertex_code, fragment_code)
+	}
+
+	pub fn from_code(vertex_code: Vec<u32>, fragment_code: Vec<u32>) -> Self {
+		Self {
+			vertex_code,
+			fragment_code,
+			stride: 0,
+			attributes: Vec::new(),
+			instance: None,
+			topologies: vec![vk::PrimitiveTopology::TRIANGLE_LIST],
+			load_op: vk::AttachmentLoadOp::LOAD,
+			clear_color: [0.0; 4],
+			blend: no_blending(),
+			blend_mode: None,
+			textures: Vec::new(),
+			inputs: Vec::new(),
+			uniforms: Vec::new(),
+			push_constants: None,
+			samples: vk::SampleCountFlags::TYPE_1,
+			depth: false,
+			subpass: None,
+		}
+	}
+
+	pub fn vertex<V: VertexLayout>(mut self) -> Self {
+		self.stride = mem::size_of::<V>() as u32;
+		self.attributes = V::attributes()
+			.into_iter()
+			.enumerate()
+			.map(|(location, (format, offset))| vk::VertexInputAttributeDescription {
+				location: location as u32,
+				binding: 0,
+				format,
+				offset,
+			})
+			.collect();
+		self
+	}
+
+	// attributes bound to the locations after those of `vertex`
+	pub fn instance<I: VertexLayout>(mut self) -> Self {
+		self.instance = Some((mem::size_of::<I>() as u32, I::attributes()));
+		self
+	}
+
+	pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+		self.topologies = vec![topology];
+		self
+	}
+
+	// switched between with `Pipeline::bind_topology`
+	pub fn topologies(mut self, topologies: &[vk::PrimitiveTopology]) -> Self {
+		self.topologies = topologies.to_vec();
+		self
+	}
+
+	// CLEAR replaces the output instead of drawing over it
+	pub fn load_op(mut self, load_op: vk::AttachmentLoadOp) -> Self {
+		self.load_op = load_op;
+		self
+	}
+
+	// like `load_op`, with the color CLEAR uses
+	pub fn load(mut self, load: LoadOp) -> Self {
+		match load {
+			LoadOp::Load => self.load_op = vk::AttachmentLoadOp::LOAD,
+			LoadOp::Clear(color) => {
+				self.load_op = vk::AttachmentLoadOp::CLEAR;
+				self.clear_color = color;
+			}
+		}
+		self
+	}
+
+	pub fn blend(mut self, blend: vk::PipelineColorBlendAttachmentState) -> Self {
+		self.blend = blend;
+		self.blend_mode = None;
+		self
+	}
+
+	// also picks how multisampled layers are composited, see `Multisample`
+	pub fn blend_mode(mut self, mode: BlendMode) -> Self {
+		self.blend = mode.state();
+		self.blend_mode = Some(mode);
+		self
+	}
+
+	// sampled by the fragment shader, see `Pipeline::write_texture`
+	pub fn texture(mut self, binding: u32) -> Self {
+		self.textures.push(binding);
+		self
+	}
+
+	// read by the fragment shader with subpassLoad, the descriptor sets are
+	// allocated by the caller, see `Pipeline::bind_with`
+	pub fn input_attachment(mut self, binding: u32) -> Self {
+		self.inputs.push(binding);
+		self
+	}
+
+	// read by `stages`, see `Pipeline::write_buffer`
+	pub fn uniform(mut self, binding: u32, stages: vk::ShaderStageFlags) -> Self {
+		self.uniforms.push((binding, stages));
+		self
+	}
+
+	pub fn push_constants(mut self, stages: vk::ShaderStageFlags, size: u32) -> Self {
+		self.push_constants = Some((stages, size));
+		self
+	}
+
+	// multisampled anti-aliasing, lowered to the highest count the output
+	// supports. see `Multisample`
+	pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+		self.samples = samples;
+		self
+	}
+
+	// depth tested and written with a depth image per output, cleared to 1.0
+	// at the start of every frame
+	pub fn depth(mut self, depth: bool) -> Self {
+		self.depth = depth;
+		self
+	}
+
+	// draw in `subpass` of `renderpass` instead of a render pass of the
+	// pipeline's own, which then has no outputs. `samples` and `depth` are
+	// ignored
+	pub fn subpass(mut self, renderpass: vk::RenderPass, subpass: u32) -> Self {
+		self.subpass = Some((renderpass, subpass));
+		self
+	}
+
+	pub fn build(&self, base: &Base) -> Pipeline { unsafe {
+		if let Some((renderpass, _)) = self.subpass {
+			return self.build_pipeline(base, renderpass, None, None);
+		}
+		let device = &base.device;
+		let depth_format = self.depth.then(|| find_depth_format(base));
+		let samples = msaa::supported_samples(base, self.samples, self.depth);
+		if samples != vk::SampleCountFlags::TYPE_1 {
+			let renderpass = msaa::render_pass(
+				device,
+				base.surface_format.format,
+				samples,
+				self.load_op,
+				depth_format,
+			);
+			let multisample = Multisample::new(
+				base,
+				renderpass,
+				samples,
+				self.load_op,
+				self.blend_mode.unwrap_or(BlendMode::Premultiplied),
+			);
+			return self.build_pipeline(base, renderpass, Some(multisample), depth_format);
+		}
+
+		let mut renderpass_attachments = vec![
+			vk::AttachmentDescription {
+				format: base.surface_format.format,
+				samples: vk::SampleCountFlags::TYPE_1,
+				load_op: self.load_op,
+				store_op: vk::AttachmentStoreOp::STORE,
+				initial_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+				final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+				..Default::default()
+			},
+		];
+		if let Some(format) = depth_format {
+			renderpass_attachments.push(depth_attachment(format, vk::SampleCountFlags::TYPE_1));
+		}
+		let color_attachment_refs = [vk::AttachmentReference {
+			attachment: 0,
+			layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+		}];
+		let depth_attachment_ref = vk::AttachmentReference {
+			attachment: 1,
+			layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+		};
+		let dependencies = [external_dependency()];
+
+		let mut subpass = vk::SubpassDescription::default()
+			.color_attachments(&color_attachment_refs)
+			.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+		if depth_format.is_some() {
+			subpass = subpass.depth_stencil_attachment(&depth_attachment_ref);
+		}
+
+		let renderpass_create_info = vk::RenderPassCreateInfo::default()
+			.attachments(&renderpass_attachments)
+			.subpasses(std::slice::from_ref(&subpass))
+			.dependencies(&dependencies);
+
+		let renderpass = device
+			.create_render_pass(&renderpass_create_info, None)
+			.unwrap();
+		self.build_pipeline(base, renderpass, None, depth_format)
+	}}
+
+	unsafe fn build_pipeline(
+		&self,
+		base: &Base,
+		renderpass: vk::RenderPass,
+		multisample: Option<Multisample>,
+		depth_format: Option<vk::Format>,
+	) -> Pipeline {
+		let device = &base.device;
+		let vertex_shader_info = vk::ShaderModuleCreateInfo::default().code(&self.vertex_code);
+		let frag_shader_info = vk::ShaderModuleCreateInfo::default().code(&self.fragment_code);
+
+		let vertex_shader_module = device.create_shader_module(&vertex_shader_info, None)
+			.expect("Vertex shader module error");
+		let fragment_shader_module = device.create_shader_module(&frag_shader_info, None)
+			.expect("Fragment shader module error");
+
+		let bindings: Vec<_> = self.textures
+			.iter()
+			.map(|&binding| (
+				binding,
+				vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+				vk::ShaderStageFlags::FRAGMENT,
+			))
+			.chain(self.inputs
+				.iter()
+				.map(|&binding| (
+					binding,
+					vk::DescriptorType::INPUT_ATTACHMENT,
+					vk::ShaderStageFlags::FRAGMENT,
+				))
+			)
+			.chain(self.uniforms
+				.iter()
+				.map(|&(binding, stages)| (binding, vk::DescriptorType::UNIFORM_BUFFER, stages))
+			)
+			.collect();
+		let (descriptor_set_layout, descriptor_pool, descriptor_set) = if bindings.is_empty() {
+			(None, None, None)
+		} else {
+			let descriptor_sizes: Vec<_> = [
+				(vk::DescriptorType::COMBINED_IMAGE_SAMPLER, self.textures.len()),
+				(vk::DescriptorType::INPUT_ATTACHMENT, self.inputs.len()),
+				(vk::DescriptorType::UNIFORM_BUFFER, self.uniforms.len()),
+			]
+				.into_iter()
+				.filter(|&(_, count)| count > 0)
+				.map(|(ty, count)| vk::DescriptorPoolSize {
+					ty,
+					descriptor_count: count as u32,
+				})
+				.collect();
+			let descriptor_pool_info = vk::DescriptorPoolCreateInfo::default()
+				.pool_sizes(&descriptor_sizes)
+				.max_sets(1);
+			let descriptor_pool = device
+				.create_descriptor_pool(&descriptor_pool_info, None)
+				.unwrap();
+			let desc_layout_bindings: Vec<_> = bindings
+				.iter()
+				.map(|&(binding, descriptor_type, stage_flags)| vk::DescriptorSetLayoutBinding {
+					binding,
+					descriptor_type,
+					descriptor_count: 1,
+					stage_flags,
+					..Default::default()
+				})
+				.collect();
+			let descriptor_info =
+				vk::DescriptorSetLayoutCreateInfo::default().bindings(&desc_layout_bindings);
+			let descriptor_set_layout = device
+				.create_descriptor_set_layout(&descriptor_info, None)
+				.unwrap();
+			let set_layouts = [descriptor_set_layout];
+			let desc_alloc_info = vk::DescriptorSetAllocateInfo::default()
+				.descriptor_pool(descriptor_pool)
+				.set_layouts(&set_layouts);
+			let descriptor_set = device
+				.allocate_descriptor_sets(&desc_alloc_info)
+				.unwrap()[0];
+			(Some(descriptor_set_layout), Some(descriptor_pool), Some(descriptor_set))
+		};
+
+		let set_layouts: Vec<_> = descriptor_set_layout.into_iter().collect();
+		let push_constant_ranges: Vec<_> = self.push_constants
+			.iter()
+			.map(|&(stage_flags, size)| vk::PushConstantRange {
+				stage_flags,
+				offset: 0,
+				size,
+			})
+			.collect();
+		let layout_create_info = vk::PipelineLayoutCreateInfo::default()
+			.set_layouts(&set_layouts)
+			.push_constant_ranges(&push_constant_ranges);
+		let pipeline_layout = device.create_pipeline_layout(&layout_create_info, None)
+			.unwrap();
+
+		let shader_entry_name = CStr::from_bytes_with_nul_unchecked(b"main\0");
+		let shader_stage_create_infos = [
+			vk::PipelineShaderStageCreateInfo {
+				module: vertex_shader_module,
+				p_name: shader_entry_name.as_ptr(),
+				stage: vk::ShaderStageFlags::VERTEX,
+				..Default::default()
+			},
+			vk::PipelineShaderStageCreateInfo {
+				module: fragment_shader_module,
+				p_name: shader_entry_name.as_ptr(),
+				stage: vk::ShaderStageFlags::FRAGMENT,
+				..Default::default()
+			},
+		];
+		let mut vertex_input_binding_descriptions = vec![vk::VertexInputBindingDescription {
+			binding: 0,
+			stride: self.stride,
+			input_rate: vk::VertexInputRate::VERTEX,
+		}];
+		let mut attributes = self.attributes.clone();
+		if let Some((stride, instance_attributes)) = &self.instance {
+			vertex_input_binding_descriptions.push(vk::VertexInputBindingDescription {
+				binding: 1,
+				stride: *stride,
+				input_rate: vk::VertexInputRate::INSTANCE,
+			});
+			let first = attributes.len();
+			attributes.extend(instance_attributes
+				.iter()
+				.enumerate()
+				.map(|(i, &(format, offset))| vk::VertexInputAttributeDescription {
+					location: (first + i) as u32,
+					binding: 1,
+					format,
+					offset,
+				})
+			);
+		}
+		let vertex_input_state_info = if attributes.is_empty() {
+			vk::PipelineVertexInputStateCreateInfo::default()
+		} else {
+			vk::PipelineVertexInputStateCreateInfo::default()
+				.vertex_attribute_descriptions(&attributes)
+				.vertex_binding_descriptions(&vertex_input_binding_descriptions)
+		};
+		let viewports = [viewport(base.render_resolution)];
+		let scissors = [base.render_resolution.into()];
+		let viewport_state_info = vk::PipelineViewportStateCreateInfo::default()
+			.scissors(&scissors)
+			.viewports(&viewports);
+
+		let rasterization_info = vk::PipelineRasterizationStateCreateInfo {
+			front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+			line_width: 1.0,
+			polygon_mode: vk::PolygonMode::FILL,
+			..Default::default()
+		};
+		let multisample_state_info = vk::PipelineMultisampleStateCreateInfo {
+			rasterization_samples: multisample
+				.as_ref()
+				.map_or(vk::SampleCountFlags::TYPE_1, |multisample| multisample.samples()),
+			..Default::default()
+		};
+		let depth_state_info = vk::PipelineDepthStencilStateCreateInfo {
+			depth_test_enable: depth_format.is_some() as u32,
+			depth_write_enable: depth_format.is_some() as u32,
+			depth_compare_op: vk::CompareOp::LESS_OR_EQUAL,
+			max_depth_bounds: 1.0,
+			..Default::default()
+		};
+		let color_blend_attachment_states = [self.blend];
+		let color_blend_state = vk::PipelineColorBlendStateCreateInfo::default()
+			.attachments(&color_blend_attachment_states);
+
+		let dynamic_state = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+		let dynamic_state_info =
+			vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_state);
+
+		let input_assembly_state_infos: Vec<_> = self.topologies
+			.iter()
+			.map(|&topology| vk::PipelineInputAssemblyStateCreateInfo {
+				topology,
+				..Default::default()
+			})
+			.collect();
+		let graphic_pipeline_infos: Vec<_> = input_assembly_state_infos
+			.iter()
+			.map(|input_assembly_state_info| vk::GraphicsPipelineCreateInfo::default()
+				.stages(&shader_stage_create_infos)
+				.vertex_input_state(&vertex_input_state_info)
+				.input_assembly_state(input_assembly_state_info)
+				.viewport_state(&viewport_state_info)
+				.rasterization_state(&rasterization_info)
+				.multisample_state(&multisample_state_info)
+				.depth_stencil_state(&depth_state_info)
+				.color_blend_state(&color_blend_state)
+				.dynamic_state(&dynamic_state_info)
+				.layout(pipeline_layout)
+				.render_pass(renderpass)
+				.subpass(self.subpass.map_or(0, |(_, subpass)| subpass))
+			)
+			.collect();
+
+		let pipelines = device
+			.create_graphics_pipelines(vk::PipelineCache::null(), &graphic_pipeline_infos, None)
+			.expect("Unable to create graphics pipeline");
+
+		Pipeline {
+			device: device.clone(),
+			format: base.surface_format.format,
+			renderpass,
+			owns_renderpass: self.subpass.is_none(),
+			pipeline_layout,
+			pipelines: self.topologies.iter().copied().zip(pipelines).collect(),
+			vertex_shader_module,
+			fragment_shader_module,
+			descriptor_set_layout,
+			descriptor_pool,
+			descriptor_set,
+			output_image_views: Vec::new(),
+			framebuffers: Vec::new(),
+			extent: base.render_resolution,
+			clear_color: self.clear_color,
+			memory_properties: base.device_memory_properties,
+			depth_format,
+			depth_images: Vec::new(),
+			multisample,
+			rewritten: false,
+		}
+	}
+}
+
+// the first one usable as depth attachment, D16_UNORM always is
+fn find_depth_format(base: &Base) -> vk::Format { unsafe {
+	[
+		vk::Format::D32_SFLOAT,
+		vk::Format::X8_D24_UNORM_PACK32,
+		vk::Format::D16_UNORM,
+	]
+		.into_iter()
+		.find(|&format| base.instance
+			.get_physical_device_format_properties(base.pdevice, format)
+			.optimal_tiling_features
+			.contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+		)
+		.unwrap_or(vk::Format::D16_UNORM)
+}}
+
+// cleared every frame and never stored
+pub fn depth_attachment(format: vk::Format, samples: vk::SampleCountFlags) -> vk::AttachmentDescription {
+	vk::AttachmentDescription {
+		format,
+		samples,
+		load_op: vk::AttachmentLoadOp::CLEAR,
+		store_op: vk::AttachmentStoreOp::DONT_CARE,
+		initial_layout: vk::ImageLayout::UNDEFINED,
+		final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+		..Default::default()
+	}
+}
+
+// waits for the earlier writes to the output and the depth image
+pub fn external_dependency() -> vk::SubpassDependency {
+	vk::SubpassDependency {
+		src_subpass: vk::SUBPASS_EXTERNAL,
+		dst_subpass: 0,
+		src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+			| vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+		src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+		dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+			| vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+		dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
+			| vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+			| vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+		..Default::default()
+	}
+}
+
+// an image only used as attachment of a pipeline's render pass
+pub struct Attachment {
+	pub image: vk::Image,
+	pub memory: vk::DeviceMemory,
+	pub view: vk::ImageView,
+}
+
+impl Attachment {
+	pub unsafe fn new(
+		device: &ash::Device,
+		memory_properties: &vk::PhysicalDeviceMemoryProperties,
+		format: vk::Format,
+		extent: vk::Extent2D,
+		samples: vk::SampleCountFlags,
+		usage: vk::ImageUsageFlags,
+	) -> Self {
+		let image_create_info = vk::ImageCreateInfo {
+			image_type: vk::ImageType::TYPE_2D,
+			format,
+			extent: extent.into(),
+			mip_levels: 1,
+			array_layers: 1,
+			samples,
+			tiling: vk::ImageTiling::OPTIMAL,
+			usage: usage | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+			sharing_mode: vk::SharingMode::EXCLUSIVE,
+			..Default::default()
+		};
+		let image = device.create_image(&image_create_info, None).unwrap();
+		let memory_req = device.get_image_memory_requirements(image);
+		// never leaves the tile memory on tilers
+		let memory_index = find_memorytype_index(
+			&memory_req,
+			memory_properties,
+			vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+		)
+			.or_else(|| find_memorytype_index(
+				&memory_req,
+				memory_properties,
+				vk::MemoryPropertyFlags::DEVICE_LOCAL,
+			))
+			.expect("Unable to find suitable memorytype for the attachment.");
+		let allocate_info = vk::MemoryAllocateInfo {
+			allocation_size: memory_req.size,
+			memory_type_index: memory_index,
+			..Default::default()
+		};
+		let memory = device.allocate_memory(&allocate_info, None).unwrap();
+		device.bind_image_memory(image, memory, 0).unwrap();
+		let aspect_mask = if usage.contains(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT) {
+			vk::ImageAspectFlags::DEPTH
+		} else {
+			vk::ImageAspectFlags::COLOR
+		};
+		let view_info = vk::ImageViewCreateInfo::default()
+			.view_type(vk::ImageViewType::TYPE_2D)
+			.format(format)
+			.subresource_range(vk::ImageSubresourceRange {
+				aspect_mask,
+				base_mip_level: 0,
+				level_count: 1,
+				base_array_layer: 0,
+				layer_count: 1,
+			})
+			.image(image);
+		let view = device.create_image_view(&view_info, None).unwrap();
+		Self { image, memory, view }
+	}
+
+	pub unsafe fn destroy(&self, device: &ash::Device) {
+		device.destroy_image_view(self.view, None);
+		device.destroy_image(self.image, None);
+		device.free_memory(self.memory, None);
+	}
+}
+
+fn viewport(extent: vk::Extent2D) -> vk::Viewport {
+	vk::Viewport {
+		x: 0.0,
+		y: 0.0,
+		width: extent.width as f32,
+		height: extent.height as f32,
+		min_depth: 0.0,
+		max_depth: 1.0,
+	}
+}
+
+// render pass, pipeline and framebuffers of the output images, the parts of
+// `Layer` every drawing layer shares
+pub struct Pipeline {
+	device: ash::Device,
+	format: vk::Format,
+	renderpass: vk::RenderPass,
+	// false if built for a subpass of another render pass
+	owns_renderpass: bool,
+	pipeline_layout: vk::PipelineLayout,
+	pipelines: Vec<(vk::PrimitiveTopology, vk::Pipeline)>,
+	vertex_shader_module: vk::ShaderModule,
+	fragment_shader_module: vk::ShaderModule,
+	descriptor_set_layout: Option<vk::DescriptorSetLayout>,
+	descriptor_pool: Option<vk::DescriptorPool>,
+	descriptor_set: Option<vk::DescriptorSet>,
+	output_image_views: Vec<vk::ImageView>,
+	framebuffers: Vec<vk::Framebuffer>,
+	extent: vk::Extent2D,
+	clear_color: [f32; 4],
+	memory_properties: vk::PhysicalDeviceMemoryProperties,
+	depth_format: Option<vk::Format>,
+	depth_images: Vec<Attachment>,
+	multisample: Option<Multisample>,
+	// the descriptor set was rewritten since `take_rewritten`
+	rewritten: bool,
+}
+
+impl Pipeline {
+	pub fn layout(&self) -> vk::PipelineLayout {
+		self.pipeline_layout
+	}
+
+	pub fn extent(&self) -> vk::Extent2D {
+		self.extent
+	}
+
+	pub fn viewport(&self) -> vk::Viewport {
+		viewport(self.extent)
+	}
+
+	// for framebuffers of images that aren't outputs
+	pub fn render_pass(&self) -> vk::RenderPass {
+		self.renderpass
+	}
+
+	// for descriptor sets allocated by the caller, see `bind_with`
+	pub fn descriptor_set_layout(&self) -> vk::DescriptorSetLayout {
+		self.descriptor_set_layout.expect("no descriptor bindings")
+	}
+
+	pub fn write_texture(&self, binding: u32, texture: &Texture) {
+		self.write_image(binding, texture.image_view, texture.sampler);
+	}
+
+	// replaces `texture` with `image` and points `binding` at it. the old
+	// descriptor invalidates recorded command buffers, so the layer has to
+	// return `take_rewritten` from `Layer::tick` to be recorded again
+	pub fn replace_texture(&mut self, base: &Base, binding: u32, texture: &mut Texture, image: image::RgbaImage) {
+		// the old texture may still be drawn
+		unsafe { base.device.device_wait_idle().unwrap() };
+		let (width, height) = image.dimensions();
+		*texture = Texture::new(
+			base,
+			vk::Format::R8G8B8A8_UNORM,
+			vk::Extent2D { width, height },
+			&image.into_raw(),
+		);
+		self.write_texture(binding, texture);
+		self.rewritten = true;
+	}
+
+	// true once after `replace_texture`
+	pub fn take_rewritten(&mut self) -> bool {
+		mem::take(&mut self.rewritten)
+	}
+
+	// `view` is sampled in SHADER_READ_ONLY_OPTIMAL
+	pub fn write_image(&self, binding: u32, view: vk::ImageView, sampler: vk::Sampler) { unsafe {
+		let texture_descriptor = vk::DescriptorImageInfo {
+			image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+			image_view: view,
+			sampler,
+		};
+		let write_desc_sets = [
+			vk::WriteDescriptorSet {
+				dst_set: self.descriptor_set.expect("no texture bindings"),
+				dst_binding: binding,
+				descriptor_count: 1,
+				descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+				p_image_info: &texture_descriptor,
+				..Default::default()
+			},
+		];
+		self.device.update_descriptor_sets(&write_desc_sets, &[]);
+	}}
+
+	// `buffer` holds the whole uniform block of `binding`
+	pub fn write_buffer(&self, binding: u32, buffer: vk::Buffer) { unsafe {
+		let buffer_descriptor = vk::DescriptorBufferInfo {
+			buffer,
+			offset: 0,
+			range: vk::WHOLE_SIZE,
+		};
+		let write_desc_sets = [
+			vk::WriteDescriptorSet {
+				dst_set: self.descriptor_set.expect("no uniform bindings"),
+				dst_binding: binding,
+				descriptor_count: 1,
+				descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+				p_buffer_info: &buffer_descriptor,
+				..Default::default()
+			},
+		];
+		self.device.update_descriptor_sets(&write_desc_sets, &[]);
+	}}
+
+	// for `Layer::resize`, framebuffers are created by `set_output`
+	pub fn resize(&mut self, extent: vk::Extent2D) {
+		self.extent = extent;
+	}
+
+	// for `Layer::set_output`, called again on resize, the device is idle then
+	pub fn set_output(&mut self, images: Vec<vk::Image>) { unsafe {
+		for framebuffer in self.framebuffers.drain(..) {
+			self.device.destroy_framebuffer(framebuffer, None);
+		}
+		for image_view in self.output_image_views.drain(..) {
+			self.device.destroy_image_view(image_view, None);
+		}
+		for depth_image in self.depth_images.drain(..) {
+			depth_image.destroy(&self.device);
+		}
+		let (multisample_views, samples) = match &mut self.multisample {
+			Some(multisample) => (
+				multisample.set_output(self.extent, images.len()),
+				multisample.samples(),
+			),
+			None => (Vec::new(), vk::SampleCountFlags::TYPE_1),
+		};
+		if let Some(format) = self.depth_format {
+			self.depth_images = (0..images.len())
+				.map(|_| Attachment::new(
+					&self.device,
+					&self.memory_properties,
+					format,
+					self.extent,
+					samples,
+					vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+				))
+				.collect();
+		}
+		let (framebuffers, image_views) = images.into_iter()
+			.enumerate()
+			.map(|(i, image)| {
+				let create_view_info = vk::ImageViewCreateInfo::default()
+					.view_type(vk::ImageViewType::TYPE_2D)
+					.format(self.format)
+					.components(vk::ComponentMapping {
+						r: vk::ComponentSwizzle::R,
+						g: vk::ComponentSwizzle::G,
+						b: vk::ComponentSwizzle::B,
+						a: vk::ComponentSwizzle::A,
+					})
+					.subresource_range(vk::ImageSubresourceRange {
+						aspect_mask: vk::ImageAspectFlags::COLOR,
+						base_mip_level: 0,
+						level_count: 1,
+						base_array_layer: 0,
+						layer_count: 1,
+					})
+					.image(image);
+				let image_view = self.device.create_image_view(&create_view_info, None).unwrap();
+				let mut framebuffer_attachments = multisample_views.get(i).cloned().unwrap_or_default();
+				framebuffer_attachments.push(image_view);
+				if let Some(depth_image) = self.depth_images.get(i) {
+					framebuffer_attachments.push(depth_image.view);
+				}
+				let frame_buffer_create_info = vk::FramebufferCreateInfo::default()
+					.render_pass(self.renderpass)
+					.attachments(&framebuffer_attachments)
+					.width(self.extent.width)
+					.height(self.extent.height)
+					.layers(1);
+				let framebuffer = self.device
+					.create_framebuffer(&frame_buffer_create_info, None)
+					.unwrap();
+				(framebuffer, image_view)
+			}).unzip();
+		self.framebuffers = framebuffers;
+		self.output_image_views = image_views;
+	}}
+
+	fn clear_values(&self) -> Vec<vk::ClearValue> {
+		let clear = |color| vk::ClearValue {
+			color: vk::ClearColorValue {
+				float32: color,
+			},
+		};
+		// a multisampled image blended over the output starts with the color
+		// the blend mode leaves unchanged, one resolved into it replaces it and
+		// starts with `clear_color`
+		let mut clear_values = match &self.multisample {
+			Some(multisample) if multisample.composites() => {
+				vec![clear(multisample.clear_color()), clear([0.0; 4]), clear(self.clear_color)]
+			}
+			Some(_) => vec![clear(self.clear_color), clear([0.0; 4])],
+			None => vec![clear(self.clear_color)],
+		};
+		if self.depth_format.is_some() {
+			clear_values.push(vk::ClearValue {
+				depth_stencil: vk::ClearDepthStencilValue {
+					depth: 1.0,
+					stencil: 0,
+				},
+			});
+		}
+		clear_values
+	}
+
+	// for `Layer::target`. multisampled layers blended over the output use
+	// two subpasses, which a secondary command buffer can't, so they begin
+	// their render pass themselves in `begin` and are recorded inline
+	pub fn target(&self, idx: usize) -> Option<RenderTarget> {
+		if self.composites() {
+			return None;
+		}
+		Some(RenderTarget {
+			render_pass: self.renderpass,
+			framebuffer: self.framebuffers[idx],
+			render_area: self.extent.into(),
+			clear_values: self.clear_values(),
+		})
+	}
+
+	// for `Layer::render`, `bind` after beginning the render pass if the
+	// layer has no target
+	pub fn begin(&self, command_buffer: vk::CommandBuffer, idx: usize) { unsafe {
+		if self.composites() {
+			let clear_values = self.clear_values();
+			let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+				.render_pass(self.renderpass)
+				.framebuffer(self.framebuffers[idx])
+				.render_area(self.extent.into())
+				.clear_values(&clear_values);
+			self.device.cmd_begin_render_pass(
+				command_buffer,
+				&render_pass_begin_info,
+				vk::SubpassContents::INLINE,
+			);
+		}
+		self.bind(command_buffer);
+	}}
+
+	// after the draws of `begin`, blends multisampled layers over the output
+	pub fn end(&self, command_buffer: vk::CommandBuffer, idx: usize) {
+		if let Some(multisample) = &self.multisample {
+			if multisample.composites() {
+				multisample.finish(command_buffer, idx);
+			}
+		}
+	}
+
+	fn composites(&self) -> bool {
+		self.multisample.as_ref().is_some_and(|multisample| multisample.composites())
+	}
+
+	// bind the first pipeline, the descriptor set and the output viewport
+	pub fn bind(&self, command_buffer: vk::CommandBuffer) {
+		self.bind_sets(command_buffer, self.descriptor_set);
+	}
+
+	// like `bind`, with a descriptor set of `descriptor_set_layout` allocated
+	// by the caller instead of the pipeline's own
+	pub fn bind_with(&self, command_buffer: vk::CommandBuffer, descriptor_set: vk::DescriptorSet) {
+		self.bind_sets(command_buffer, Some(descriptor_set));
+	}
+
+	fn bind_sets(&self, command_buffer: vk::CommandBuffer, descriptor_set: Option<vk::DescriptorSet>) { unsafe {
+		let device = &self.device;
+		if let Some(descriptor_set) = descriptor_set {
+			device.cmd_bind_descriptor_sets(
+				command_buffer,
+				vk::PipelineBindPoint::GRAPHICS,
+				self.pipeline_layout,
+				0,
+				&[descriptor_set],
+				&[],
+			);
+		}
+		device.cmd_bind_pipeline(
+			command_buffer,
+			vk::PipelineBindPoint::GRAPHICS,
+			self.pipelines[0].1,
+		);
+		device.cmd_set_viewport(command_buffer, 0, &[viewport(self.extent)]);
+		device.cmd_set_scissor(command_buffer, 0, &[self.extent.into()]);
+	}}
+
+	// switch to the pipeline of another topology given to the builder
+	pub fn bind_topology(&self, command_buffer: vk::CommandBuffer, topology: vk::PrimitiveTopology) {
+		let &(_, pipeline) = self.pipelines
+			.iter()
+			.find(|&&(t, _)| t == topology)
+			.unwrap_or_else(|| panic!("pipeline built without {:?}", topology));
+		unsafe {
+			self.device.cmd_bind_pipeline(
+				command_buffer,
+				vk::PipelineBindPoint::GRAPHICS,
+				pipeline,
+			);
+		}
+	}
+}
+
+impl Drop for Pipeline {
+	fn drop(&mut self) { unsafe {
+		let device = &self.device;
+		device.device_wait_idle().unwrap();
+		for &(_, pipeline) in self.pipelines.iter() {
+			device.destroy_pipeline(pipeline, None);
+		}
+		device.destroy_pipeline_layout(self.pipeline_layout, None);
+		if let Some(descriptor_set_layout) = self.descriptor_set_layout {
+			device.destroy_descriptor_set_layout(descriptor_set_layout, None);
+		}
+		if let Some(descriptor_pool) = self.descriptor_pool {
+			device.destroy_descriptor_pool(descriptor_pool, None);
+		}
+		device.destroy_shader_module(self.vertex_shader_module, None);
+		device.destroy_shader_module(self.fragment_shader_module, None);
+		for &image_view in self.output_image_views.iter() {
+			device.destroy_image_view(image_view, None);
+		}
+		for &framebuffer in self.framebuffers.iter() {
+			device.destroy_framebuffer(framebuffer, None);
+		}
+		for depth_image in self.depth_images.iter() {
+			depth_image.destroy(device);
+		}
+		self.multisample = None;
+		if self.owns_renderpass {
+			device.destroy_render_pass(self.renderpass, None);
+		}
+	}}
+}
+
+// host visible vertex or index buffer, reallocated to fit larger uploads
+pub struct HostBuffer<T: Copy> {
+	device: ash::Device,
+	memory_properties: vk::PhysicalDeviceMemoryProperties,
+	usage: vk::BufferUsageFlags,
+	buffer: vk::Buffer,
+	memory: vk::DeviceMemory,
+	memory_req: vk::MemoryRequirements,
+	capacity: usize,
+	_item: PhantomData<T>,
+}
+
+unsafe fn allocate_buffer(
+	device: &ash::Device,
+	memory_properties: &vk::PhysicalDeviceMemoryProperties,
+	usage: vk::BufferUsageFlags,
+	size: u64,
+) -> (vk::Buffer, vk::DeviceMemory, vk::MemoryRequirements) {
+	let buffer_info = vk::BufferCreateInfo {
+		size,
+		usage,
+		sharing_mode: vk::SharingMode::EXCLUSIVE,
+		..Default::default()
+	};
+	let buffer = device.create_buffer(&buffer_info, None).unwrap();
+	let memory_req = device.get_buffer_memory_requirements(buffer);
+	let memory_index = find_memorytype_index(
+		&memory_req,
+		memory_properties,
+		vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+	)
+	.expect("Unable to find suitable memorytype for the buffer.");
+	let allocate_info = vk::MemoryAllocateInfo {
+		allocation_size: memory_req.size,
+		memory_type_index: memory_index,
+		..Default::default()
+	};
+	let memory = device.allocate_memory(&allocate_info, None).unwrap();
+	device.bind_buffer_memory(buffer, memory, 0).unwrap();
+	(buffer, memory, memory_req)
+}
+
+impl<T: Copy> HostBuffer<T> {
+	// `capacity` items fit before the first reallocation
+	pub fn new(base: &Base, usage: vk::BufferUsageFlags, capacity: usize) -> Self { unsafe {
+		let capacity = capacity.max(1);
+		let (buffer, memory, memory_req) = allocate_buffer(
+			&base.device,
+			&base.device_memory_properties,
+			usage,
+			(capacity * mem::size_of::<T>()) as u64,
+		);
+		Self {
+			device: base.device.clone(),
+			memory_properties: base.device_memory_properties,
+			usage,
+			buffer,
+			memory,
+			memory_req,
+			capacity,
+			_item: PhantomData,
+		}
+	}}
+
+	// changes after `upload` grows the buffer
+	pub fn buffer(&self) -> vk::Buffer {
+		self.buffer
+	}
+
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	// at least doubles the capacity so that growing data reallocates rarely,
+	// waits for the device since frames in flight may read the old buffer
+	fn reserve(&mut self, len: usize) { unsafe {
+		if len <= self.capacity {
+			return;
+		}
+		let capacity = len.max(self.capacity * 2);
+		self.device.device_wait_idle().unwrap();
+		self.device.free_memory(self.memory, None);
+		self.device.destroy_buffer(self.buffer, None);
+		let (buffer, memory, memory_req) = allocate_buffer(
+			&self.device,
+			&self.memory_properties,
+			self.usage,
+			(capacity * mem::size_of::<T>()) as u64,
+		);
+		self.buffer = buffer;
+		self.memory = memory;
+		self.memory_req = memory_req;
+		self.capacity = capacity;
+	}}
+
+	// the buffer must not be in use, returns the number of items written
+	pub fn upload(&mut self, items: &[T]) -> u32 { unsafe {
+		self.reserve(items.len());
+		let ptr = self.device.map_memory(
+			self.memory,
+			0,
+			self.memory_req.size,
+			vk::MemoryMapFlags::empty(),
+		)
+		.unwrap();
+		let mut align = Align::new(
+			ptr,
+			mem::align_of::<T>() as u64,
+			self.memory_req.size,
+		);
+		align.copy_from_slice(items);
+		self.device.unmap_memory(self.memory);
+		items.len() as u32
+	}}
+}
+
+impl<T: Copy> Drop for HostBuffer<T> {
+	fn drop(&mut self) { unsafe {
+		self.device.device_wait_idle().unwrap();
+		self.device.free_memory(self.memory, None);
+		self.device.destroy_buffer(self.buffer, None);
+	}}
+}
+
+// sampled image uploaded once, with a linear mirrored repeat sampler
+pub struct Texture {
+	device: ash::Device,
+	image: vk::Image,
+	memory: vk::DeviceMemory,
+	image_view: vk::ImageView,
+	sampler: vk::Sampler,
+}
+
+impl Texture {
+	// `data` is tightly packed in `format`
+	pub fn new(base: &Base, format: vk::Format, extent: vk::Extent2D, data: &[u8]) -> Self { unsafe {
+		let device = &base.device;
+		let image_buffer_info = vk::BufferCreateInfo {
+			size: data.len() as u64,
+			usage: vk::BufferUsageFlags::TRANSFER_SRC,
+			sharing_mode: vk::SharingMode::EXCLUSIVE,
+			..Default::default()
+		};
+		let image_buffer = device.create_buffer(&image_buffer_info, None).unwrap();
+		let image_buffer_memory_req = device.get_buffer_memory_requirements(image_buffer);
+		let image_buffer_memory_index = find_memorytype_index(
+			&image_buffer_memory_req,
+			&base.device_memory_properties,
+			vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+		).unwrap();
+		let image_buffer_allocate_info = vk::MemoryAllocateInfo {
+			allocation_size: image_buffer_memory_req.size,
+			memory_type_index: image_buffer_memory_index,
+			..Default::default()
+		};
+		let image_buffer_memory = device
+			.allocate_memory(&image_buffer_allocate_info, None)
+			.unwrap();
+		let image_ptr = device
+			.map_memory(
+				image_buffer_memory,
+				0,
+				image_buffer_memory_req.size,
+				vk::MemoryMapFlags::empty(),
+			)
+			.unwrap();
+		let mut image_slice = Align::new(
+			image_ptr,
+			mem::align_of::<u8>() as u64,
+			image_buffer_memory_req.size,
+		);
+		image_slice.copy_from_slice(data);
+		device.unmap_memory(image_buffer_memory);
+		device
+			.bind_buffer_memory(image_buffer, image_buffer_memory, 0)
+			.unwrap();
+
+		let texture_create_info = vk::ImageCreateInfo {
+			image_type: vk::ImageType::TYPE_2D,
+			format,
+			extent: extent.into(),
+			mip_levels: 1,
+			array_layers: 1,
+			samples: vk::SampleCountFlags::TYPE_1,
+			tiling: vk::ImageTiling::OPTIMAL,
+			usage: vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+			sharing_mode: vk::SharingMode::EXCLUSIVE,
+			..Default::default()
+		};
+		let image = device.create_image(&texture_create_info, None).unwrap();
+		let texture_memory_req = device.get_image_memory_requirements(image);
+		let texture_memory_index = find_memorytype_index(
+			&texture_memory_req,
+			&base.device_memory_properties,
+			vk::MemoryPropertyFlags::DEVICE_LOCAL,
+		).unwrap();
+		let texture_allocate_info = vk::MemoryAllocateInfo {
+			allocation_size: texture_memory_req.size,
+			memory_type_index: texture_memory_index,
+			..Default::default()
+		};
+		let memory = device.allocate_memory(&texture_allocate_info, None).unwrap();
+		device.bind_image_memory(image, memory, 0).unwrap();
+
+		let subresource_range = vk::ImageSubresourceRange {
+			aspect_mask: vk::ImageAspectFlags::COLOR,
+			level_count: 1,
+			layer_count: 1,
+			..Default::default()
+		};
+		record_submit_commandbuffer(
+			device,
+			base.setup_command_buffer,
+			base.setup_commands_reuse_fence,
+			base.present_queue,
+			&[],
+			&[],
+			&[],
+			|device, texture_command_buffer| {
+				let texture_barrier = vk::ImageMemoryBarrier {
+					dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+					new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+					image,
+					subresource_range,
+					..Default::default()
+				};
+				device.cmd_pipeline_barrier(
+					texture_command_buffer,
+					vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+					vk::PipelineStageFlags::TRANSFER,
+					vk::DependencyFlags::empty(),
+					&[],
+					&[],
+					&[texture_barrier],
+				);
+				let buffer_copy_regions = vk::BufferImageCopy::default()
+					.image_subresource(
+						vk::ImageSubresourceLayers::default()
+							.aspect_mask(vk::ImageAspectFlags::COLOR)
+							.layer_count(1),
+					)
+					.image_extent(extent.into());
+				device.cmd_copy_buffer_to_image(
+					texture_command_buffer,
+					image_buffer,
+					image,
+					vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+					&[buffer_copy_regions],
+				);
+				let texture_barrier_end = vk::ImageMemoryBarrier {
+					src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+					dst_access_mask: vk::AccessFlags::SHADER_READ,
+					old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+					new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+					image,
+					subresource_range,
+					..Default::default()
+				};
+				device.cmd_pipeline_barrier(
+					texture_command_buffer,
+					vk::PipelineStageFlags::TRANSFER,
+					vk::PipelineStageFlags::FRAGMENT_SHADER,
+					vk::DependencyFlags::empty(),
+					&[],
+					&[],
+					&[texture_barrier_end],
+				);
+			},
+		);
+		// the staging buffer is freed once the copy is done
+		device
+			.wait_for_fences(&[base.setup_commands_reuse_fence], true, std::u64::MAX)
+			.unwrap();
+		device.destroy_buffer(image_buffer, None);
+		device.free_memory(image_buffer_memory, None);
+
+		let sampler_info = vk::SamplerCreateInfo {
+			mag_filter: vk::Filter::LINEAR,
+			min_filter: vk::Filter::LINEAR,
+			mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+			address_mode_u: vk::SamplerAddressMode::MIRRORED_REPEAT,
+			address_mode_v: vk::SamplerAddressMode::MIRRORED_REPEAT,
+			address_mode_w: vk::SamplerAddressMode::MIRRORED_REPEAT,
+			max_anisotropy: 1.0,
+			border_color: vk::BorderColor::FLOAT_OPAQUE_WHITE,
+			compare_op: vk::CompareOp::NEVER,
+			..Default::default()
+		};
+		let sampler = device.create_sampler(&sampler_info, None).unwrap();
+		let texture_image_view_info = vk::ImageViewCreateInfo {
+			view_type: vk::ImageViewType::TYPE_2D,
+			format,
+			components: vk::ComponentMapping {
+				r: vk::ComponentSwizzle::R,
+				g: vk::ComponentSwizzle::G,
+				b: vk::ComponentSwizzle::B,
+				a: vk::ComponentSwizzle::A,
+			},
+			subresource_range,
+			image,
+			..Default::default()
+		};
+		let image_view = device
+			.create_image_view(&texture_image_view_info, None)
+			.unwrap();
+		Self {
+			device: device.clone(),
+			image,
+			memory,
+			image_view,
+			sampler,
+		}
+	}}
+}
+
+impl Drop for Texture {
+	fn drop(&mut self) { unsafe {
+		let device = &self.device;
+		device.device_wait_idle().unwrap();
+		device.destroy_sampler(self.sampler, None);
+		device.destroy_image_view(self.image_view, None);
+		device.destroy_image(self.image, None);
+		device.free_memory(self.memory, None);
+	}}
+}
+
+pub struct DrawContext<'a> {
+	pub device: &'a ash::Device,
+	pub command_buffer: vk::CommandBuffer,
+	pub layout: vk::PipelineLayout,
+	// uploaded by the last `prepare`
+	pub vertex_count: u32,
+	pub idx: usize,
+}
+
+pub type DrawFn = Box<dyn Fn(&DrawContext) + Send + Sync>;
+
+// a layer from shaders, a vertex type and a draw callback, e.g.
+//
+// let builder = PipelineBuilder::new(vert, frag)
+// 	.vertex::<MyVertex>()
+// 	.blend(alpha_blending());
+// let layer = RenderLayer::new(base, &builder, 1024);
+pub struct RenderLayer<V: VertexLayout> {
+	pub vertices: Vec<V>,
+	base: BaseRef,
+	pipeline: Pipeline,
+	vertex_buffer: HostBuffer<V>,
+	count: u32,
+	// draws all vertices once if none
+	draw: Option<DrawFn>,
+}
+
+impl<V: VertexLayout> RenderLayer<V> {
+	pub fn new_ref(base: BaseRef, builder: &PipelineBuilder, capacity: usize) -> Arc<RwLock<Self>> {
+		Arc::new(RwLock::new(Self::new(base, builder, capacity)))
+	}
+
+	pub fn new(base: BaseRef, builder: &PipelineBuilder, capacity: usize) -> Self {
+		let (pipeline, vertex_buffer) = {
+			let base = base.read().unwrap();
+			(
+				builder.build(&base),
+				HostBuffer::new(&base, vk::BufferUsageFlags::VERTEX_BUFFER, capacity),
+			)
+		};
+		Self {
+			vertices: Vec::new(),
+			base,
+			pipeline,
+			vertex_buffer,
+			count: 0,
+			draw: None,
+		}
+	}
+
+	// records the draw calls, the pipeline and vertex buffer are bound
+	pub fn with_draw<F: Fn(&DrawContext) + Send + Sync + 'static>(mut self, draw: F) -> Self {
+		self.draw = Some(Box::new(draw));
+		self
+	}
+
+	pub fn pipeline(&self) -> &Pipeline {
+		&self.pipeline
+	}
+}
+
+impl<V: VertexLayout> Layer for RenderLayer<V> {
+	fn set_output(&mut self, images: Vec<vk::Image>) {
+		self.pipeline.set_output(images);
+	}
+
+	fn resize(&mut self, extent: vk::Extent2D) {
+		self.pipeline.resize(extent);
+	}
+
+	fn prepare(&mut self, _idx: usize) -> bool {
+		let buffer = self.vertex_buffer.buffer();
+		let count = self.vertex_buffer.upload(&self.vertices);
+		let changed = count != self.count || buffer != self.vertex_buffer.buffer();
+		self.count = count;
+		changed
+	}
+
+	fn target(&self, idx: usize) -> Option<RenderTarget> {
+		self.pipeline.target(idx)
+	}
+
+	fn render(&self, command_buffer: vk::CommandBuffer, idx: usize) { unsafe {
+		let base = self.base.read().unwrap();
+		let device = &base.device;
+		self.pipeline.begin(command_buffer, idx);
+		device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer.buffer()], &[0]);
+		match &self.draw {
+			Some(draw) => draw(&DrawContext {
+				device,
+				command_buffer,
+				layout: self.pipeline.layout(),
+				vertex_count: self.count,
+				idx,
+			}),
+			None => device.cmd_draw(command_buffer, self.count, 1, 0, 0),
+		}
+		self.pipeline.end(command_buffer, idx);
+	}}
+}
+
+#[cfg(test)]
+mod tests {
+	use ash::vk;
+
+	use super::{alpha_blending, BlendMode, LoadOp, PipelineBuilder};
+
+	#[test]
+	fn load_sets_the_clear_color() {
+		let builder = PipelineBuilder::from_code(Vec::new(), Vec::new())
+			.load(LoadOp::Clear([0.0, 0.0, 1.0, 1.0]));
+		assert_eq!(builder.load_op, vk::AttachmentLoadOp::CLEAR);
+		assert_eq!(builder.clear_color, [0.0, 0.0, 1.0, 1.0]);
+		let builder = builder.load(LoadOp::Load);
+		assert_eq!(builder.load_op, vk::AttachmentLoadOp::LOAD);
+	}
+
+	#[test]
+	fn raw_blend_states_have_no_mode() {
+		let builder = PipelineBuilder::from_code(Vec::new(), Vec::new())
+			.blend_mode(BlendMode::Multiply);
+		assert_eq!(builder.blend_mode, Some(BlendMode::Multiply));
+		assert_eq!(builder.blend.src_color_blend_factor, vk::BlendFactor::DST_COLOR);
+		let builder = builder.blend(alpha_blending());
+		assert_eq!(builder.blend_mode, None);
+		assert_eq!(BlendMode::Off.state().blend_enable, 0);
+	}
+}
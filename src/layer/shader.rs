@@ -0,0 +1,222 @@
+use std::mem;
+use std::sync::{Arc, RwLock};
+use ash::vk;
+
+use crate::animation::FrameTime;
+use crate::base::BaseRef;
+use crate::input::{InputContext, InputEvent, InputHandler};
+use crate::layer::{Layer, RenderTarget};
+use crate::layer::pipeline::{Pipeline, PipelineBuilder, Texture};
+
+// push constants of a shader layer, see src/shader/plasma.frag
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub struct ShaderConstants {
+	pub params: [[f32; 4]; 4],
+	pub resolution: [f32; 2],
+	// normalized device coordinates of the last pointer position
+	pub mouse: [f32; 2],
+	pub time: f32,
+	pub delta: f32,
+	pub frame: u32,
+	_pad: u32,
+}
+
+// a fullscreen triangle shaded by a user fragment shader, sampling the
+// cache images given to `LayerCompositor::set_inputs` at bindings 0, 1, ...
+pub struct ShaderLayer {
+	base: BaseRef,
+	// passed to the shader as is
+	pub params: [[f32; 4]; 4],
+	// false: rendered again only when the pointer moves or params change
+	pub animated: bool,
+	constants: ShaderConstants,
+	pipeline: Pipeline,
+	inputs: u32,
+	sampler: vk::Sampler,
+	input_views: Vec<vk::ImageView>,
+	// sampled by every input until `set_input`
+	_placeholder: Texture,
+}
+
+impl ShaderLayer {
+	pub fn new_ref(base: BaseRef, spirv: Vec<u32>, inputs: u32) -> Arc<RwLock<Self>> {
+		Arc::new(RwLock::new(Self::new(base, spirv, inputs)))
+	}
+
+	pub fn new(base: BaseRef, spirv: Vec<u32>, inputs: u32) -> Self { unsafe {
+		let (pipeline, sampler, placeholder) = {
+			let base = base.read().unwrap();
+			let vertex_code = ash::util::read_spv(&mut std::io::Cursor::new(
+				&include_bytes!("../../assets/spvs/fullscreen_vert.spv")[..],
+			))
+				.expect("Failed to read vertex shader spv file");
+			let mut builder = PipelineBuilder::from_code(vertex_code, spirv)
+				.push_constants(
+					vk::ShaderStageFlags::FRAGMENT,
+					mem::size_of::<ShaderConstants>() as u32,
+				);
+			for binding in 0..inputs {
+				builder = builder.texture(binding);
+			}
+			let sampler_info = vk::SamplerCreateInfo {
+				mag_filter: vk::Filter::LINEAR,
+				min_filter: vk::Filter::LINEAR,
+				mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+				address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+				address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+				address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+				max_anisotropy: 1.0,
+				border_color: vk::BorderColor::FLOAT_OPAQUE_WHITE,
+				compare_op: vk::CompareOp::NEVER,
+				..Default::default()
+			};
+			let sampler = base.device.create_sampler(&sampler_info, None).unwrap();
+			let pipeline = builder.build(&base);
+			let placeholder = Texture::new(
+				&base,
+				vk::Format::R8G8B8A8_UNORM,
+				vk::Extent2D { width: 1, height: 1 },
+				&[0; 4],
+			);
+			for binding in 0..inputs {
+				pipeline.write_texture(binding, &placeholder);
+			}
+			(pipeline, sampler, placeholder)
+		};
+		Self {
+			base,
+			params: [[0.0; 4]; 4],
+			animated: true,
+			constants: ShaderConstants::default(),
+			pipeline,
+			inputs,
+			sampler,
+			input_views: Vec::new(),
+			_placeholder: placeholder,
+		}
+	}}
+
+	fn destroy_input_views(&mut self) { unsafe {
+		let base = self.base.read().unwrap();
+		for view in self.input_views.drain(..) {
+			base.device.destroy_image_view(view, None);
+		}
+	}}
+}
+
+impl Drop for ShaderLayer {
+	fn drop(&mut self) { unsafe {
+		self.base.read().unwrap().device.device_wait_idle().unwrap();
+		self.destroy_input_views();
+		self.base.read().unwrap().device.destroy_sampler(self.sampler, None);
+	}}
+}
+
+impl Layer for ShaderLayer {
+	fn set_output(&mut self, images: Vec<vk::Image>) {
+		self.pipeline.set_output(images);
+	}
+
+	fn resize(&mut self, extent: vk::Extent2D) {
+		self.pipeline.resize(extent);
+	}
+
+	fn set_input(&mut self, images: Vec<vk::Image>) { unsafe {
+		assert_eq!(images.len() as u32, self.inputs, "input count differs from the shader");
+		self.destroy_input_views();
+		let base = self.base.read().unwrap();
+		for (binding, image) in images.into_iter().enumerate() {
+			let create_view_info = vk::ImageViewCreateInfo::default()
+				.view_type(vk::ImageViewType::TYPE_2D)
+				.format(base.surface_format.format)
+				.subresource_range(vk::ImageSubresourceRange {
+					aspect_mask: vk::ImageAspectFlags::COLOR,
+					base_mip_level: 0,
+					level_count: 1,
+					base_array_layer: 0,
+					layer_count: 1,
+				})
+				.image(image);
+			let view = base.device.create_image_view(&create_view_info, None).unwrap();
+			self.pipeline.write_image(binding as u32, view, self.sampler);
+			self.input_views.push(view);
+		}
+	}}
+
+	fn target(&self, idx: usize) -> Option<RenderTarget> {
+		self.pipeline.target(idx)
+	}
+
+	fn input(&mut self) -> Option<&mut dyn InputHandler> {
+		Some(self)
+	}
+
+	fn tick(&mut self, time: &FrameTime) -> bool {
+		let changed = self.constants.params != self.params;
+		self.constants.params = self.params;
+		if self.animated {
+			self.constants.time = time.time;
+			self.constants.delta = time.delta;
+			self.constants.frame = time.frame as u32;
+		}
+		self.animated || changed
+	}
+
+	fn prepare(&mut self, _idx: usize) -> bool {
+		let extent = self.pipeline.extent();
+		let resolution = [extent.width as f32, extent.height as f32];
+		let changed = resolution != self.constants.resolution;
+		self.constants.resolution = resolution;
+		changed
+	}
+
+	fn render(&self, command_buffer: vk::CommandBuffer, idx: usize) { unsafe {
+		let base = self.base.read().unwrap();
+		let device = &base.device;
+		self.pipeline.begin(command_buffer, idx);
+		device.cmd_push_constants(
+			command_buffer,
+			self.pipeline.layout(),
+			vk::ShaderStageFlags::FRAGMENT,
+			0,
+			std::slice::from_raw_parts(
+				&self.constants as *const ShaderConstants as *const u8,
+				mem::size_of::<ShaderConstants>(),
+			),
+		);
+		device.cmd_draw(command_buffer, 3, 1, 0, 0);
+		self.pipeline.end(command_buffer, idx);
+	}}
+}
+
+impl InputHandler for ShaderLayer {
+	// the pointer is tracked but never consumed
+	fn handle(&mut self, event: &InputEvent, ctx: &mut InputContext) -> bool {
+		if let InputEvent::PointerMoved { position } = *event {
+			self.constants.mouse = position;
+			ctx.update();
+		}
+		false
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::mem;
+
+	use super::ShaderConstants;
+	use crate::offset_of;
+
+	// std430 offsets of the push constant block in src/shader/plasma.frag
+	#[test]
+	fn constants_layout() {
+		assert_eq!(offset_of!(ShaderConstants, params), 0);
+		assert_eq!(offset_of!(ShaderConstants, resolution), 64);
+		assert_eq!(offset_of!(ShaderConstants, mouse), 72);
+		assert_eq!(offset_of!(ShaderConstants, time), 80);
+		assert_eq!(offset_of!(ShaderConstants, delta), 84);
+		assert_eq!(offset_of!(ShaderConstants, frame), 88);
+		assert_eq!(mem::size_of::<ShaderConstants>(), 96);
+	}
+}
@@ -0,0 +1,369 @@
+use ash::vk;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, RwLock};
+
+use crate::base::BaseRef;
+use crate::camera::{Camera2D, Coordinates};
+use crate::layer::{Layer, RenderTarget};
+use crate::layer::pipeline::{HostBuffer, Pipeline, PipelineBuilder, VertexLayout, alpha_blending};
+use crate::offset_of;
+
+#[derive(Clone, Debug, Copy)]
+struct Vertex {
+	pos: [f32; 2],
+	// relative to the center of the shape
+	local: [f32; 2],
+	size: [f32; 2],
+	params: [f32; 4],
+	fill: [f32; 4],
+	border: [f32; 4],
+}
+
+impl VertexLayout for Vertex {
+	fn attributes() -> Vec<(vk::Format, u32)> {
+		vec![
+			(vk::Format::R32G32_SFLOAT, offset_of!(Vertex, pos) as u32),
+			(vk::Format::R32G32_SFLOAT, offset_of!(Vertex, local) as u32),
+			(vk::Format::R32G32_SFLOAT, offset_of!(Vertex, size) as u32),
+			(vk::Format::R32G32B32A32_SFLOAT, offset_of!(Vertex, params) as u32),
+			(vk::Format::R32G32B32A32_SFLOAT, offset_of!(Vertex, fill) as u32),
+			(vk::Format::R32G32B32A32_SFLOAT, offset_of!(Vertex, border) as u32),
+		]
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShapeKind {
+	// `radius` 0.0 gives sharp corners
+	Rect { radius: f32 },
+	Ellipse,
+	// the band of `thickness` inside the ellipse
+	Ring { thickness: f32 },
+}
+
+// in world coordinates of `Shapes::camera` and `Shapes::coordinates`. the
+// border lies inside the outline, covering `border_width` of the shape
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shape {
+	pub kind: ShapeKind,
+	pub center: [f32; 2],
+	// half the width and height
+	pub size: [f32; 2],
+	pub fill: [f32; 4],
+	pub border_color: [f32; 4],
+	pub border_width: f32,
+}
+
+impl Shape {
+	fn new(kind: ShapeKind, center: [f32; 2], size: [f32; 2]) -> Self {
+		Self {
+			kind,
+			center,
+			size,
+			fill: [1.0; 4],
+			border_color: [0.0; 4],
+			border_width: 0.0,
+		}
+	}
+
+	pub fn rect(min: [f32; 2], max: [f32; 2]) -> Self {
+		Self::rounded_rect(min, max, 0.0)
+	}
+
+	pub fn rounded_rect(min: [f32; 2], max: [f32; 2], radius: f32) -> Self {
+		Self::new(
+			ShapeKind::Rect { radius },
+			[(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0],
+			[(max[0] - min[0]).abs() / 2.0, (max[1] - min[1]).abs() / 2.0],
+		)
+	}
+
+	pub fn circle(center: [f32; 2], radius: f32) -> Self {
+		Self::ellipse(center, [radius, radius])
+	}
+
+	pub fn ellipse(center: [f32; 2], radii: [f32; 2]) -> Self {
+		Self::new(ShapeKind::Ellipse, center, radii)
+	}
+
+	pub fn ring(center: [f32; 2], radius: f32, thickness: f32) -> Self {
+		Self::new(ShapeKind::Ring { thickness }, center, [radius, radius])
+	}
+
+	pub fn fill(mut self, color: [f32; 4]) -> Self {
+		self.fill = color;
+		self
+	}
+
+	pub fn border(mut self, color: [f32; 4], width: f32) -> Self {
+		self.border_color = color;
+		self.border_width = width;
+		self
+	}
+
+	// `margin` around the shape keeps the anti-aliased edge inside the quad
+	fn vertices(&self, margin: f32) -> [Vertex; 6] {
+		let (kind, param) = match self.kind {
+			ShapeKind::Rect { radius } => (0.0, radius),
+			ShapeKind::Ellipse => (1.0, 0.0),
+			ShapeKind::Ring { thickness } => (2.0, thickness),
+		};
+		let [w, h] = [self.size[0] + margin, self.size[1] + margin];
+		[[-w, -h], [w, -h], [w, h], [-w, -h], [w, h], [-w, h]].map(|local| Vertex {
+			pos: [self.center[0] + local[0], self.center[1] + local[1]],
+			local,
+			size: self.size,
+			params: [kind, param, self.border_width, 0.0],
+			fill: self.fill,
+			border: self.border_color,
+		})
+	}
+}
+
+// shapes by key in insertion order
+#[derive(Default)]
+struct ShapeMap {
+	shapes: Vec<(String, Shape)>,
+	// index into `shapes`
+	keys: HashMap<String, usize>,
+}
+
+impl ShapeMap {
+	fn set(&mut self, key: &str, shape: Shape) {
+		match self.keys.get(key) {
+			Some(&idx) => self.shapes[idx].1 = shape,
+			None => {
+				self.keys.insert(key.to_string(), self.shapes.len());
+				self.shapes.push((key.to_string(), shape));
+			}
+		}
+	}
+
+	fn get(&self, key: &str) -> Option<&Shape> {
+		self.keys.get(key).map(|&idx| &self.shapes[idx].1)
+	}
+
+	fn get_mut(&mut self, key: &str) -> Option<&mut Shape> {
+		self.keys.get(key).map(|&idx| &mut self.shapes[idx].1)
+	}
+
+	// the shapes above move down one
+	fn remove(&mut self, key: &str) -> Option<Shape> {
+		let idx = self.keys.remove(key)?;
+		let (_, shape) = self.shapes.remove(idx);
+		for (key, _) in self.shapes[idx..].iter() {
+			*self.keys.get_mut(key).unwrap() -= 1;
+		}
+		Some(shape)
+	}
+
+	fn clear(&mut self) {
+		self.shapes.clear();
+		self.keys.clear();
+	}
+
+	fn iter(&self) -> impl Iterator<Item = &Shape> {
+		self.shapes.iter().map(|(_, shape)| shape)
+	}
+}
+
+// see src/shader/shapes.vert
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct PushConstants {
+	transform: [f32; 16],
+}
+
+// rects, rounded rects, ellipses and rings by key, drawn in insertion order
+// as quads whose edges are anti-aliased with signed distances in the
+// fragment shader. the camera works like the one of `Triangles`
+pub struct Shapes {
+	pub camera: Camera2D,
+	pub coordinates: Coordinates,
+	shapes: ShapeMap,
+
+	base: BaseRef,
+	pipeline: Pipeline,
+	vertex_buffer: HostBuffer<Vertex>,
+	transform: [f32; 16],
+	// vertices uploaded by the last `prepare`
+	count: u32,
+}
+
+impl Shapes {
+	pub fn new_ref(base: BaseRef) -> Arc<RwLock<Self>> {
+		Arc::new(RwLock::new(Self::new(base)))
+	}
+
+	pub fn new(base: BaseRef) -> Self {
+		let (pipeline, vertex_buffer) = {
+			let base = base.read().unwrap();
+			let pipeline = PipelineBuilder::new(
+				include_bytes!("../../assets/spvs/shapes_vert.spv"),
+				include_bytes!("../../assets/spvs/shapes_frag.spv"),
+			)
+				.vertex::<Vertex>()
+				.blend(alpha_blending())
+				.push_constants(
+					vk::ShaderStageFlags::VERTEX,
+					mem::size_of::<PushConstants>() as u32,
+				)
+				.build(&base);
+			let vertex_buffer = HostBuffer::new(&base, vk::BufferUsageFlags::VERTEX_BUFFER, 0);
+			(pipeline, vertex_buffer)
+		};
+		Self {
+			camera: Camera2D::default(),
+			coordinates: Coordinates::default(),
+			shapes: ShapeMap::default(),
+			base,
+			pipeline,
+			vertex_buffer,
+			transform: Camera2D::default().matrix([1.0, 1.0]),
+			count: 0,
+		}
+	}
+
+	// replaces the shape of `key` in place, or adds it on top
+	pub fn set(&mut self, key: &str, shape: Shape) {
+		self.shapes.set(key, shape);
+	}
+
+	pub fn get(&self, key: &str) -> Option<&Shape> {
+		self.shapes.get(key)
+	}
+
+	pub fn get_mut(&mut self, key: &str) -> Option<&mut Shape> {
+		self.shapes.get_mut(key)
+	}
+
+	pub fn remove(&mut self, key: &str) -> Option<Shape> {
+		self.shapes.remove(key)
+	}
+
+	pub fn clear(&mut self) {
+		self.shapes.clear();
+	}
+
+	pub fn len(&self) -> usize {
+		self.shapes.shapes.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.shapes.shapes.is_empty()
+	}
+
+	// of the output in pixels
+	pub fn extent(&self) -> [f32; 2] {
+		let extent = self.pipeline.extent();
+		[extent.width as f32, extent.height as f32]
+	}
+}
+
+impl Layer for Shapes {
+	fn set_output(&mut self, images: Vec<vk::Image>) {
+		self.pipeline.set_output(images);
+	}
+
+	fn resize(&mut self, extent: vk::Extent2D) {
+		self.pipeline.resize(extent);
+	}
+
+	fn target(&self, idx: usize) -> Option<RenderTarget> {
+		self.pipeline.target(idx)
+	}
+
+	fn prepare(&mut self, _idx: usize) -> bool {
+		let extent = self.extent();
+		let transform = self.camera.matrix_in(self.coordinates, extent);
+		let buffer = self.vertex_buffer.buffer();
+		// two pixels in world units
+		let a = self.camera.to_world_in(self.coordinates, [0.0, 0.0], extent);
+		let b = self.camera.to_world_in(self.coordinates, [4.0 / extent[0].max(1.0), 0.0], extent);
+		let margin = ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt();
+		let vertices: Vec<Vertex> = self.shapes
+			.iter()
+			.flat_map(|shape| shape.vertices(margin))
+			.collect();
+		let count = self.vertex_buffer.upload(&vertices);
+		let changed = transform != self.transform
+			|| count != self.count
+			|| buffer != self.vertex_buffer.buffer();
+		self.transform = transform;
+		self.count = count;
+		changed
+	}
+
+	fn render(&self, draw_command_buffer: vk::CommandBuffer, idx: usize) { unsafe {
+		let base = self.base.read().unwrap();
+		let device = &base.device;
+		self.pipeline.begin(draw_command_buffer, idx);
+		device.cmd_bind_vertex_buffers(
+			draw_command_buffer,
+			0,
+			&[self.vertex_buffer.buffer()],
+			&[0],
+		);
+		let push_constants = PushConstants {
+			transform: self.transform,
+		};
+		device.cmd_push_constants(
+			draw_command_buffer,
+			self.pipeline.layout(),
+			vk::ShaderStageFlags::VERTEX,
+			0,
+			std::slice::from_raw_parts(
+				&push_constants as *const PushConstants as *const u8,
+				mem::size_of::<PushConstants>(),
+			),
+		);
+		device.cmd_draw(draw_command_buffer, self.count, 1, 0, 0);
+		self.pipeline.end(draw_command_buffer, idx);
+	}}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Shape, ShapeMap};
+
+	fn map(keys: &[&str]) -> ShapeMap {
+		let mut map = ShapeMap::default();
+		for (i, key) in keys.iter().enumerate() {
+			map.set(key, Shape::circle([i as f32, 0.0], 1.0));
+		}
+		map
+	}
+
+	fn order(map: &ShapeMap) -> Vec<f32> {
+		map.iter().map(|shape| shape.center[0]).collect()
+	}
+
+	#[test]
+	fn set_replaces_in_place() {
+		let mut map = map(&["a", "b", "c"]);
+		map.set("a", Shape::circle([5.0, 0.0], 1.0));
+		assert_eq!(order(&map), [5.0, 1.0, 2.0]);
+	}
+
+	#[test]
+	fn remove_reindexes() {
+		let mut map = map(&["a", "b", "c", "d"]);
+		assert_eq!(map.remove("b").unwrap().center, [1.0, 0.0]);
+		assert!(map.remove("b").is_none());
+		assert_eq!(order(&map), [0.0, 2.0, 3.0]);
+		// the keys above the removed shape still find theirs
+		assert_eq!(map.get("a").unwrap().center, [0.0, 0.0]);
+		assert_eq!(map.get("c").unwrap().center, [2.0, 0.0]);
+		assert_eq!(map.get("d").unwrap().center, [3.0, 0.0]);
+		map.get_mut("d").unwrap().center = [7.0, 0.0];
+		map.set("b", Shape::circle([8.0, 0.0], 1.0));
+		assert_eq!(order(&map), [0.0, 2.0, 7.0, 8.0]);
+		// the last and the first
+		map.remove("b");
+		map.remove("a");
+		assert_eq!(order(&map), [2.0, 7.0]);
+		assert_eq!(map.get("c").unwrap().center, [2.0, 0.0]);
+		assert_eq!(map.get("d").unwrap().center, [7.0, 0.0]);
+	}
+}
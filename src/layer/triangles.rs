@@ -1,14 +1,12 @@
-use ash::util::*;
 use ash::vk;
-use std::default::Default;
-use std::io::Cursor;
-use std::ffi::CStr;
 use std::mem;
-use std::ops::Drop;
+use std::ops::Range;
 use std::sync::{Arc, RwLock};
 
-use crate::base::{BaseRef, find_memorytype_index};
-use crate::layer::Layer;
+use crate::base::BaseRef;
+use crate::camera::{Camera2D, Coordinates};
+use crate::layer::{Layer, RenderTarget};
+use crate::layer::pipeline::{BlendMode, HostBuffer, LoadOp, Pipeline, PipelineBuilder, VertexLayout};
 use crate::offset_of;
 
 #[derive(Clone, Debug, Copy)]
@@ -17,20 +15,150 @@ pub struct Vertex {
 	pub color: [f32; 4],
 }
 
+impl VertexLayout for Vertex {
+	fn attributes() -> Vec<(vk::Format, u32)> {
+		vec![
+			(vk::Format::R32G32B32A32_SFLOAT, offset_of!(Vertex, pos) as u32),
+			(vk::Format::R32G32B32A32_SFLOAT, offset_of!(Vertex, color) as u32),
+		]
+	}
+}
+
+// see src/shader/triangle.vert
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct PushConstants {
+	transform: [f32; 16],
+	point_size: f32,
+}
+
+// topologies a batch can use
+const TOPOLOGIES: [vk::PrimitiveTopology; 6] = [
+	vk::PrimitiveTopology::TRIANGLE_LIST,
+	vk::PrimitiveTopology::TRIANGLE_STRIP,
+	vk::PrimitiveTopology::TRIANGLE_FAN,
+	vk::PrimitiveTopology::LINE_LIST,
+	vk::PrimitiveTopology::LINE_STRIP,
+	vk::PrimitiveTopology::POINT_LIST,
+];
+
+#[derive(Clone, Debug)]
+pub enum Indices {
+	U16(Vec<u16>),
+	U32(Vec<u32>),
+}
+
+impl Default for Indices {
+	fn default() -> Self {
+		Self::U32(Vec::new())
+	}
+}
+
+impl Indices {
+	pub fn len(&self) -> usize {
+		match self {
+			Self::U16(indices) => indices.len(),
+			Self::U32(indices) => indices.len(),
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	fn index_type(&self) -> vk::IndexType {
+		match self {
+			Self::U16(_) => vk::IndexType::UINT16,
+			Self::U32(_) => vk::IndexType::UINT32,
+		}
+	}
+
+	fn as_bytes(&self) -> &[u8] { unsafe {
+		match self {
+			Self::U16(indices) => std::slice::from_raw_parts(
+				indices.as_ptr() as *const u8,
+				indices.len() * mem::size_of::<u16>(),
+			),
+			Self::U32(indices) => std::slice::from_raw_parts(
+				indices.as_ptr() as *const u8,
+				indices.len() * mem::size_of::<u32>(),
+			),
+		}
+	}}
+}
+
+// a range of `Triangles::vertices`, or of `Triangles::indices` if indexed,
+// drawn with one topology
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Batch {
+	pub topology: vk::PrimitiveTopology,
+	pub indexed: bool,
+	pub first: u32,
+	pub count: u32,
+	// in pixels, for point lists
+	pub point_size: f32,
+}
+
+impl Batch {
+	pub fn vertices(topology: vk::PrimitiveTopology, range: Range<u32>) -> Self {
+		Self {
+			topology,
+			indexed: false,
+			first: range.start,
+			count: range.end - range.start,
+			point_size: 1.0,
+		}
+	}
+
+	pub fn indices(topology: vk::PrimitiveTopology, range: Range<u32>) -> Self {
+		Self {
+			indexed: true,
+			..Self::vertices(topology, range)
+		}
+	}
+
+	pub fn point_size(mut self, point_size: f32) -> Self {
+		self.point_size = point_size;
+		self
+	}
+}
+
+// `batches` or the default batch for the uploaded data, ranges past the
+// data are cut off
+fn draws(batches: &[Batch], vertex_count: u32, index_count: u32) -> Vec<Batch> {
+	let mut draws = if !batches.is_empty() {
+		batches.to_vec()
+	} else if index_count > 0 {
+		vec![Batch::indices(vk::PrimitiveTopology::TRIANGLE_LIST, 0..index_count)]
+	} else {
+		vec![Batch::vertices(vk::PrimitiveTopology::TRIANGLE_LIST, 0..vertex_count)]
+	};
+	for batch in draws.iter_mut() {
+		let len = if batch.indexed { index_count } else { vertex_count };
+		batch.first = batch.first.min(len);
+		batch.count = batch.count.min(len - batch.first);
+	}
+	draws
+}
+
 pub struct Triangles {
 	pub vertices: Vec<Vertex>,
+	pub indices: Indices,
+	// none: all indices if there are any, all vertices otherwise, as a
+	// triangle list
+	pub batches: Vec<Batch>,
+	// vertex positions are in world coordinates of the camera, which are
+	// `coordinates` moved and zoomed by it
+	pub camera: Camera2D,
+	pub coordinates: Coordinates,
 	base: BaseRef,
-	graphics_pipelines: Vec<vk::Pipeline>,
-	pipeline_layout: vk::PipelineLayout,
-	vertex_shader_module: vk::ShaderModule,
-	vertex_input_buffer: vk::Buffer,
-	vertex_input_buffer_memory: vk::DeviceMemory,
-	vertex_input_buffer_memory_req: vk::MemoryRequirements,
-	fragment_shader_module: vk::ShaderModule,
-	output_image_views: Vec<vk::ImageView>,
-	framebuffers: Vec<vk::Framebuffer>,
-	renderpass: vk::RenderPass,
-	viewports: Vec<vk::Viewport>,
+	pipeline: Pipeline,
+	vertex_buffer: HostBuffer<Vertex>,
+	index_buffer: HostBuffer<u8>,
+	index_type: vk::IndexType,
+	// batches of the data uploaded by the last `prepare`
+	draws: Vec<Batch>,
+	transform: [f32; 16],
 }
 
 impl Triangles {
@@ -38,339 +166,211 @@ impl Triangles {
 		Arc::new(RwLock::new(Self::new(base)))
 	}
 
-	pub fn new(base: BaseRef) -> Self { unsafe {
-		let base_clone = base.clone();
-		let base = base.read().unwrap();
-		let device = &base.device;
-
-		let renderpass_attachments = [
-			vk::AttachmentDescription {
-				format: base.surface_format.format,
-				samples: vk::SampleCountFlags::TYPE_1,
-				load_op: vk::AttachmentLoadOp::CLEAR,
-				store_op: vk::AttachmentStoreOp::STORE,
-				final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
-				..Default::default()
-			},
-		];
-		let color_attachment_refs = [vk::AttachmentReference {
-			attachment: 0,
-			layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-		}];
-		let dependencies = [vk::SubpassDependency {
-			src_subpass: vk::SUBPASS_EXTERNAL,
-			src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-			dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
-				| vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-			dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-			..Default::default()
-		}];
-
-		let subpass = vk::SubpassDescription::default()
-			.color_attachments(&color_attachment_refs)
-			.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
-
-		let renderpass_create_info = vk::RenderPassCreateInfo::default()
-			.attachments(&renderpass_attachments)
-			.subpasses(std::slice::from_ref(&subpass))
-			.dependencies(&dependencies);
-
-		let renderpass = device
-			.create_render_pass(&renderpass_create_info, None)
-			.unwrap();
-
-		let mut vertex_spv_file =
-			Cursor::new(&include_bytes!("../../assets/spvs/triangle_vert.spv")[..]);
-		let mut frag_spv_file =
-			Cursor::new(&include_bytes!("../../assets/spvs/triangle_frag.spv")[..]);
-
-		let vertex_code =
-			read_spv(&mut vertex_spv_file).expect("Failed to read vertex shader spv file");
-		let vertex_shader_info = vk::ShaderModuleCreateInfo::default().code(&vertex_code);
-
-		let frag_code =
-			read_spv(&mut frag_spv_file).expect("Failed to read fragment shader spv file");
-		let frag_shader_info = vk::ShaderModuleCreateInfo::default().code(&frag_code);
-
-		let vertex_shader_module = device.create_shader_module(&vertex_shader_info, None)
-			.expect("Vertex shader module error");
-
-		let fragment_shader_module = device.create_shader_module(&frag_shader_info, None)
-			.expect("Fragment shader module error");
-
-		let layout_create_info = vk::PipelineLayoutCreateInfo::default();
-
-		let pipeline_layout = device.create_pipeline_layout(&layout_create_info, None)
-			.unwrap();
-
-		let shader_entry_name = CStr::from_bytes_with_nul_unchecked(b"main\0");
-		let shader_stage_create_infos = [
-			vk::PipelineShaderStageCreateInfo {
-				module: vertex_shader_module,
-				p_name: shader_entry_name.as_ptr(),
-				stage: vk::ShaderStageFlags::VERTEX,
-				..Default::default()
-			},
-			vk::PipelineShaderStageCreateInfo {
-				s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
-				module: fragment_shader_module,
-				p_name: shader_entry_name.as_ptr(),
-				stage: vk::ShaderStageFlags::FRAGMENT,
-				..Default::default()
-			},
-		];
-		let vertex_input_binding_descriptions = [vk::VertexInputBindingDescription {
-			binding: 0,
-			stride: mem::size_of::<Vertex>() as u32,
-			input_rate: vk::VertexInputRate::VERTEX,
-		}];
-		let vertex_input_attribute_descriptions = [
-			vk::VertexInputAttributeDescription {
-				location: 0,
-				binding: 0,
-				format: vk::Format::R32G32B32A32_SFLOAT,
-				offset: offset_of!(Vertex, pos) as u32,
-			},
-			vk::VertexInputAttributeDescription {
-				location: 1,
-				binding: 0,
-				format: vk::Format::R32G32B32A32_SFLOAT,
-				offset: offset_of!(Vertex, color) as u32,
-			},
-		];
-
-		let vertex_input_state_info = vk::PipelineVertexInputStateCreateInfo::default()
-			.vertex_attribute_descriptions(&vertex_input_attribute_descriptions)
-			.vertex_binding_descriptions(&vertex_input_binding_descriptions);
-		let vertex_input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo {
-			topology: vk::PrimitiveTopology::TRIANGLE_LIST,
-			..Default::default()
-		};
-		let vertex_input_buffer_info = vk::BufferCreateInfo {
-			size: 100 * mem::size_of::<Vertex>() as u64,
-			usage: vk::BufferUsageFlags::VERTEX_BUFFER,
-			sharing_mode: vk::SharingMode::EXCLUSIVE,
-			..Default::default()
-		};
+	pub fn new(base: BaseRef) -> Self {
+		let builder = Self::builder().load_op(vk::AttachmentLoadOp::CLEAR);
+		Self::with_builder(base, &builder, 100)
+	}
 
-		let vertex_input_buffer = device
-			.create_buffer(&vertex_input_buffer_info, None)
-			.unwrap();
+	// anti-aliased with up to `samples` samples per pixel, unless `load_op`
+	// clears the output such layers are recorded inline instead of in
+	// parallel, see `Multisample`
+	pub fn with_samples(
+		base: BaseRef,
+		load_op: vk::AttachmentLoadOp,
+		capacity: usize,
+		samples: vk::SampleCountFlags,
+	) -> Self {
+		let builder = Self::builder()
+			.load_op(load_op)
+			.samples(samples);
+		Self::with_builder(base, &builder, capacity)
+	}
 
-		let vertex_input_buffer_memory_req = device
-			.get_buffer_memory_requirements(vertex_input_buffer);
+	// e.g. Alpha and Load to stack over the layers below
+	pub fn with_modes(base: BaseRef, blend: BlendMode, load: LoadOp, capacity: usize) -> Self {
+		let builder = Self::builder()
+			.blend_mode(blend)
+			.load(load);
+		Self::with_builder(base, &builder, capacity)
+	}
 
-		let vertex_input_buffer_memory_index = find_memorytype_index(
-			&vertex_input_buffer_memory_req,
-			&base.device_memory_properties,
-			vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+	// the pipeline `Triangles` draws with, to change e.g. its blending
+	// before `with_builder`
+	pub fn builder() -> PipelineBuilder {
+		PipelineBuilder::new(
+			include_bytes!("../../assets/spvs/triangle_vert.spv"),
+			include_bytes!("../../assets/spvs/triangle_frag.spv"),
 		)
-		.expect("Unable to find suitable memorytype for the vertex buffer.");
-
-		let vertex_buffer_allocate_info = vk::MemoryAllocateInfo {
-			allocation_size: vertex_input_buffer_memory_req.size,
-			memory_type_index: vertex_input_buffer_memory_index,
-			..Default::default()
-		};
+			.vertex::<Vertex>()
+			.topologies(&TOPOLOGIES)
+			.push_constants(
+				vk::ShaderStageFlags::VERTEX,
+				mem::size_of::<PushConstants>() as u32,
+			)
+	}
 
-		let vertex_input_buffer_memory = device
-			.allocate_memory(&vertex_buffer_allocate_info, None)
-			.unwrap();
-
-		device
-			.bind_buffer_memory(vertex_input_buffer, vertex_input_buffer_memory, 0)
-			.unwrap();
-		let viewports = vec![vk::Viewport {
-			x: 0.0,
-			y: 0.0,
-			width: base.render_resolution.width as f32,
-			height: base.render_resolution.height as f32,
-			min_depth: 0.0,
-			max_depth: 1.0,
-		}];
-		let scissors = [base.render_resolution.into()];
-		let viewport_state_info = vk::PipelineViewportStateCreateInfo::default()
-			.scissors(&scissors)
-			.viewports(&viewports);
-
-		let rasterization_info = vk::PipelineRasterizationStateCreateInfo {
-			front_face: vk::FrontFace::COUNTER_CLOCKWISE,
-			line_width: 1.0,
-			polygon_mode: vk::PolygonMode::FILL,
-			..Default::default()
-		};
-		let multisample_state_info = vk::PipelineMultisampleStateCreateInfo {
-			rasterization_samples: vk::SampleCountFlags::TYPE_1,
-			..Default::default()
+	// the vertex buffer starts with room for `capacity` vertices and grows to
+	// fit `vertices`
+	pub fn with_builder(base: BaseRef, builder: &PipelineBuilder, capacity: usize) -> Self {
+		let (pipeline, vertex_buffer, index_buffer) = {
+			let base = base.read().unwrap();
+			let pipeline = builder.build(&base);
+			let vertex_buffer = HostBuffer::new(&base, vk::BufferUsageFlags::VERTEX_BUFFER, capacity);
+			let index_buffer = HostBuffer::new(&base, vk::BufferUsageFlags::INDEX_BUFFER, 0);
+			(pipeline, vertex_buffer, index_buffer)
 		};
-		let color_blend_attachment_states = [vk::PipelineColorBlendAttachmentState {
-			blend_enable: 0,
-			src_color_blend_factor: vk::BlendFactor::SRC_COLOR,
-			dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_DST_COLOR,
-			color_blend_op: vk::BlendOp::ADD,
-			src_alpha_blend_factor: vk::BlendFactor::ZERO,
-			dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-			alpha_blend_op: vk::BlendOp::ADD,
-			color_write_mask: vk::ColorComponentFlags::RGBA,
-		}];
-		let color_blend_state = vk::PipelineColorBlendStateCreateInfo::default()
-			.logic_op(vk::LogicOp::CLEAR)
-			.attachments(&color_blend_attachment_states);
-
-		let dynamic_state = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
-		let dynamic_state_info =
-			vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_state);
-
-		let graphic_pipeline_info = vk::GraphicsPipelineCreateInfo::default()
-			.stages(&shader_stage_create_infos)
-			.vertex_input_state(&vertex_input_state_info)
-			.input_assembly_state(&vertex_input_assembly_state_info)
-			.viewport_state(&viewport_state_info)
-			.rasterization_state(&rasterization_info)
-			.multisample_state(&multisample_state_info)
-			.color_blend_state(&color_blend_state)
-			.dynamic_state(&dynamic_state_info)
-			.layout(pipeline_layout)
-			.render_pass(renderpass);
-
-		let graphics_pipelines = device
-			.create_graphics_pipelines(vk::PipelineCache::null(), &[graphic_pipeline_info], None)
-			.expect("Unable to create graphics pipeline");
-
 		Self {
 			vertices: Vec::new(),
-			base: base_clone,
-			graphics_pipelines,
-			pipeline_layout,
-			vertex_shader_module,
-			vertex_input_buffer,
-			vertex_input_buffer_memory,
-			vertex_input_buffer_memory_req,
-			fragment_shader_module,
-			output_image_views: Vec::new(),
-			framebuffers: Vec::new(),
-			renderpass,
-			viewports,
+			indices: Indices::default(),
+			batches: Vec::new(),
+			camera: Camera2D::default(),
+			coordinates: Coordinates::default(),
+			base,
+			pipeline,
+			vertex_buffer,
+			index_buffer,
+			index_type: vk::IndexType::UINT32,
+			draws: Vec::new(),
+			transform: Camera2D::default().matrix([1.0, 1.0]),
 		}
-	}}
-}
+	}
 
-impl Drop for Triangles {
-	fn drop(&mut self) { unsafe {
-		let base = self.base.read().unwrap();
-		let device = &base.device;
-		device.device_wait_idle().unwrap();
-		for pipeline in mem::take(&mut self.graphics_pipelines) {
-			device.destroy_pipeline(pipeline, None);
-		}
-		device.destroy_pipeline_layout(self.pipeline_layout, None);
-		device
-			.destroy_shader_module(self.vertex_shader_module, None);
-		device
-			.destroy_shader_module(self.fragment_shader_module, None);
-		for &image_view in self.output_image_views.iter() {
-			device.destroy_image_view(image_view, None);
-		}
-		for &framebuffer in self.framebuffers.iter() {
-			device.destroy_framebuffer(framebuffer, None);
-		}
-		device.destroy_render_pass(self.renderpass, None);
-		device.free_memory(self.vertex_input_buffer_memory, None);
-		device.destroy_buffer(self.vertex_input_buffer, None);
-	}}
+	// of the output in pixels
+	pub fn extent(&self) -> [f32; 2] {
+		let extent = self.pipeline.extent();
+		[extent.width as f32, extent.height as f32]
+	}
+
+	// from normalized device coordinates of the output, e.g. pointer
+	// positions of input events
+	pub fn to_world(&self, ndc: [f32; 2]) -> [f32; 2] {
+		self.camera.to_world_in(self.coordinates, ndc, self.extent())
+	}
+
+	pub fn to_ndc(&self, world: [f32; 2]) -> [f32; 2] {
+		self.camera.to_ndc_in(self.coordinates, world, self.extent())
+	}
+
+	// `position` in pixels of the window, which is the size of the output
+	pub fn window_to_world(&self, position: [f32; 2]) -> [f32; 2] {
+		let extent = self.extent();
+		let ndc = [
+			position[0] / extent[0].max(1.0) * 2.0 - 1.0,
+			position[1] / extent[1].max(1.0) * 2.0 - 1.0,
+		];
+		self.to_world(ndc)
+	}
 }
 
 impl Layer for Triangles {
-	fn set_output(&mut self, image: Vec<vk::Image>) { unsafe {
-		let base = self.base.read().unwrap();
-		let (framebuffers, image_views) = image.into_iter()
-			.map(|image| {
-				let create_view_info = vk::ImageViewCreateInfo::default()
-					.view_type(vk::ImageViewType::TYPE_2D)
-					.format(base.surface_format.format)
-					.components(vk::ComponentMapping {
-						r: vk::ComponentSwizzle::R,
-						g: vk::ComponentSwizzle::G,
-						b: vk::ComponentSwizzle::B,
-						a: vk::ComponentSwizzle::A,
-					})
-					.subresource_range(vk::ImageSubresourceRange {
-						aspect_mask: vk::ImageAspectFlags::COLOR,
-						base_mip_level: 0,
-						level_count: 1,
-						base_array_layer: 0,
-						layer_count: 1,
-					})
-					.image(image);
-				let image_view = base.device.create_image_view(&create_view_info, None).unwrap();
-				let framebuffer_attachments = [image_view];
-				let frame_buffer_create_info = vk::FramebufferCreateInfo::default()
-					.render_pass(self.renderpass)
-					.attachments(&framebuffer_attachments)
-					.width(base.render_resolution.width)
-					.height(base.render_resolution.height)
-					.layers(1);
-				let framebuffer = base.device
-					.create_framebuffer(&frame_buffer_create_info, None)
-					.unwrap();
-				(framebuffer, image_view)
-			}).unzip();
-		self.framebuffers = framebuffers;
-		self.output_image_views = image_views;
-	}}
+	fn set_output(&mut self, images: Vec<vk::Image>) {
+		self.pipeline.set_output(images);
+	}
 
-	fn render(&self, draw_command_buffer: vk::CommandBuffer, idx: usize) { unsafe {
-		let base = self.base.read().unwrap();
-		let device = &base.device;
+	fn resize(&mut self, extent: vk::Extent2D) {
+		self.pipeline.resize(extent);
+	}
 
-		let clear_values = [
-			vk::ClearValue {
-				color: vk::ClearColorValue {
-					float32: [0.0, 0.0, 0.0, 0.0],
-				},
-			},
-		];
+	fn target(&self, idx: usize) -> Option<RenderTarget> {
+		self.pipeline.target(idx)
+	}
 
-		let vert_ptr = device.map_memory(
-			self.vertex_input_buffer_memory,
-			0,
-			self.vertex_input_buffer_memory_req.size,
-			vk::MemoryMapFlags::empty(),
-		)
-		.unwrap();
+	fn prepare(&mut self, _idx: usize) -> bool {
+		let transform = self.camera.matrix_in(self.coordinates, self.extent());
+		let buffers = [self.vertex_buffer.buffer(), self.index_buffer.buffer()];
+		let vertex_count = self.vertex_buffer.upload(&self.vertices);
+		self.index_buffer.upload(self.indices.as_bytes());
+		let index_type = self.indices.index_type();
+		let draws = draws(&self.batches, vertex_count, self.indices.len() as u32);
+		let changed = transform != self.transform
+			|| index_type != self.index_type
+			|| draws != self.draws
+			|| buffers != [self.vertex_buffer.buffer(), self.index_buffer.buffer()];
+		self.transform = transform;
+		self.index_type = index_type;
+		self.draws = draws;
+		changed
+	}
 
-		let mut vert_align = Align::new(
-			vert_ptr,
-			mem::align_of::<Vertex>() as u64,
-			self.vertex_input_buffer_memory_req.size,
-		);
-		vert_align.copy_from_slice(&self.vertices);
-		device.unmap_memory(self.vertex_input_buffer_memory);
-		let render_pass_begin_info = vk::RenderPassBeginInfo::default()
-			.render_pass(self.renderpass)
-			.framebuffer(self.framebuffers[idx])
-			.render_area(base.render_resolution.into())
-			.clear_values(&clear_values);
-		device.cmd_begin_render_pass(
-			draw_command_buffer,
-			&render_pass_begin_info,
-			vk::SubpassContents::INLINE,
-		);
-		device.cmd_bind_pipeline(
-			draw_command_buffer,
-			vk::PipelineBindPoint::GRAPHICS,
-			self.graphics_pipelines[0],
-		);
-		device.cmd_set_viewport(draw_command_buffer, 0, &self.viewports);
-        let scissors = [base.render_resolution.into()];
-		device.cmd_set_scissor(draw_command_buffer, 0, &scissors);
+	fn render(&self, draw_command_buffer: vk::CommandBuffer, idx: usize) { unsafe {
+		let base = self.base.read().unwrap();
+		let device = &base.device;
+		self.pipeline.begin(draw_command_buffer, idx);
 		device.cmd_bind_vertex_buffers(
 			draw_command_buffer,
 			0,
-			&[self.vertex_input_buffer],
+			&[self.vertex_buffer.buffer()],
 			&[0],
 		);
-		device.cmd_draw(draw_command_buffer, 6, 1, 0, 0);
-		device.cmd_end_render_pass(draw_command_buffer);
+		device.cmd_bind_index_buffer(
+			draw_command_buffer,
+			self.index_buffer.buffer(),
+			0,
+			self.index_type,
+		);
+		let mut topology = vk::PrimitiveTopology::TRIANGLE_LIST;
+		for batch in self.draws.iter() {
+			if batch.count == 0 {
+				continue;
+			}
+			if batch.topology != topology {
+				self.pipeline.bind_topology(draw_command_buffer, batch.topology);
+				topology = batch.topology;
+			}
+			let push_constants = PushConstants {
+				transform: self.transform,
+				point_size: batch.point_size,
+			};
+			device.cmd_push_constants(
+				draw_command_buffer,
+				self.pipeline.layout(),
+				vk::ShaderStageFlags::VERTEX,
+				0,
+				std::slice::from_raw_parts(
+					&push_constants as *const PushConstants as *const u8,
+					mem::size_of::<PushConstants>(),
+				),
+			);
+			if batch.indexed {
+				device.cmd_draw_indexed(draw_command_buffer, batch.count, 1, batch.first, 0, 0);
+			} else {
+				device.cmd_draw(draw_command_buffer, batch.count, 1, batch.first, 0);
+			}
+		}
+		self.pipeline.end(draw_command_buffer, idx);
 	}}
 }
+
+#[cfg(test)]
+mod tests {
+	use ash::vk;
+
+	use super::{Batch, draws};
+
+	const LIST: vk::PrimitiveTopology = vk::PrimitiveTopology::TRIANGLE_LIST;
+
+	fn ranges(draws: &[Batch]) -> Vec<(bool, u32, u32)> {
+		draws.iter().map(|batch| (batch.indexed, batch.first, batch.count)).collect()
+	}
+
+	#[test]
+	fn default_batch() {
+		assert_eq!(ranges(&draws(&[], 6, 0)), [(false, 0, 6)]);
+		// indices take over once there are any
+		assert_eq!(ranges(&draws(&[], 6, 9)), [(true, 0, 9)]);
+	}
+
+	#[test]
+	fn batches_are_clamped() {
+		let batches = [
+			Batch::vertices(LIST, 3..9),
+			Batch::vertices(LIST, 10..12),
+			Batch::indices(LIST, 0..3),
+			Batch::indices(LIST, 2..8),
+		];
+		assert_eq!(
+			ranges(&draws(&batches, 6, 4)),
+			[(false, 3, 3), (false, 6, 0), (true, 0, 3), (true, 2, 2)],
+		);
+	}
+}
@@ -1,3 +1,13 @@
+pub mod animation;
+pub mod app;
 pub mod base;
+pub mod camera;
 pub mod compositor;
+pub mod effect;
+pub mod graph;
+pub mod input;
 pub mod layer;
+pub mod profiler;
+pub mod recorder;
+pub mod scene;
+pub mod tracker;
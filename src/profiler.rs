@@ -0,0 +1,199 @@
+use ash::vk;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::base::Base;
+
+// frames kept in `FrameStats::history`
+pub const HISTORY: usize = 120;
+
+#[derive(Clone, Debug, Default)]
+pub struct FrameStats {
+	pub fps: f32,
+	pub cpu_ms: f32,
+	// none without timestamp support
+	pub gpu_ms: Option<f32>,
+	// cpu frame times in ms, newest last
+	pub history: VecDeque<f32>,
+	// gpu ms per layer of the compositor, with the layer name
+	pub layers: Vec<(String, f32)>,
+	// bytes of the layer caches and transient images, not of the buffers and
+	// textures the layers own
+	pub memory: u64,
+}
+
+// timestamp queries around graph passes, results are read back once the
+// frame's fence has been waited
+pub struct GpuTimer {
+	device: ash::Device,
+	query_pool: vk::QueryPool,
+	capacity: u32,
+	// nanoseconds per tick
+	period: f32,
+	// (scope, first query) of every timed range, none for the whole frame
+	ranges: Vec<(Option<usize>, u32)>,
+}
+
+impl GpuTimer {
+	pub fn new(base: &Base, capacity: u32) -> Option<Self> { unsafe {
+		let valid_bits = base.instance
+			.get_physical_device_queue_family_properties(base.pdevice)
+			[base.queue_family_index as usize]
+			.timestamp_valid_bits;
+		if valid_bits == 0 {
+			return None;
+		}
+		let period = base.instance
+			.get_physical_device_properties(base.pdevice)
+			.limits
+			.timestamp_period;
+		let create_info = vk::QueryPoolCreateInfo::default()
+			.query_type(vk::QueryType::TIMESTAMP)
+			.query_count(capacity * 2);
+		let query_pool = base.device.create_query_pool(&create_info, None).unwrap();
+		Some(Self {
+			device: base.device.clone(),
+			query_pool,
+			capacity,
+			period,
+			ranges: Vec::new(),
+		})
+	}}
+
+	// must be recorded before any range of the frame
+	pub fn reset(&mut self, command_buffer: vk::CommandBuffer) { unsafe {
+		self.ranges.clear();
+		self.device.cmd_reset_query_pool(command_buffer, self.query_pool, 0, self.capacity * 2);
+	}}
+
+	// none once the pool is full
+	pub fn begin(&mut self, command_buffer: vk::CommandBuffer, scope: Option<usize>) -> Option<u32> {
+		if self.ranges.len() as u32 >= self.capacity {
+			return None;
+		}
+		let query = self.ranges.len() as u32 * 2;
+		self.ranges.push((scope, query));
+		unsafe {
+			self.device.cmd_write_timestamp(
+				command_buffer,
+				vk::PipelineStageFlags::TOP_OF_PIPE,
+				self.query_pool,
+				query,
+			);
+		}
+		Some(query)
+	}
+
+	pub fn end(&mut self, command_buffer: vk::CommandBuffer, query: u32) { unsafe {
+		self.device.cmd_write_timestamp(
+			command_buffer,
+			vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+			self.query_pool,
+			query + 1,
+		);
+	}}
+
+	// ms per scope summed over its ranges, the submission must have completed
+	pub fn resolve(&self) -> Vec<(Option<usize>, f32)> {
+		if self.ranges.is_empty() {
+			return Vec::new();
+		}
+		let mut data = vec![0u64; self.ranges.len() * 2];
+		let available = unsafe {
+			self.device.get_query_pool_results(
+				self.query_pool,
+				0,
+				&mut data,
+				vk::QueryResultFlags::TYPE_64,
+			)
+		};
+		if available.is_err() {
+			return Vec::new();
+		}
+		let mut result: Vec<(Option<usize>, f32)> = Vec::new();
+		for &(scope, query) in self.ranges.iter() {
+			let ticks = data[query as usize + 1].saturating_sub(data[query as usize]);
+			let ms = ticks as f32 * self.period / 1_000_000.0;
+			match result.iter_mut().find(|(s, _)| *s == scope) {
+				Some((_, total)) => *total += ms,
+				None => result.push((scope, ms)),
+			}
+		}
+		result
+	}
+}
+
+impl Drop for GpuTimer {
+	fn drop(&mut self) { unsafe {
+		self.device.device_wait_idle().unwrap();
+		self.device.destroy_query_pool(self.query_pool, None);
+	}}
+}
+
+pub struct Profiler {
+	pub timer: Option<GpuTimer>,
+	stats: FrameStats,
+	cpu_start: Option<Instant>,
+	last_frame: Option<Instant>,
+}
+
+impl Profiler {
+	pub fn new(base: &Base) -> Self {
+		Self {
+			timer: GpuTimer::new(base, 256),
+			stats: FrameStats::default(),
+			cpu_start: None,
+			last_frame: None,
+		}
+	}
+
+	pub fn stats(&self) -> &FrameStats {
+		&self.stats
+	}
+
+	pub fn begin_cpu(&mut self) {
+		self.cpu_start = Some(Instant::now());
+	}
+
+	pub fn end_cpu(&mut self) {
+		let now = Instant::now();
+		if let Some(start) = self.cpu_start.take() {
+			self.stats.cpu_ms = (now - start).as_secs_f32() * 1000.0;
+		}
+		if let Some(last) = self.last_frame {
+			let ms = (now - last).as_secs_f32() * 1000.0;
+			if self.stats.history.len() == HISTORY {
+				self.stats.history.pop_front();
+			}
+			self.stats.history.push_back(ms);
+			let average = self.stats.history.iter().sum::<f32>() / self.stats.history.len() as f32;
+			self.stats.fps = if average > 0.0 { 1000.0 / average } else { 0.0 };
+		}
+		self.last_frame = Some(now);
+	}
+
+	// gpu times of the previous frame, `names` are the current layer names
+	pub fn resolve(&mut self, names: Vec<String>, memory: u64) {
+		self.stats.memory = memory;
+		let timer = match self.timer.as_ref() {
+			Some(timer) => timer,
+			None => return,
+		};
+		let times = timer.resolve();
+		self.stats.gpu_ms = times
+			.iter()
+			.find(|(scope, _)| scope.is_none())
+			.map(|&(_, ms)| ms);
+		self.stats.layers = names
+			.into_iter()
+			.enumerate()
+			.map(|(idx, name)| {
+				let ms = times
+					.iter()
+					.find(|(scope, _)| *scope == Some(idx))
+					.map_or(0.0, |&(_, ms)| ms);
+				(name, ms)
+			})
+			.collect();
+	}
+}
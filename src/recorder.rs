@@ -0,0 +1,138 @@
+use ash::vk;
+use std::thread;
+
+use crate::base::Base;
+use crate::layer::LayerRef;
+
+// a secondary command buffer and the pool it was allocated from
+#[derive(Clone, Copy, Default)]
+pub struct Secondary {
+	pool: vk::CommandPool,
+	pub command_buffer: vk::CommandBuffer,
+}
+
+impl Secondary {
+	// back to its pool, the device must be done with it
+	pub fn free(&mut self, device: &ash::Device) { unsafe {
+		if self.command_buffer != vk::CommandBuffer::null() {
+			device.free_command_buffers(self.pool, &[self.command_buffer]);
+		}
+		*self = Self::default();
+	}}
+}
+
+pub struct RecordJob<'a> {
+	pub layer: &'a LayerRef,
+	pub idx: usize,
+	// selects the command pool on first use, afterwards the job goes to the
+	// worker owning the pool `secondary` was allocated from
+	pub worker: usize,
+	// allocated on first use, re-recorded in place afterwards
+	pub secondary: &'a mut Secondary,
+}
+
+// records layers into secondary command buffers, one command pool and thread
+// per worker
+pub struct Recorder {
+	device: ash::Device,
+	pools: Vec<vk::CommandPool>,
+}
+
+impl Recorder {
+	pub fn new(base: &Base) -> Self {
+		let workers = thread::available_parallelism()
+			.map(|n| n.get())
+			.unwrap_or(1);
+		let pool_create_info = vk::CommandPoolCreateInfo::default()
+			.flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+			.queue_family_index(base.queue_family_index);
+		let pools = (0..workers)
+			.map(|_| unsafe {
+				base.device.create_command_pool(&pool_create_info, None).unwrap()
+			})
+			.collect();
+		Self {
+			device: base.device.clone(),
+			pools,
+		}
+	}
+
+	pub fn workers(&self) -> usize {
+		self.pools.len()
+	}
+
+	// the previous submission using the command buffers must have completed
+	pub fn record(&self, jobs: Vec<RecordJob>) {
+		let mut per_worker: Vec<Vec<RecordJob>> = self.pools.iter().map(|_| Vec::new()).collect();
+		for job in jobs.into_iter() {
+			let worker = self.pools
+				.iter()
+				.position(|&pool| pool == job.secondary.pool)
+				.unwrap_or(job.worker % self.pools.len());
+			per_worker[worker].push(job);
+		}
+		let busy = per_worker.iter().filter(|jobs| !jobs.is_empty()).count();
+		if busy <= 1 {
+			for (&pool, jobs) in self.pools.iter().zip(per_worker.into_iter()) {
+				for job in jobs.into_iter() {
+					unsafe { Self::record_job(&self.device, pool, job) };
+				}
+			}
+			return;
+		}
+		thread::scope(|scope| {
+			for (&pool, jobs) in self.pools.iter().zip(per_worker.into_iter()) {
+				if jobs.is_empty() {
+					continue;
+				}
+				let device = &self.device;
+				scope.spawn(move || {
+					for job in jobs.into_iter() {
+						unsafe { Self::record_job(device, pool, job) };
+					}
+				});
+			}
+		});
+	}
+
+	unsafe fn record_job(device: &ash::Device, pool: vk::CommandPool, job: RecordJob) {
+		let layer = job.layer.read().unwrap();
+		let target = layer
+			.target(job.idx)
+			.expect("only layers with a render target can be recorded");
+		let secondary = job.secondary;
+		if secondary.command_buffer == vk::CommandBuffer::null() {
+			let allocate_info = vk::CommandBufferAllocateInfo::default()
+				.command_buffer_count(1)
+				.command_pool(pool)
+				.level(vk::CommandBufferLevel::SECONDARY);
+			secondary.pool = pool;
+			secondary.command_buffer = device.allocate_command_buffers(&allocate_info).unwrap()[0];
+		}
+		let command_buffer = secondary.command_buffer;
+		let inheritance_info = vk::CommandBufferInheritanceInfo::default()
+			.render_pass(target.render_pass)
+			.subpass(0)
+			.framebuffer(target.framebuffer);
+		let begin_info = vk::CommandBufferBeginInfo::default()
+			.flags(vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE)
+			.inheritance_info(&inheritance_info);
+		device
+			.begin_command_buffer(command_buffer, &begin_info)
+			.expect("Begin secondary commandbuffer");
+		layer.render(command_buffer, job.idx);
+		device
+			.end_command_buffer(command_buffer)
+			.expect("End secondary commandbuffer");
+	}
+}
+
+impl Drop for Recorder {
+	fn drop(&mut self) { unsafe {
+		self.device.device_wait_idle().unwrap();
+		// frees every command buffer recorded from them
+		for &pool in self.pools.iter() {
+			self.device.destroy_command_pool(pool, None);
+		}
+	}}
+}
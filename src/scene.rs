@@ -0,0 +1,355 @@
+use ash::vk;
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use crate::base::BaseRef;
+use crate::camera::Coordinates;
+use crate::compositor::LayerCompositor;
+use crate::layer::LayerRef;
+use crate::layer::clear::Clear;
+use crate::layer::group::LayerGroup;
+use crate::layer::image_viewer::ImageViewer;
+use crate::layer::mesh::{Mesh, MeshError};
+use crate::layer::monotext::Monotext;
+use crate::layer::pipeline::{BlendMode, LoadOp};
+use crate::layer::monotext::label_stack::line::Line;
+use crate::layer::shader::ShaderLayer;
+use crate::layer::triangles::{Triangles, Vertex};
+
+// a layer stack described in RON, bottom layer first:
+//
+// Scene(layers: [
+//     (kind: Clear(color: (0.1, 0.1, 0.1, 1.0))),
+//     (kind: Image(path: "assets/images/map.png", zoom: 2.0), cached: true),
+//     (
+//         kind: Image(path: "assets/images/map.png", rect: Some(((8.0, 8.0), (72.0, 72.0)))),
+//         coordinates: Pixels(scale: 1.0),
+//     ),
+//     (
+//         kind: Text(font: "assets/images/font.png", lines: [
+//             (text: "load 42%", color: (1.0, 1.0, 0.0, 1.0)),
+//         ]),
+//         placement: Some((offset: (0.5, -0.5), scale: (0.5, 0.5))),
+//     ),
+// ])
+//
+// relative paths are resolved against the directory of the scene file
+#[derive(Clone, Debug, Deserialize)]
+pub struct Scene {
+	pub layers: Vec<LayerDesc>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LayerDesc {
+	pub kind: LayerKind,
+	// rendered once and composited from its cache until marked updated
+	#[serde(default)]
+	pub cached: bool,
+	// wraps the layer in a `LayerGroup` with this transform
+	#[serde(default)]
+	pub placement: Option<Placement>,
+	// of the positions in triangles, image rects and text
+	#[serde(default)]
+	pub coordinates: Coordinates,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum LayerKind {
+	Clear {
+		#[serde(default)]
+		color: [f32; 4],
+	},
+	Triangles {
+		vertices: Vec<VertexDesc>,
+		// clear the output before drawing instead of drawing over it
+		#[serde(default)]
+		clear: bool,
+		// multisampled anti-aliasing, 0 or 1 disables it
+		#[serde(default)]
+		samples: u32,
+		#[serde(default)]
+		blend: BlendMode,
+	},
+	Image {
+		path: String,
+		#[serde(default)]
+		pan: [f32; 2],
+		#[serde(default = "one")]
+		zoom: f32,
+		#[serde(default)]
+		blend: BlendMode,
+		// top left and bottom right corners, see `ImageViewer::rect`
+		#[serde(default)]
+		rect: Option<[[f32; 2]; 2]>,
+	},
+	Text {
+		font: String,
+		#[serde(default = "one")]
+		scale: f32,
+		lines: Vec<TextLine>,
+		// of the top left corner, see `Monotext::position`
+		#[serde(default)]
+		position: Option<[f32; 2]>,
+	},
+	// compiled fragment shader, see src/shader/plasma.frag
+	Shader {
+		path: String,
+		#[serde(default)]
+		params: [[f32; 4]; 4],
+		#[serde(default = "yes")]
+		animated: bool,
+	},
+	// .obj, .gltf or .glb, see `mesh::load::load`
+	Mesh {
+		path: String,
+		#[serde(default)]
+		clear: bool,
+		#[serde(default)]
+		eye: Option<[f32; 3]>,
+	},
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct VertexDesc {
+	pub pos: [f32; 2],
+	pub color: [f32; 4],
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TextLine {
+	pub text: String,
+	#[serde(default = "white")]
+	pub color: [f32; 4],
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Placement {
+	#[serde(default)]
+	pub offset: [f32; 2],
+	#[serde(default = "unit_scale")]
+	pub scale: [f32; 2],
+	#[serde(default)]
+	pub rotation: f32,
+	#[serde(default = "one")]
+	pub opacity: f32,
+}
+
+fn one() -> f32 {
+	1.0
+}
+
+fn yes() -> bool {
+	true
+}
+
+fn white() -> [f32; 4] {
+	[1.0; 4]
+}
+
+fn unit_scale() -> [f32; 2] {
+	[1.0; 2]
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+	Io(PathBuf, std::io::Error),
+	Parse(ron::error::SpannedError),
+	Image(PathBuf, image::ImageError),
+	Mesh(MeshError),
+}
+
+impl fmt::Display for SceneError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			SceneError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+			SceneError::Parse(e) => write!(f, "scene: {}", e),
+			SceneError::Image(path, e) => write!(f, "{}: {}", path.display(), e),
+			SceneError::Mesh(e) => write!(f, "{}", e),
+		}
+	}
+}
+
+impl std::error::Error for SceneError {}
+
+impl Scene {
+	pub fn parse(source: &str) -> Result<Self, SceneError> {
+		ron::from_str(source).map_err(SceneError::Parse)
+	}
+
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+		let path = path.as_ref();
+		let source = std::fs::read_to_string(path)
+			.map_err(|e| SceneError::Io(path.to_path_buf(), e))?;
+		Self::parse(&source)
+	}
+
+	// replace the layers of `compositor`. every layer is created before the
+	// old ones are dropped, so a scene that fails to build leaves the
+	// compositor untouched
+	pub fn build(
+		&self,
+		base: &BaseRef,
+		compositor: &mut LayerCompositor,
+		dir: impl AsRef<Path>,
+	) -> Result<(), SceneError> {
+		let mut layers = Vec::with_capacity(self.layers.len());
+		for desc in self.layers.iter() {
+			// a placed layer is cached inside its group
+			let cached = desc.cached && desc.placement.is_none();
+			layers.push((desc.create(base, dir.as_ref())?, cached));
+		}
+		compositor.clear();
+		for (layer, cached) in layers {
+			if cached {
+				compositor.new_cached_layer(layer);
+			} else {
+				compositor.new_layer(layer);
+			}
+		}
+		Ok(())
+	}
+}
+
+impl LayerDesc {
+	fn create(&self, base: &BaseRef, dir: &Path) -> Result<LayerRef, SceneError> {
+		let layer: LayerRef = match &self.kind {
+			LayerKind::Clear { color } => {
+				let mut clear = Clear::new(base.clone());
+				clear.color = *color;
+				Arc::new(RwLock::new(clear))
+			}
+			LayerKind::Triangles { vertices, clear, samples, blend } => {
+				let load_op = if *clear {
+					vk::AttachmentLoadOp::CLEAR
+				} else {
+					vk::AttachmentLoadOp::LOAD
+				};
+				let builder = Triangles::builder()
+					.load_op(load_op)
+					.blend_mode(*blend)
+					.samples(vk::SampleCountFlags::from_raw((*samples).max(1)));
+				let mut triangles = Triangles::with_builder(
+					base.clone(),
+					&builder,
+					vertices.len().max(3),
+				);
+				triangles.vertices = vertices
+					.iter()
+					.map(|v| Vertex {
+						pos: [v.pos[0], v.pos[1], 0.0, 1.0],
+						color: v.color,
+					})
+					.collect();
+				triangles.coordinates = self.coordinates;
+				Arc::new(RwLock::new(triangles))
+			}
+			LayerKind::Image { path, pan, zoom, blend, rect } => {
+				let path = dir.join(path);
+				let image = image::open(&path)
+					.map_err(|e| SceneError::Image(path, e))?
+					.into_rgba8();
+				let mut viewer = ImageViewer::with_modes(base.clone(), image, *blend, LoadOp::Load);
+				viewer.pan = *pan;
+				viewer.zoom = *zoom;
+				viewer.coordinates = self.coordinates;
+				viewer.rect = *rect;
+				Arc::new(RwLock::new(viewer))
+			}
+			LayerKind::Text { font, scale, lines, position } => {
+				let path = dir.join(font);
+				let font = image::open(&path)
+					.map_err(|e| SceneError::Image(path, e))?
+					.into_luma8();
+				let mut text = Monotext::new(base.clone(), font);
+				text.label_stack.set_scaler(*scale);
+				text.coordinates = self.coordinates;
+				text.position = *position;
+				for (idx, line) in lines.iter().enumerate() {
+					text.label_stack.add_text(&idx.to_string(), Line::new_colored(
+						line.text.bytes().collect(),
+						line.color,
+					));
+				}
+				Arc::new(RwLock::new(text))
+			}
+			LayerKind::Shader { path, params, animated } => {
+				let path = dir.join(path);
+				let spirv = std::fs::File::open(&path)
+					.and_then(|mut file| ash::util::read_spv(&mut file))
+					.map_err(|e| SceneError::Io(path, e))?;
+				let mut shader = ShaderLayer::new(base.clone(), spirv, 0);
+				shader.params = *params;
+				shader.animated = *animated;
+				Arc::new(RwLock::new(shader))
+			}
+			LayerKind::Mesh { path, clear, eye } => {
+				let load_op = if *clear {
+					vk::AttachmentLoadOp::CLEAR
+				} else {
+					vk::AttachmentLoadOp::LOAD
+				};
+				let data = crate::layer::mesh::load::load(dir.join(path))
+					.map_err(SceneError::Mesh)?;
+				let mut mesh = Mesh::with_options(base.clone(), load_op, vk::SampleCountFlags::TYPE_1);
+				mesh.set_data(data);
+				if let Some(eye) = eye {
+					mesh.camera.eye = *eye;
+				}
+				Arc::new(RwLock::new(mesh))
+			}
+		};
+		let placement = match &self.placement {
+			Some(placement) => placement,
+			None => return Ok(layer),
+		};
+		let mut group = LayerGroup::new(base.clone());
+		group.offset = placement.offset;
+		group.scale = placement.scale;
+		group.rotation = placement.rotation;
+		group.opacity = placement.opacity;
+		if self.cached {
+			group.new_cached_layer(layer);
+		} else {
+			group.new_layer(layer);
+		}
+		Ok(Arc::new(RwLock::new(group)))
+	}
+}
+
+// polls the modification time of a scene file
+pub struct SceneWatcher {
+	path: PathBuf,
+	modified: Option<SystemTime>,
+}
+
+impl SceneWatcher {
+	pub fn new(path: impl Into<PathBuf>) -> Self {
+		let path = path.into();
+		let modified = modified(&path);
+		Self {
+			path,
+			modified,
+		}
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	// true once per change of the file
+	pub fn changed(&mut self) -> bool {
+		let modified = modified(&self.path);
+		if modified.is_none() || modified == self.modified {
+			return false;
+		}
+		self.modified = modified;
+		true
+	}
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+	std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,29 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (binding = 0) uniform sampler2D samplerColor;
+
+layout (push_constant) uniform Params {
+	vec4 params[5];
+	vec2 resolution;
+	vec2 direction;
+} params;
+
+layout (location = 0) in vec2 o_uv;
+layout (location = 0) out vec4 uFragColor;
+
+// one direction of a separable gaussian, params[0].x is sigma in pixels
+void main() {
+	float sigma = max(params.params[0].x, 0.001);
+	int radius = min(int(ceil(sigma * 3.0)), 64);
+	vec2 texel = params.direction / params.resolution;
+	vec4 sum = vec4(0.0);
+	float total = 0.0;
+	for (int i = -radius; i <= radius; i++) {
+		float weight = exp(-float(i * i) / (2.0 * sigma * sigma));
+		sum += texture(samplerColor, o_uv + texel * float(i)) * weight;
+		total += weight;
+	}
+	uFragColor = sum / total;
+}
@@ -0,0 +1,25 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (binding = 0) uniform sampler2D samplerColor;
+
+layout (push_constant) uniform Params {
+	vec4 params[5];
+	vec2 resolution;
+	vec2 direction;
+} params;
+
+layout (location = 0) in vec2 o_uv;
+layout (location = 0) out vec4 uFragColor;
+
+// params[0..4] are the matrix columns, params[4] the offset
+void main() {
+	mat4 matrix = mat4(
+		params.params[0],
+		params.params[1],
+		params.params[2],
+		params.params[3]
+	);
+	uFragColor = clamp(matrix * texture(samplerColor, o_uv) + params.params[4], 0.0, 1.0);
+}
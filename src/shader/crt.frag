@@ -0,0 +1,29 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (binding = 0) uniform sampler2D samplerColor;
+
+layout (push_constant) uniform Params {
+	vec4 params[5];
+	vec2 resolution;
+	vec2 direction;
+} params;
+
+layout (location = 0) in vec2 o_uv;
+layout (location = 0) out vec4 uFragColor;
+
+// params[0] is (scanline intensity, curvature)
+void main() {
+	vec2 centered = o_uv * 2.0 - 1.0;
+	centered *= 1.0 + params.params[0].y * dot(centered.yx, centered.yx);
+	vec2 uv = centered * 0.5 + 0.5;
+	if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+		uFragColor = vec4(0.0, 0.0, 0.0, 1.0);
+		return;
+	}
+	vec4 color = texture(samplerColor, uv);
+	float scanline = 0.5 + 0.5 * cos(uv.y * params.resolution.y * 3.14159265);
+	color.rgb *= 1.0 - params.params[0].x * (1.0 - scanline);
+	uFragColor = color;
+}
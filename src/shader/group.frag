@@ -0,0 +1,17 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (binding = 0) uniform sampler2D samplerColor;
+
+layout (push_constant) uniform Params {
+	mat4 transform;
+	float opacity;
+} params;
+
+layout (location = 0) in vec2 o_uv;
+layout (location = 0) out vec4 uFragColor;
+
+void main() {
+	uFragColor = texture(samplerColor, o_uv) * params.opacity;
+}
@@ -0,0 +1,20 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (push_constant) uniform Params {
+	mat4 transform;
+	float opacity;
+} params;
+
+layout (location = 0) out vec2 o_uv;
+
+const vec2 corners[6] = vec2[](
+	vec2(0.0, 0.0), vec2(0.0, 1.0), vec2(1.0, 1.0),
+	vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0)
+);
+
+void main() {
+	o_uv = corners[gl_VertexIndex];
+	gl_Position = params.transform * vec4(o_uv * 2.0 - 1.0, 0.0, 1.0);
+}
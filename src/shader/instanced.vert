@@ -0,0 +1,31 @@
+#version 400
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+// base mesh
+layout (location = 0) in vec2 pos;
+layout (location = 1) in vec4 color;
+layout (location = 2) in vec2 uv;
+// per instance
+layout (location = 3) in vec2 offset;
+layout (location = 4) in vec2 scale;
+layout (location = 5) in float rotation;
+layout (location = 6) in vec4 instance_color;
+layout (location = 7) in vec2 uv_offset;
+
+// camera transform of the layer
+layout (push_constant) uniform Params {
+	mat4 transform;
+} p;
+
+layout (location = 0) out vec4 o_color;
+layout (location = 1) out vec2 o_uv;
+void main() {
+	float s = sin(rotation);
+	float c = cos(rotation);
+	vec2 scaled = pos * scale;
+	vec2 world = offset + vec2(c * scaled.x - s * scaled.y, s * scaled.x + c * scaled.y);
+	o_color = color * instance_color;
+	o_uv = uv + uv_offset;
+	gl_Position = p.transform * vec4(world, 0.0, 1.0);
+}
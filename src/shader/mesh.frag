@@ -0,0 +1,27 @@
+#version 400
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (std140, binding = 0) uniform Uniforms {
+	mat4 model;
+	mat4 view;
+	mat4 projection;
+	mat4 normal;
+	vec4 light_direction;
+	vec4 light_color;
+	vec4 ambient;
+	vec4 color;
+} u;
+layout (binding = 1) uniform sampler2D samplerColor;
+
+layout (location = 0) in vec3 o_normal;
+layout (location = 1) in vec2 o_uv;
+layout (location = 0) out vec4 uFragColor;
+
+void main() {
+	vec4 albedo = texture(samplerColor, o_uv) * u.color;
+	// the light shines along light_direction
+	float diffuse = max(dot(normalize(o_normal), -normalize(u.light_direction.xyz)), 0.0);
+	vec3 light = u.ambient.rgb + u.light_color.rgb * diffuse;
+	uFragColor = vec4(albedo.rgb * light, albedo.a);
+}
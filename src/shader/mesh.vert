@@ -0,0 +1,28 @@
+#version 400
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (location = 0) in vec3 pos;
+layout (location = 1) in vec3 normal;
+layout (location = 2) in vec2 uv;
+
+layout (std140, binding = 0) uniform Uniforms {
+	mat4 model;
+	mat4 view;
+	mat4 projection;
+	// inverse transpose of model
+	mat4 normal;
+	// w unused
+	vec4 light_direction;
+	vec4 light_color;
+	vec4 ambient;
+	vec4 color;
+} u;
+
+layout (location = 0) out vec3 o_normal;
+layout (location = 1) out vec2 o_uv;
+void main() {
+	o_normal = mat3(u.normal) * normal;
+	o_uv = uv;
+	gl_Position = u.projection * u.view * u.model * vec4(pos, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+// the resolved multisampled image of the layer
+layout (input_attachment_index = 0, binding = 0) uniform subpassInput resolved;
+
+layout (location = 0) out vec4 uFragColor;
+
+void main() {
+	uFragColor = subpassLoad(resolved);
+}
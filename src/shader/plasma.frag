@@ -0,0 +1,31 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+// the push constants every ShaderLayer shader receives
+layout (push_constant) uniform Params {
+	vec4 params[4];
+	vec2 resolution;
+	vec2 mouse;
+	float time;
+	float delta;
+	uint frame;
+} params;
+
+layout (location = 0) in vec2 o_uv;
+layout (location = 0) out vec4 uFragColor;
+
+// animated background, params[0] is (speed, scale)
+void main() {
+	float speed = params.params[0].x == 0.0 ? 1.0 : params.params[0].x;
+	float scale = params.params[0].y == 0.0 ? 8.0 : params.params[0].y;
+	vec2 p = (o_uv * 2.0 - 1.0) * scale;
+	p.x *= params.resolution.x / params.resolution.y;
+	float t = params.time * speed;
+	float v = sin(p.x + t)
+		+ sin(p.y + t * 0.7)
+		+ sin(p.x + p.y + t * 1.3)
+		+ sin(length(p - params.mouse * scale) - t * 2.0);
+	vec3 color = 0.5 + 0.5 * cos(v + vec3(0.0, 2.1, 4.2));
+	uFragColor = vec4(color, 1.0);
+}
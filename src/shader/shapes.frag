@@ -0,0 +1,51 @@
+#version 400
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (location = 0) in vec2 o_local;
+// half extents of the shape
+layout (location = 1) flat in vec2 o_size;
+// kind, corner radius or ring thickness, border width
+layout (location = 2) flat in vec4 o_params;
+layout (location = 3) flat in vec4 o_fill;
+layout (location = 4) flat in vec4 o_border;
+layout (location = 0) out vec4 uFragColor;
+
+// see ShapeKind in src/layer/shapes.rs
+const float RECT = 0.0;
+const float ELLIPSE = 1.0;
+const float RING = 2.0;
+
+float sd_rounded_rect(vec2 p, vec2 size, float radius) {
+	vec2 q = abs(p) - size + radius;
+	return length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+// approximate, exact for circles
+float sd_ellipse(vec2 p, vec2 size) {
+	float k0 = length(p / size);
+	float k1 = length(p / (size * size));
+	return k0 * (k0 - 1.0) / max(k1, 1e-6);
+}
+
+void main() {
+	float kind = o_params.x;
+	float d;
+	if (kind == RECT) {
+		d = sd_rounded_rect(o_local, o_size, min(o_params.y, min(o_size.x, o_size.y)));
+	} else if (kind == ELLIPSE) {
+		d = sd_ellipse(o_local, o_size);
+	} else {
+		float half_thickness = o_params.y / 2.0;
+		d = abs(sd_ellipse(o_local, o_size) + half_thickness) - half_thickness;
+	}
+	// about a pixel wide transition at the edges
+	float aa = max(fwidth(d), 1e-6);
+	float coverage = 1.0 - smoothstep(-aa / 2.0, aa / 2.0, d);
+	vec4 color = o_fill;
+	float border_width = o_params.z;
+	if (border_width > 0.0) {
+		color = mix(o_fill, o_border, smoothstep(-border_width - aa / 2.0, -border_width + aa / 2.0, d));
+	}
+	uFragColor = vec4(color.rgb, color.a * coverage);
+}
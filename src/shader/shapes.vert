@@ -0,0 +1,29 @@
+#version 400
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (location = 0) in vec2 pos;
+layout (location = 1) in vec2 local;
+layout (location = 2) in vec2 size;
+layout (location = 3) in vec4 params;
+layout (location = 4) in vec4 fill;
+layout (location = 5) in vec4 border;
+
+// camera transform of the layer
+layout (push_constant) uniform Params {
+	mat4 transform;
+} p;
+
+layout (location = 0) out vec2 o_local;
+layout (location = 1) flat out vec2 o_size;
+layout (location = 2) flat out vec4 o_params;
+layout (location = 3) flat out vec4 o_fill;
+layout (location = 4) flat out vec4 o_border;
+void main() {
+	o_local = local;
+	o_size = size;
+	o_params = params;
+	o_fill = fill;
+	o_border = border;
+	gl_Position = p.transform * vec4(pos, 0.0, 1.0);
+}
@@ -5,8 +5,15 @@
 layout (location = 0) in vec4 pos;
 layout (location = 1) in vec4 color;
 
+// camera transform of the layer, point size of the current batch
+layout (push_constant) uniform Params {
+	mat4 transform;
+	float point_size;
+} params;
+
 layout (location = 0) out vec4 o_color;
 void main() {
 	o_color = color;
-	gl_Position = pos;
+	gl_PointSize = params.point_size;
+	gl_Position = params.transform * pos;
 }
@@ -0,0 +1,26 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (binding = 0) uniform sampler2D samplerColor;
+
+layout (push_constant) uniform Params {
+	vec4 params[5];
+	vec2 resolution;
+	vec2 direction;
+} params;
+
+layout (location = 0) in vec2 o_uv;
+layout (location = 0) out vec4 uFragColor;
+
+// params[0] is (radius, softness, strength)
+void main() {
+	vec4 color = texture(samplerColor, o_uv);
+	vec2 centered = o_uv - 0.5;
+	centered.x *= params.resolution.x / params.resolution.y;
+	float radius = params.params[0].x;
+	float softness = max(params.params[0].y, 0.001);
+	float shade = smoothstep(radius, radius - softness, length(centered));
+	color.rgb *= mix(1.0, shade, params.params[0].z);
+	uFragColor = color;
+}
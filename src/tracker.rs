@@ -0,0 +1,242 @@
+use ash::vk;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageUsage {
+	TransferSrc,
+	TransferDst,
+	ColorAttachment,
+	Sampled,
+	Present,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageState {
+	pub layout: vk::ImageLayout,
+	pub access: vk::AccessFlags,
+	pub stage: vk::PipelineStageFlags,
+}
+
+impl Default for ImageState {
+	fn default() -> Self {
+		Self {
+			layout: vk::ImageLayout::UNDEFINED,
+			access: vk::AccessFlags::empty(),
+			stage: vk::PipelineStageFlags::TOP_OF_PIPE,
+		}
+	}
+}
+
+impl ImageUsage {
+	pub fn state(self) -> ImageState {
+		let (layout, access, stage) = match self {
+			Self::TransferSrc => (
+				vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+				vk::AccessFlags::TRANSFER_READ,
+				vk::PipelineStageFlags::TRANSFER,
+			),
+			Self::TransferDst => (
+				vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+				vk::AccessFlags::TRANSFER_WRITE,
+				vk::PipelineStageFlags::TRANSFER,
+			),
+			Self::ColorAttachment => (
+				vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+				vk::AccessFlags::COLOR_ATTACHMENT_READ
+					| vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+				vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+			),
+			Self::Sampled => (
+				vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+				vk::AccessFlags::SHADER_READ,
+				vk::PipelineStageFlags::FRAGMENT_SHADER,
+			),
+			Self::Present => (
+				vk::ImageLayout::PRESENT_SRC_KHR,
+				vk::AccessFlags::empty(),
+				vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+			),
+		};
+		ImageState { layout, access, stage }
+	}
+}
+
+fn is_write(access: vk::AccessFlags) -> bool {
+	access.intersects(
+		vk::AccessFlags::TRANSFER_WRITE
+			| vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+			| vk::AccessFlags::SHADER_WRITE
+			| vk::AccessFlags::HOST_WRITE
+			| vk::AccessFlags::MEMORY_WRITE,
+	)
+}
+
+// the state of an image in `old` after its use as `usage`, and what the
+// barrier before that use waits on, none if no barrier is needed
+fn transition(old: ImageState, usage: ImageUsage) -> (ImageState, Option<ImageState>) {
+	let new = usage.state();
+	if old.layout == new.layout && !is_write(old.access) && !is_write(new.access) {
+		// read after read: no barrier, but later writes wait for every reader
+		let state = ImageState {
+			layout: old.layout,
+			access: old.access | new.access,
+			stage: old.stage | new.stage,
+		};
+		return (state, None);
+	}
+	// only writes need to be made available
+	let src = ImageState {
+		access: if is_write(old.access) { old.access } else { vk::AccessFlags::empty() },
+		..old
+	};
+	(new, Some(src))
+}
+
+pub type ImageTrackerRef = Arc<Mutex<ImageTracker>>;
+
+// last known layout/access/stage of every image used in command buffers,
+// images never seen are treated as undefined
+#[derive(Default)]
+pub struct ImageTracker {
+	states: HashMap<vk::Image, ImageState>,
+}
+
+impl ImageTracker {
+	pub fn new_ref() -> ImageTrackerRef {
+		Arc::new(Mutex::new(Self::default()))
+	}
+
+	pub fn get(&self, image: vk::Image) -> ImageState {
+		self.states.get(&image).copied().unwrap_or_default()
+	}
+
+	// the image content is no longer needed, the next transition starts from
+	// UNDEFINED but still waits for earlier accesses
+	pub fn discard(&mut self, image: vk::Image) {
+		let mut state = self.get(image);
+		state.layout = vk::ImageLayout::UNDEFINED;
+		self.states.insert(image, state);
+	}
+
+	// must be called when an image is destroyed, handles can be reused
+	pub fn forget(&mut self, image: vk::Image) {
+		self.states.remove(&image);
+	}
+
+	// set state without recording a barrier, for transitions done elsewhere
+	// (render pass final layout, another queue submission)
+	pub fn assume(&mut self, image: vk::Image, state: ImageState) {
+		self.states.insert(image, state);
+	}
+
+	// record the barrier (if any) needed before `image` is used as `usage`
+	pub fn require(
+		&mut self,
+		device: &ash::Device,
+		command_buffer: vk::CommandBuffer,
+		image: vk::Image,
+		usage: ImageUsage,
+	) {
+		let (new, src) = transition(self.get(image), usage);
+		self.states.insert(image, new);
+		let src = match src {
+			Some(src) => src,
+			None => return,
+		};
+		let barrier = vk::ImageMemoryBarrier {
+			src_access_mask: src.access,
+			dst_access_mask: new.access,
+			old_layout: src.layout,
+			new_layout: new.layout,
+			src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+			dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+			image,
+			subresource_range: vk::ImageSubresourceRange {
+				aspect_mask: vk::ImageAspectFlags::COLOR,
+				level_count: 1,
+				layer_count: 1,
+				..Default::default()
+			},
+			..Default::default()
+		};
+		unsafe {
+			device.cmd_pipeline_barrier(
+				command_buffer,
+				src.stage,
+				new.stage,
+				vk::DependencyFlags::empty(),
+				&[],
+				&[],
+				&[barrier],
+			);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use ash::vk::{self, Handle};
+
+	use super::{transition, ImageState, ImageTracker, ImageUsage};
+
+	#[test]
+	fn read_after_read_needs_no_barrier() {
+		let sampled = ImageUsage::Sampled.state();
+		let (state, barrier) = transition(sampled, ImageUsage::Sampled);
+		assert_eq!(barrier, None);
+		assert_eq!(state, sampled);
+	}
+
+	#[test]
+	fn readers_are_remembered() {
+		// a reader in another stage, same layout and access
+		let vertex_read = ImageState {
+			stage: vk::PipelineStageFlags::VERTEX_SHADER,
+			..ImageUsage::Sampled.state()
+		};
+		let (state, barrier) = transition(vertex_read, ImageUsage::Sampled);
+		assert_eq!(barrier, None);
+		assert_eq!(
+			state.stage,
+			vk::PipelineStageFlags::VERTEX_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+		);
+		// the next write waits for both
+		let (_, barrier) = transition(state, ImageUsage::ColorAttachment);
+		assert_eq!(barrier.unwrap().stage, state.stage);
+	}
+
+	#[test]
+	fn layout_changes_wait_on_writes() {
+		let (state, barrier) = transition(ImageUsage::ColorAttachment.state(), ImageUsage::Sampled);
+		assert_eq!(state, ImageUsage::Sampled.state());
+		assert_eq!(barrier, Some(ImageUsage::ColorAttachment.state()));
+		// reads have nothing to make available
+		let (_, barrier) = transition(ImageUsage::Sampled.state(), ImageUsage::TransferDst);
+		let barrier = barrier.unwrap();
+		assert_eq!(barrier.layout, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+		assert_eq!(barrier.access, vk::AccessFlags::empty());
+		assert_eq!(barrier.stage, vk::PipelineStageFlags::FRAGMENT_SHADER);
+	}
+
+	#[test]
+	fn unknown_images_start_undefined() {
+		let (_, barrier) = transition(ImageState::default(), ImageUsage::ColorAttachment);
+		assert_eq!(barrier, Some(ImageState::default()));
+	}
+
+	#[test]
+	fn discard_keeps_the_previous_access() {
+		let image = vk::Image::from_raw(1);
+		let mut tracker = ImageTracker::default();
+		tracker.assume(image, ImageUsage::ColorAttachment.state());
+		tracker.discard(image);
+		let (_, barrier) = transition(tracker.get(image), ImageUsage::ColorAttachment);
+		assert_eq!(barrier, Some(ImageState {
+			layout: vk::ImageLayout::UNDEFINED,
+			..ImageUsage::ColorAttachment.state()
+		}));
+		tracker.forget(image);
+		assert_eq!(tracker.get(image), ImageState::default());
+	}
+}
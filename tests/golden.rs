@@ -0,0 +1,255 @@
+// renders layers offscreen and compares the frames against the PNGs in
+// tests/references. needs a vulkan driver, lavapipe works, and the shaders
+// compiled into assets/spvs, so the tests are ignored by default:
+//
+//     VKWH_BLESS=1 cargo test --test golden -- --ignored
+//
+// writes the current frames as new references, without VKWH_BLESS they are
+// compared. references are blessed on lavapipe, other drivers may differ. a
+// missing device fails the tests, VKWH_NO_DEVICE=skip skips them instead.
+// failures leave the frame and a diff image in target/golden
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use ash::vk;
+
+use vkwh::base::{Base, BaseRef};
+use vkwh::compositor::LayerCompositor;
+use vkwh::effect::Effect;
+use vkwh::layer::clear::Clear;
+use vkwh::layer::group::LayerGroup;
+use vkwh::layer::image_viewer::ImageViewer;
+use vkwh::layer::monotext::Monotext;
+use vkwh::layer::monotext::label_stack::line::Line;
+use vkwh::layer::shader::ShaderLayer;
+use vkwh::layer::triangles::{Triangles, Vertex};
+
+const SIZE: vk::Extent2D = vk::Extent2D { width: 128, height: 96 };
+// per channel, drivers differ in rasterization and filtering rounding
+const TOLERANCE: u8 = 2;
+
+// none without a vulkan device if VKWH_NO_DEVICE=skip
+fn render<F: FnOnce(&BaseRef, &mut LayerCompositor)>(setup: F) -> Option<image::RgbaImage> {
+	let base: BaseRef = match Base::try_new_headless(SIZE) {
+		Some(base) => Arc::new(RwLock::new(base)),
+		None if std::env::var("VKWH_NO_DEVICE").as_deref() == Ok("skip") => {
+			eprintln!("SKIPPED: no vulkan device found");
+			return None;
+		}
+		None => panic!("no vulkan device found, install lavapipe or set VKWH_NO_DEVICE=skip"),
+	};
+	let mut compositor = LayerCompositor::new(base.clone());
+	setup(&base, &mut compositor);
+	compositor.render();
+	Some(compositor.capture())
+}
+
+fn check(name: &str, frame: Option<image::RgbaImage>) {
+	let actual = match &frame {
+		Some(actual) => actual,
+		None => return,
+	};
+	let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	let reference_path = root.join("tests/references").join(format!("{}.png", name));
+	if std::env::var_os("VKWH_BLESS").is_some() {
+		std::fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+		actual.save(&reference_path).unwrap();
+		return;
+	}
+	let reference = match image::open(&reference_path) {
+		Ok(reference) => reference.into_rgba8(),
+		Err(e) => panic!(
+			"{}: {}, run with VKWH_BLESS=1 to create it",
+			reference_path.display(),
+			e,
+		),
+	};
+	assert_eq!(reference.dimensions(), actual.dimensions(), "{}: size differs", name);
+
+	let mut diff = image::RgbaImage::new(actual.width(), actual.height());
+	let mut mismatched = 0;
+	for (x, y, pixel) in actual.enumerate_pixels() {
+		let expected = reference.get_pixel(x, y);
+		let delta: Vec<u8> = (0..4).map(|c| pixel[c].abs_diff(expected[c])).collect();
+		if delta.iter().any(|&d| d > TOLERANCE) {
+			mismatched += 1;
+			diff.put_pixel(x, y, image::Rgba([delta[0], delta[1], delta[2], 255]));
+		} else {
+			diff.put_pixel(x, y, image::Rgba([0, 0, 0, 255]));
+		}
+	}
+	if mismatched == 0 {
+		return;
+	}
+	let out = root.join("target/golden");
+	std::fs::create_dir_all(&out).unwrap();
+	actual.save(out.join(format!("{}_actual.png", name))).unwrap();
+	diff.save(out.join(format!("{}_diff.png", name))).unwrap();
+	panic!(
+		"{}: {} pixels differ by more than {}, see {}",
+		name,
+		mismatched,
+		TOLERANCE,
+		out.display(),
+	);
+}
+
+fn clear(base: &BaseRef, color: [f32; 4]) -> Arc<RwLock<Clear>> {
+	let clear = Clear::new_ref(base.clone());
+	clear.write().unwrap().color = color;
+	clear
+}
+
+fn triangle(base: &BaseRef) -> Arc<RwLock<Triangles>> {
+	let triangles = Triangles::new_ref(base.clone());
+	triangles.write().unwrap().vertices = vec![
+		Vertex { pos: [-0.8, 0.8, 0.0, 1.0], color: [1.0, 0.0, 0.0, 1.0] },
+		Vertex { pos: [0.8, 0.8, 0.0, 1.0], color: [0.0, 1.0, 0.0, 1.0] },
+		Vertex { pos: [0.0, -0.8, 0.0, 1.0], color: [0.0, 0.0, 1.0, 1.0] },
+	];
+	triangles
+}
+
+fn gradient() -> image::RgbaImage {
+	image::RgbaImage::from_fn(64, 64, |x, y| {
+		image::Rgba([(x * 4) as u8, (y * 4) as u8, 128, 255])
+	})
+}
+
+// 16x32 cells like utils/font_texgen.py, each glyph draws the bits of its
+// code as horizontal bars so that every character looks different
+fn font() -> image::GrayImage {
+	image::GrayImage::from_fn(1024, 1024, |x, y| {
+		let code = (y / 32) * 64 + x / 16;
+		let bit = (y % 32) / 4;
+		let inside = x % 16 >= 2 && x % 16 < 14;
+		if inside && code & (1 << bit) != 0 {
+			image::Luma([255])
+		} else {
+			image::Luma([0])
+		}
+	})
+}
+
+#[test]
+#[ignore = "needs a vulkan driver and references"]
+fn clear_color() {
+	let frame = render(|base, compositor| {
+		compositor.new_layer(clear(base, [0.2, 0.4, 0.6, 1.0]));
+	});
+	check("clear_color", frame);
+}
+
+#[test]
+#[ignore = "needs a vulkan driver and references"]
+fn triangles() {
+	let frame = render(|base, compositor| {
+		compositor.new_layer(triangle(base));
+	});
+	check("triangles", frame);
+}
+
+#[test]
+#[ignore = "needs a vulkan driver and references"]
+fn image_viewer() {
+	let frame = render(|base, compositor| {
+		compositor.new_layer(ImageViewer::new_ref(base.clone(), gradient()));
+	});
+	check("image_viewer", frame);
+}
+
+#[test]
+#[ignore = "needs a vulkan driver and references"]
+fn image_viewer_zoomed() {
+	let frame = render(|base, compositor| {
+		let viewer = ImageViewer::new_ref(base.clone(), gradient());
+		{
+			let mut viewer = viewer.write().unwrap();
+			viewer.zoom = 2.0;
+			viewer.pan = [0.25, 0.25];
+		}
+		compositor.new_layer(viewer);
+	});
+	check("image_viewer_zoomed", frame);
+}
+
+#[test]
+#[ignore = "needs a vulkan driver and references"]
+fn monotext() {
+	let frame = render(|base, compositor| {
+		let text = Monotext::new_ref(base.clone(), font());
+		{
+			let mut text = text.write().unwrap();
+			text.label_stack.add_text("a", Line::new_colored(
+				b"vkwh".to_vec(),
+				[1.0, 1.0, 0.0, 1.0],
+			));
+			text.label_stack.add_text("b", Line::new_colored(
+				b"0123".to_vec(),
+				[0.0, 1.0, 1.0, 1.0],
+			));
+		}
+		compositor.new_layer(clear(base, [0.0, 0.0, 0.0, 1.0]));
+		compositor.new_layer(text);
+	});
+	check("monotext", frame);
+}
+
+#[test]
+#[ignore = "needs a vulkan driver and references"]
+fn cached_layer() {
+	let frame = render(|base, compositor| {
+		compositor.new_cached_layer(triangle(base));
+	});
+	check("cached_layer", frame);
+}
+
+#[test]
+#[ignore = "needs a vulkan driver and references"]
+fn group_transform() {
+	let frame = render(|base, compositor| {
+		let group = LayerGroup::new_ref(base.clone());
+		{
+			let mut group = group.write().unwrap();
+			group.new_layer(triangle(base));
+			group.offset = [0.4, 0.2];
+			group.scale = [0.5, 0.5];
+			group.rotation = 0.5;
+			group.opacity = 0.75;
+		}
+		compositor.new_layer(clear(base, [0.1, 0.1, 0.1, 1.0]));
+		compositor.new_layer(group);
+	});
+	check("group_transform", frame);
+}
+
+#[test]
+#[ignore = "needs a vulkan driver and references"]
+fn effects() {
+	let frame = render(|base, compositor| {
+		compositor.new_layer(ImageViewer::new_ref(base.clone(), gradient()));
+		compositor.push_effect(Effect::Vignette {
+			radius: 0.6,
+			softness: 0.3,
+			strength: 1.0,
+		});
+		compositor.push_effect(Effect::GaussianBlur { sigma: 2.0 });
+	});
+	check("effects", frame);
+}
+
+#[test]
+#[ignore = "needs a vulkan driver and references"]
+fn shader_layer() {
+	let frame = render(|base, compositor| {
+		// loaded at run time, the file is a build artifact
+		let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets/spvs/plasma_frag.spv");
+		let mut file = std::fs::File::open(&path)
+			.unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+		let spirv = ash::util::read_spv(&mut file).unwrap();
+		let shader = ShaderLayer::new_ref(base.clone(), spirv, 0);
+		// time stays at zero
+		shader.write().unwrap().animated = false;
+		compositor.new_layer(shader);
+	});
+	check("shader_layer", frame);
+}